@@ -0,0 +1,231 @@
+use std::cmp::Ordering;
+
+use crate::error::{Arity, LispError, Result};
+use crate::token::Span;
+use crate::value::{Procedure, Value};
+
+/// An evaluated argument, with the source it came from so errors can point
+/// at it.
+#[derive(Debug, Clone)]
+pub struct Arg {
+    pub value: Value,
+    pub span: Span,
+}
+
+/// A procedure written in Rust. `func` is only called with a number of
+/// arguments that `arity` accepts, and gets the span of the whole call.
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub func: fn(Span, &[Arg]) -> Result<Value>,
+}
+
+impl From<&Builtin> for Procedure {
+    fn from(builtin: &Builtin) -> Self {
+        Procedure::Builtin(*builtin)
+    }
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("+", Arity::AtLeast(0), add),
+    builtin("-", Arity::AtLeast(1), subtract),
+    builtin("*", Arity::AtLeast(0), multiply),
+    builtin("/", Arity::AtLeast(1), divide),
+    builtin("=", Arity::AtLeast(1), |_, args| {
+        compare(args, |o| o == Ordering::Equal)
+    }),
+    builtin("<", Arity::AtLeast(1), |_, args| {
+        compare(args, |o| o == Ordering::Less)
+    }),
+    builtin(">", Arity::AtLeast(1), |_, args| {
+        compare(args, |o| o == Ordering::Greater)
+    }),
+    builtin("<=", Arity::AtLeast(1), |_, args| {
+        compare(args, |o| o != Ordering::Greater)
+    }),
+    builtin(">=", Arity::AtLeast(1), |_, args| {
+        compare(args, |o| o != Ordering::Less)
+    }),
+    builtin("not", Arity::Exactly(1), |_, args| {
+        Ok(Value::Bool(!args[0].value.is_truthy()))
+    }),
+    builtin("equal?", Arity::Exactly(2), |_, args| {
+        Ok(Value::Bool(args[0].value == args[1].value))
+    }),
+    builtin("list", Arity::AtLeast(0), |_, args| {
+        Ok(Value::List(
+            args.iter().map(|arg| arg.value.clone()).collect(),
+        ))
+    }),
+    builtin("cons", Arity::Exactly(2), cons),
+    builtin("car", Arity::Exactly(1), car),
+    builtin("cdr", Arity::Exactly(1), cdr),
+    builtin("null?", Arity::Exactly(1), |_, args| {
+        Ok(Value::Bool(match &args[0].value {
+            Value::List(items) => items.is_empty(),
+            Value::Nil => true,
+            _ => false,
+        }))
+    }),
+];
+
+const fn builtin(
+    name: &'static str,
+    arity: Arity,
+    func: fn(Span, &[Arg]) -> Result<Value>,
+) -> Builtin {
+    Builtin { name, arity, func }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Integer(n) => n as f64,
+            Number::Float(n) => n,
+        }
+    }
+
+    fn is_zero(self) -> bool {
+        self.as_f64() == 0.0
+    }
+
+    /// Integers are compared exactly, anything else as floats.
+    fn compare(self, other: Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => Some(a.cmp(&b)),
+            (a, b) => a.as_f64().partial_cmp(&b.as_f64()),
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Integer(n) => Value::Integer(n),
+            Number::Float(n) => Value::Float(n),
+        }
+    }
+}
+
+fn number(arg: &Arg) -> Result<Number> {
+    match arg.value {
+        Value::Integer(n) => Ok(Number::Integer(n)),
+        Value::Float(n) => Ok(Number::Float(n)),
+        _ => Err(type_error("a number", arg)),
+    }
+}
+
+fn list(arg: &Arg) -> Result<&[Value]> {
+    match &arg.value {
+        Value::List(items) => Ok(items),
+        _ => Err(type_error("a list", arg)),
+    }
+}
+
+fn non_empty_list(arg: &Arg) -> Result<(&Value, &[Value])> {
+    list(arg)?
+        .split_first()
+        .ok_or_else(|| type_error("a non-empty list", arg))
+}
+
+fn type_error(expected: &'static str, arg: &Arg) -> LispError {
+    LispError::TypeError {
+        expected,
+        found: arg.value.clone(),
+        span: arg.span,
+    }
+}
+
+/// Combines `init` with every argument in turn. Integers stay integers
+/// unless a float is involved.
+fn fold(
+    span: Span,
+    init: Number,
+    args: &[Arg],
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Value> {
+    let mut result = init;
+    for arg in args {
+        result = match (result, number(arg)?) {
+            (Number::Integer(a), Number::Integer(b)) => {
+                Number::Integer(int_op(a, b).ok_or(LispError::IntegerOverflow { span })?)
+            }
+            (a, b) => Number::Float(float_op(a.as_f64(), b.as_f64())),
+        };
+    }
+
+    Ok(result.into())
+}
+
+fn add(span: Span, args: &[Arg]) -> Result<Value> {
+    fold(span, Number::Integer(0), args, i64::checked_add, |a, b| {
+        a + b
+    })
+}
+
+fn multiply(span: Span, args: &[Arg]) -> Result<Value> {
+    fold(span, Number::Integer(1), args, i64::checked_mul, |a, b| {
+        a * b
+    })
+}
+
+/// `(- x)` negates `x`, `(- x y z)` subtracts `y` and `z` from `x`.
+fn subtract(span: Span, args: &[Arg]) -> Result<Value> {
+    match args {
+        [first, rest @ ..] if !rest.is_empty() => {
+            fold(span, number(first)?, rest, i64::checked_sub, |a, b| a - b)
+        }
+        _ => fold(span, Number::Integer(0), args, i64::checked_sub, |a, b| {
+            a - b
+        }),
+    }
+}
+
+/// `(/ x)` is `1 / x`, `(/ x y z)` divides `x` by `y` and then `z`.
+/// Integers divide to integers, rounding towards zero.
+fn divide(span: Span, args: &[Arg]) -> Result<Value> {
+    let (init, divisors) = match args {
+        [first, rest @ ..] if !rest.is_empty() => (number(first)?, rest),
+        _ => (Number::Integer(1), args),
+    };
+
+    for divisor in divisors {
+        if number(divisor)?.is_zero() {
+            return Err(LispError::DivisionByZero { span: divisor.span });
+        }
+    }
+
+    fold(span, init, divisors, i64::checked_div, |a, b| a / b)
+}
+
+/// True if `holds` for every pair of neighbouring arguments.
+fn compare(args: &[Arg], holds: fn(Ordering) -> bool) -> Result<Value> {
+    let numbers = args.iter().map(number).collect::<Result<Vec<_>>>()?;
+    Ok(Value::Bool(
+        numbers
+            .windows(2)
+            .all(|pair| pair[0].compare(pair[1]).is_some_and(holds)),
+    ))
+}
+
+fn cons(_: Span, args: &[Arg]) -> Result<Value> {
+    let mut items = vec![args[0].value.clone()];
+    items.extend_from_slice(list(&args[1])?);
+    Ok(Value::List(items))
+}
+
+fn car(_: Span, args: &[Arg]) -> Result<Value> {
+    Ok(non_empty_list(&args[0])?.0.clone())
+}
+
+fn cdr(_: Span, args: &[Arg]) -> Result<Value> {
+    Ok(Value::List(non_empty_list(&args[0])?.1.to_vec()))
+}
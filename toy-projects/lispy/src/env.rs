@@ -0,0 +1,97 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::builtins;
+use crate::value::Value;
+
+/// A scope of variables. Lookups that miss go on to the parent scope, so a
+/// child sees everything its parents define.
+///
+/// Cloning is cheap and the clone shares its variables with the original,
+/// which is how closures see definitions made after they were created,
+/// including their own for recursion. That makes reference cycles, so
+/// closures are never freed. It's fine for a REPL.
+#[derive(Clone, Default)]
+pub struct Environment(Rc<RefCell<Frame>>);
+
+#[derive(Default)]
+struct Frame {
+    vars: HashMap<String, Value>,
+    parent: Option<Environment>,
+}
+
+impl Environment {
+    /// The top level environment, with every builtin procedure defined.
+    pub fn global() -> Self {
+        let env = Self::default();
+        for builtin in builtins::BUILTINS {
+            env.define(builtin.name, Value::Procedure(builtin.into()));
+        }
+
+        env
+    }
+
+    /// A new, empty scope inside this one.
+    pub fn child(&self) -> Self {
+        Environment(Rc::new(RefCell::new(Frame {
+            vars: HashMap::new(),
+            parent: Some(self.clone()),
+        })))
+    }
+
+    /// Sets `name` in this scope, shadowing any parent's `name`.
+    pub fn define(&self, name: &str, value: Value) {
+        self.0.borrow_mut().vars.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let frame = self.0.borrow();
+        match frame.vars.get(name) {
+            Some(value) => Some(value.clone()),
+            None => frame.parent.as_ref()?.get(name),
+        }
+    }
+}
+
+// Printing the variables could recurse forever through closures.
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Environment").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_sees_parent() {
+        let parent = Environment::default();
+        parent.define("x", Value::Integer(1));
+        let child = parent.child();
+
+        assert_eq!(child.get("x"), Some(Value::Integer(1)));
+        assert_eq!(child.get("y"), None);
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let parent = Environment::default();
+        parent.define("x", Value::Integer(1));
+        let child = parent.child();
+        child.define("x", Value::Integer(2));
+
+        assert_eq!(child.get("x"), Some(Value::Integer(2)));
+        assert_eq!(parent.get("x"), Some(Value::Integer(1)));
+    }
+
+    #[test]
+    fn test_global_has_builtins() {
+        assert!(matches!(
+            Environment::global().get("+"),
+            Some(Value::Procedure(_))
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+use std::error;
+use std::fmt;
+
+use crate::parse::ParseError;
+use crate::token::Span;
+use crate::value::Value;
+
+/// How many arguments a procedure takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(self, count: usize) -> bool {
+        match self {
+            Arity::Exactly(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exactly(1) => write!(f, "1 argument"),
+            Arity::Exactly(n) => write!(f, "{} arguments", n),
+            Arity::AtLeast(1) => write!(f, "at least 1 argument"),
+            Arity::AtLeast(n) => write!(f, "at least {} arguments", n),
+        }
+    }
+}
+
+/// Everything that can go wrong while reading or evaluating. `span` is the
+/// part of the source to blame, and is printed after the message.
+#[derive(Debug, Clone)]
+pub enum LispError {
+    Parse(ParseError),
+    UnboundSymbol {
+        name: String,
+        span: Span,
+    },
+    ArityMismatch {
+        name: String,
+        expected: Arity,
+        found: usize,
+        span: Span,
+    },
+    TypeError {
+        expected: &'static str,
+        found: Value,
+        span: Span,
+    },
+    NotAProcedure {
+        found: Value,
+        span: Span,
+    },
+    DivisionByZero {
+        span: Span,
+    },
+    IntegerOverflow {
+        span: Span,
+    },
+    /// Evaluation nested too deeply, usually because a procedure calls
+    /// itself without end.
+    RecursionLimit {
+        span: Span,
+    },
+    /// A special form like `let` or `define` that isn't written the way it
+    /// has to be.
+    BadForm {
+        form: &'static str,
+        reason: &'static str,
+        span: Span,
+    },
+}
+
+impl LispError {
+    pub fn span(&self) -> Span {
+        match self {
+            LispError::Parse(err) => err.span,
+            LispError::UnboundSymbol { span, .. }
+            | LispError::ArityMismatch { span, .. }
+            | LispError::TypeError { span, .. }
+            | LispError::NotAProcedure { span, .. }
+            | LispError::DivisionByZero { span }
+            | LispError::IntegerOverflow { span }
+            | LispError::RecursionLimit { span }
+            | LispError::BadForm { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for LispError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LispError::Parse(err) => write!(f, "{}", err.kind),
+            LispError::UnboundSymbol { name, .. } => write!(f, "unbound symbol `{}`", name),
+            LispError::ArityMismatch {
+                name,
+                expected,
+                found,
+                ..
+            } => write!(f, "`{}` takes {} but got {}", name, expected, found),
+            LispError::TypeError {
+                expected, found, ..
+            } => write!(
+                f,
+                "expected {} but found {} `{}`",
+                expected,
+                found.type_name(),
+                found
+            ),
+            LispError::NotAProcedure { found, .. } => write!(f, "`{}` is not a procedure", found),
+            LispError::DivisionByZero { .. } => write!(f, "division by zero"),
+            LispError::IntegerOverflow { .. } => write!(f, "integer overflow"),
+            LispError::RecursionLimit { .. } => write!(f, "too much recursion"),
+            LispError::BadForm { form, reason, .. } => write!(f, "bad `{}`: {}", form, reason),
+        }?;
+
+        write!(f, " at {}", self.span())
+    }
+}
+
+impl error::Error for LispError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LispError::Parse(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for LispError {
+    fn from(err: ParseError) -> Self {
+        LispError::Parse(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, LispError>;
@@ -1,5 +1,553 @@
-use crate::parse::Ast;
+use std::rc::Rc;
 
-pub fn eval(ast: Ast) -> isize {
-    ast.eval()
+use crate::builtins::Arg;
+use crate::env::Environment;
+use crate::error::{Arity, LispError, Result};
+use crate::parse::{Ast, AstKind};
+use crate::token::Span;
+use crate::value::{Lambda, Procedure, Value};
+
+/// How deeply evaluation can nest, counting procedure calls and the
+/// expressions inside them, before it gives up instead of overflowing the
+/// stack.
+const MAX_DEPTH: usize = 400;
+
+/// Evaluates `ast` in `env`. Definitions go into `env`, so they are still
+/// there for the next expression.
+pub fn eval(ast: &Ast, env: &Environment) -> Result<Value> {
+    eval_at(ast, env, 0)
+}
+
+/// `eval`, `depth` levels into evaluating an expression.
+fn eval_at(ast: &Ast, env: &Environment, depth: usize) -> Result<Value> {
+    if depth == MAX_DEPTH {
+        return Err(LispError::RecursionLimit { span: ast.span });
+    }
+
+    match &ast.kind {
+        AstKind::Integer(n) => Ok(Value::Integer(*n)),
+        AstKind::Float(n) => Ok(Value::Float(*n)),
+        AstKind::Str(string) => Ok(Value::Str(string.clone())),
+        AstKind::Bool(b) => Ok(Value::Bool(*b)),
+        AstKind::Symbol(name) => env.get(name).ok_or_else(|| LispError::UnboundSymbol {
+            name: name.clone(),
+            span: ast.span,
+        }),
+        AstKind::List(items) => eval_list(ast, items, env, depth + 1),
+    }
+}
+
+/// Calls `procedure`, checking first that it takes that many arguments.
+/// `span` is the call, for errors, and `depth` is how deep the call is.
+pub fn apply(procedure: &Procedure, args: &[Arg], span: Span, depth: usize) -> Result<Value> {
+    let arity = match procedure {
+        Procedure::Builtin(builtin) => builtin.arity,
+        Procedure::Lambda(lambda) => Arity::Exactly(lambda.params.len()),
+    };
+    if !arity.accepts(args.len()) {
+        return Err(LispError::ArityMismatch {
+            name: procedure.name().to_string(),
+            expected: arity,
+            found: args.len(),
+            span,
+        });
+    }
+
+    match procedure {
+        Procedure::Builtin(builtin) => (builtin.func)(span, args),
+        Procedure::Lambda(lambda) => {
+            let env = lambda.env.child();
+            for (param, arg) in lambda.params.iter().zip(args) {
+                env.define(param, arg.value.clone());
+            }
+
+            eval_body(&lambda.body, &env, depth)
+        }
+    }
+}
+
+/// Special forms first, then procedure calls. Special forms can't be
+/// shadowed: `(define if 1)` works, but `(if ...)` is still an `if`.
+fn eval_list(list: &Ast, items: &[Ast], env: &Environment, depth: usize) -> Result<Value> {
+    let (head, args) = match items.split_first() {
+        Some(split) => split,
+        None => return Ok(Value::List(Vec::new())),
+    };
+
+    match symbol(head) {
+        Some("quote") => return eval_quote(list, args),
+        Some("if") => return eval_if(list, args, env, depth),
+        Some("define") => return eval_define(list, args, env, depth),
+        Some("lambda") => return eval_lambda(list, args, env),
+        Some("let") => return eval_let(list, args, env, depth),
+        Some("cond") => return eval_cond(args, env, depth),
+        Some("begin") => return eval_body(args, env, depth),
+        _ => {}
+    }
+
+    let procedure = match eval_at(head, env, depth)? {
+        Value::Procedure(procedure) => procedure,
+        found => {
+            return Err(LispError::NotAProcedure {
+                found,
+                span: head.span,
+            })
+        }
+    };
+    let args = args
+        .iter()
+        .map(|arg| {
+            Ok(Arg {
+                value: eval_at(arg, env, depth)?,
+                span: arg.span,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    apply(&procedure, &args, list.span, depth)
+}
+
+/// Evaluates every expression in order and returns the last value, or nil
+/// if there are none.
+fn eval_body(body: &[Ast], env: &Environment, depth: usize) -> Result<Value> {
+    let mut value = Value::Nil;
+    for ast in body {
+        value = eval_at(ast, env, depth)?;
+    }
+
+    Ok(value)
+}
+
+/// `(quote expr)`
+fn eval_quote(form: &Ast, args: &[Ast]) -> Result<Value> {
+    match args {
+        [quoted] => Ok(quote(quoted)),
+        _ => Err(bad_form("quote", "expected one expression", form.span)),
+    }
+}
+
+fn quote(ast: &Ast) -> Value {
+    match &ast.kind {
+        AstKind::Integer(n) => Value::Integer(*n),
+        AstKind::Float(n) => Value::Float(*n),
+        AstKind::Str(string) => Value::Str(string.clone()),
+        AstKind::Bool(b) => Value::Bool(*b),
+        AstKind::Symbol(name) => Value::Symbol(name.clone()),
+        AstKind::List(items) => Value::List(items.iter().map(quote).collect()),
+    }
+}
+
+/// `(if test then)` or `(if test then else)`
+fn eval_if(form: &Ast, args: &[Ast], env: &Environment, depth: usize) -> Result<Value> {
+    let (test, then, otherwise) = match args {
+        [test, then] => (test, then, None),
+        [test, then, otherwise] => (test, then, Some(otherwise)),
+        _ => {
+            return Err(bad_form(
+                "if",
+                "expected a test, a then branch and maybe an else branch",
+                form.span,
+            ))
+        }
+    };
+
+    if eval_at(test, env, depth)?.is_truthy() {
+        eval_at(then, env, depth)
+    } else {
+        otherwise.map_or(Ok(Value::Nil), |otherwise| eval_at(otherwise, env, depth))
+    }
+}
+
+/// `(define name expr)` or `(define (name params...) body...)`. Returns the
+/// name.
+fn eval_define(form: &Ast, args: &[Ast], env: &Environment, depth: usize) -> Result<Value> {
+    let (target, rest) = args
+        .split_first()
+        .ok_or_else(|| bad_form("define", "expected a name", form.span))?;
+
+    let name = match (&target.kind, rest) {
+        (AstKind::Symbol(name), [expr]) => {
+            let value = eval_at(expr, env, depth)?;
+            env.define(name, value);
+            name
+        }
+        (AstKind::Symbol(_), _) => {
+            return Err(bad_form("define", "expected one value", form.span));
+        }
+        (AstKind::List(signature), body) => {
+            let name = signature
+                .first()
+                .and_then(symbol)
+                .ok_or_else(|| bad_form("define", "expected a procedure name", target.span))?;
+            let lambda = make_lambda(
+                "define",
+                Some(name.to_string()),
+                &signature[1..],
+                body,
+                form.span,
+                env,
+            )?;
+            env.define(name, lambda);
+            name
+        }
+        _ => {
+            return Err(bad_form(
+                "define",
+                "expected a name or (name params...)",
+                target.span,
+            ))
+        }
+    };
+
+    Ok(Value::Symbol(name.to_string()))
+}
+
+/// `(lambda (params...) body...)`
+fn eval_lambda(form: &Ast, args: &[Ast], env: &Environment) -> Result<Value> {
+    match args.split_first() {
+        Some((
+            Ast {
+                kind: AstKind::List(params),
+                ..
+            },
+            body,
+        )) => make_lambda("lambda", None, params, body, form.span, env),
+        Some((other, _)) => Err(bad_form("lambda", "expected a parameter list", other.span)),
+        None => Err(bad_form("lambda", "expected a parameter list", form.span)),
+    }
+}
+
+/// A closure over `env`. `form` and `span` are for errors.
+fn make_lambda(
+    form: &'static str,
+    name: Option<String>,
+    params: &[Ast],
+    body: &[Ast],
+    span: Span,
+    env: &Environment,
+) -> Result<Value> {
+    let params = params
+        .iter()
+        .map(|param| {
+            symbol(param)
+                .map(str::to_string)
+                .ok_or_else(|| bad_form(form, "parameters must be symbols", param.span))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if body.is_empty() {
+        return Err(bad_form(form, "expected a body", span));
+    }
+
+    Ok(Value::Procedure(Procedure::Lambda(Rc::new(Lambda {
+        name,
+        params,
+        body: body.to_vec(),
+        env: env.clone(),
+    }))))
+}
+
+/// `(let ((name expr)...) body...)`. Every `expr` is evaluated in the outer
+/// scope, so the bindings can't see each other.
+fn eval_let(form: &Ast, args: &[Ast], env: &Environment, depth: usize) -> Result<Value> {
+    let bindings = match args.first().map(|bindings| &bindings.kind) {
+        Some(AstKind::List(bindings)) => bindings,
+        Some(_) => {
+            return Err(bad_form(
+                "let",
+                "expected a list of (name value) bindings",
+                args[0].span,
+            ))
+        }
+        None => return Err(bad_form("let", "expected bindings", form.span)),
+    };
+    let body = &args[1..];
+    if body.is_empty() {
+        return Err(bad_form("let", "expected a body", form.span));
+    }
+
+    let scope = env.child();
+    for binding in bindings {
+        let (name, expr) = match &binding.kind {
+            AstKind::List(pair) => match pair.as_slice() {
+                [name, expr] => symbol(name).map(|name| (name, expr)),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| bad_form("let", "expected a (name value) binding", binding.span))?;
+
+        scope.define(name, eval_at(expr, env, depth)?);
+    }
+
+    eval_body(body, &scope, depth)
+}
+
+/// `(cond (test body...)... (else body...))`. The first clause whose test
+/// holds is run, and a clause without a body returns its test's value. Nil
+/// if none hold.
+fn eval_cond(clauses: &[Ast], env: &Environment, depth: usize) -> Result<Value> {
+    for clause in clauses {
+        let (test, body) = match &clause.kind {
+            AstKind::List(items) => items.split_first(),
+            _ => None,
+        }
+        .ok_or_else(|| bad_form("cond", "expected a (test body...) clause", clause.span))?;
+
+        let value = match symbol(test) {
+            Some("else") => Value::Bool(true),
+            _ => eval_at(test, env, depth)?,
+        };
+
+        if value.is_truthy() {
+            return if body.is_empty() {
+                Ok(value)
+            } else {
+                eval_body(body, env, depth)
+            };
+        }
+    }
+
+    Ok(Value::Nil)
+}
+
+fn symbol(ast: &Ast) -> Option<&str> {
+    match &ast.kind {
+        AstKind::Symbol(name) => Some(name),
+        _ => None,
+    }
+}
+
+fn bad_form(form: &'static str, reason: &'static str, span: Span) -> LispError {
+    LispError::BadForm { form, reason, span }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    /// Evaluates every expression in `source` in a fresh global environment
+    /// and returns the last value.
+    fn run(source: &str) -> Result<Value> {
+        let env = Environment::global();
+        let mut value = Value::Nil;
+        for ast in parse(source).unwrap() {
+            value = eval(&ast, &env)?;
+        }
+
+        Ok(value)
+    }
+
+    fn show(source: &str) -> String {
+        run(source).unwrap().to_string()
+    }
+
+    fn error(source: &str) -> LispError {
+        run(source).unwrap_err()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(show("(+ 1 2 3)"), "6");
+        assert_eq!(show("(- (* 4 5) (/ 9 3))"), "17");
+        assert_eq!(show("(- 5)"), "-5");
+        assert_eq!(show("(+ 1 2.5)"), "3.5");
+        assert_eq!(show("(/ 7 2)"), "3");
+        assert_eq!(show("(/ 7.0 2)"), "3.5");
+        assert_eq!(show("(+)"), "0");
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(show("(< 1 2 3)"), "#t");
+        assert_eq!(show("(< 1 3 2)"), "#f");
+        assert_eq!(show("(= 2 2.0)"), "#t");
+        assert_eq!(show("(>= 3 3 1)"), "#t");
+        assert_eq!(show("(not (> 1 2))"), "#t");
+    }
+
+    #[test]
+    fn test_atoms() {
+        assert_eq!(show("\"hi\""), "\"hi\"");
+        assert_eq!(show("#f"), "#f");
+        assert_eq!(show("'sym"), "sym");
+        assert_eq!(show("'(1 (a \"b\") #t)"), "(1 (a \"b\") #t)");
+    }
+
+    #[test]
+    fn test_define() {
+        assert_eq!(show("(define x 5)"), "x");
+        assert_eq!(show("(define x 5) (* x x)"), "25");
+        assert_eq!(show("(define (square x) (* x x)) (square 7)"), "49");
+        assert_eq!(show("(define (f) 1) f"), "#<procedure f>");
+    }
+
+    #[test]
+    fn test_recursion() {
+        let source = "
+            (define (fact n)
+              (if (<= n 1)
+                  1
+                  (* n (fact (- n 1)))))
+            (fact 10)";
+
+        assert_eq!(show(source), "3628800");
+    }
+
+    #[test]
+    fn test_closures() {
+        let source = "
+            (define (make-adder n) (lambda (x) (+ x n)))
+            (define add2 (make-adder 2))
+            (define n 100)
+            (add2 3)";
+
+        assert_eq!(show(source), "5");
+        assert_eq!(show("((lambda (x y) (- x y)) 10 4)"), "6");
+        assert_eq!(show("(lambda (x) x)"), "#<procedure>");
+    }
+
+    #[test]
+    fn test_procedures_are_values() {
+        let source = "
+            (define (twice f x) (f (f x)))
+            (twice (lambda (x) (* x 3)) 2)";
+
+        assert_eq!(show(source), "18");
+        assert_eq!(show("(define (twice f x) (f (f x))) (twice - 5)"), "5");
+    }
+
+    #[test]
+    fn test_let() {
+        assert_eq!(show("(let ((x 2) (y 3)) (* x y))"), "6");
+        // The bindings see the outer x, not each other.
+        assert_eq!(show("(define x 1) (let ((x 10) (y x)) y)"), "1");
+        // And they don't leak out.
+        assert_eq!(show("(define x 1) (let ((x 10)) x) x"), "1");
+    }
+
+    #[test]
+    fn test_if_cond_begin() {
+        assert_eq!(show("(if (< 1 2) 'yes 'no)"), "yes");
+        assert_eq!(show("(if #f 'yes)"), "nil");
+        assert_eq!(show("(if '() 'yes 'no)"), "yes");
+
+        let source = "
+            (define (sign n)
+              (cond ((< n 0) 'negative)
+                    ((= n 0) 'zero)
+                    (else 'positive)))
+            (list (sign -5) (sign 0) (sign 5))";
+        assert_eq!(show(source), "(negative zero positive)");
+        assert_eq!(show("(cond (#f 1))"), "nil");
+        assert_eq!(show("(cond ((+ 1 1)))"), "2");
+
+        assert_eq!(show("(begin (define x 1) (define y 2) (+ x y))"), "3");
+        assert_eq!(show("(begin)"), "nil");
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(show("(list 1 2 3)"), "(1 2 3)");
+        assert_eq!(show("(cons 0 '(1 2))"), "(0 1 2)");
+        assert_eq!(show("(car '(1 2 3))"), "1");
+        assert_eq!(show("(cdr '(1 2 3))"), "(2 3)");
+        assert_eq!(show("(null? (cdr '(1)))"), "#t");
+        assert_eq!(show("(equal? (list 1 'a) '(1 a))"), "#t");
+        assert_eq!(show("()"), "()");
+    }
+
+    #[test]
+    fn test_unbound_symbol() {
+        let err = error("(+ 1 nope)");
+
+        assert!(matches!(&err, LispError::UnboundSymbol { name, .. } if name == "nope"));
+        assert_eq!((err.span().start, err.span().end), (5, 9));
+    }
+
+    #[test]
+    fn test_arity_mismatch() {
+        let err = error("(define (f x) x) (f 1 2)");
+
+        assert_eq!(err.to_string(), "`f` takes 1 argument but got 2 at 1:18");
+        assert_eq!(err.span().start, 17);
+        assert!(matches!(error("(car)"), LispError::ArityMismatch { .. }));
+    }
+
+    #[test]
+    fn test_type_error_points_at_argument() {
+        let err = error("(+ 1 \"two\")");
+
+        assert_eq!(
+            err.to_string(),
+            "expected a number but found string `\"two\"` at 1:6"
+        );
+        assert_eq!((err.span().start, err.span().end), (5, 10));
+        assert!(matches!(error("(car '())"), LispError::TypeError { .. }));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let err = error("(/ 10 2 0)");
+
+        assert!(matches!(err, LispError::DivisionByZero { .. }));
+        assert_eq!(err.span().start, 8);
+        assert!(matches!(
+            error("(/ 1.5 0.0)"),
+            LispError::DivisionByZero { .. }
+        ));
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        assert!(matches!(
+            error("(* 9223372036854775807 2)"),
+            LispError::IntegerOverflow { .. }
+        ));
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let err = error("(define (f n) (f n)) (f 1)");
+        assert!(matches!(err, LispError::RecursionLimit { .. }));
+
+        let source = "
+            (define (fact n)
+              (if (<= n 1)
+                  1
+                  (* n (fact (- n 1)))))
+            (fact 3000)";
+        assert!(matches!(error(source), LispError::RecursionLimit { .. }));
+    }
+
+    #[test]
+    fn test_not_a_procedure() {
+        let err = error("(1 2)");
+
+        assert_eq!(err.to_string(), "`1` is not a procedure at 1:2");
+    }
+
+    #[test]
+    fn test_bad_forms() {
+        for source in [
+            "(if)",
+            "(define)",
+            "(define 1 2)",
+            "(define x 1 2)",
+            "(lambda x x)",
+            "(lambda (1) 1)",
+            "(lambda (x))",
+            "(let x x)",
+            "(let ((x)) x)",
+            "(cond 1)",
+            "(quote)",
+        ] {
+            assert!(
+                matches!(error(source), LispError::BadForm { .. }),
+                "{}",
+                source
+            );
+        }
+    }
 }
@@ -1,37 +1,51 @@
+mod builtins;
+mod env;
+mod error;
 mod eval;
 mod parse;
 mod repl;
+mod token;
+mod value;
 
 use crossterm::{terminal, Result};
+use env::Environment;
 use eval::eval;
 use parse::parse;
-use repl::{get_input, print_eval, print_prompt, print_ver, ReplInput};
+use repl::{get_input, print_error, print_eval, print_prompt, print_ver, ReplInput};
 use std::io::{self, Stdout};
 
 fn run(stdout: &mut Stdout) -> Result<()> {
     terminal::enable_raw_mode()?;
     print_ver(stdout)?;
+    let env = Environment::global();
 
     'repl: loop {
         print_prompt(stdout)?;
 
-        let input = get_input(stdout)?;
-        let source;
-
-        match input {
-            ReplInput::String(input) => source = input,
+        let source = match get_input(stdout)? {
+            ReplInput::String(input) => input,
             ReplInput::Skip => continue,
             ReplInput::Exit => break 'repl,
         };
 
-        // Here is where we want to start parsing and evaluating.
-        //
-        // let ast = parse(source)?;
-        // let res = eval(ast)?;
+        let exprs = match parse(&source) {
+            Ok(exprs) => exprs,
+            Err(err) => {
+                print_error(stdout, &source, &err.into())?;
+                continue;
+            }
+        };
 
-        let ast = parse(source.clone()).unwrap();
-        let res = eval(ast);
-        print_eval(stdout, res.to_string())?;
+        // Nothing after an expression that fails is evaluated.
+        for ast in exprs {
+            match eval(&ast, &env) {
+                Ok(value) => print_eval(stdout, value.to_string())?,
+                Err(err) => {
+                    print_error(stdout, &source, &err)?;
+                    break;
+                }
+            }
+        }
     }
 
     terminal::disable_raw_mode()?;
@@ -1,156 +1,334 @@
-use core::panic;
+use std::error;
+use std::fmt;
 
-// Lets parse Polish Notation Grammar.
+use crate::token::{Span, Token, TokenKind, Tokenizer};
 
-/// Create a parser and parses the source into an Ast.
-pub fn parse(source: String) -> Option<Ast> {
-    let mut parser = Parser::new(source);
-    parser.parse()
+// Lets read S-expressions.
+//
+//  (+ 1 (* 2 3))
+//      List [
+//        Symbol("+"),
+//        Integer(1),
+//        List [
+//          Symbol("*"),
+//          Integer(2),
+//          Integer(3),
+//        ],
+//      ]
+//
+//  'x is read as (quote x), so quoting needs no node of its own.
+
+/// How deeply lists and quotes can nest before reading gives up, so that
+/// something like 50,000 `(`s is an error instead of a stack overflow.
+const MAX_DEPTH: usize = 256;
+
+/// Reads every expression in the source, in order.
+pub fn parse(source: &str) -> Result<Vec<Ast>, ParseError> {
+    let mut reader = Reader {
+        tokens: Tokenizer::new(source),
+        depth: 0,
+    };
+
+    let mut exprs = Vec::new();
+    while let Some(token) = reader.next_token()? {
+        exprs.push(reader.read(token)?);
+    }
+
+    Ok(exprs)
 }
 
-struct Parser {
-    col: usize,
-    input: String,
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedCloseParen,
+    UnclosedList,
+    NothingToQuote,
+    UnterminatedString,
+    InvalidEscape(char),
+    InvalidNumber(String),
+    IntegerOutOfRange(String),
+    UnknownLiteral(String),
+    TooDeep,
 }
 
-impl Parser {
-    fn new(input: String) -> Self {
-        Self { col: 0, input }
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedCloseParen => write!(f, "unexpected `)`"),
+            ParseErrorKind::UnclosedList => write!(f, "this `(` is never closed"),
+            ParseErrorKind::NothingToQuote => write!(f, "nothing to quote after `'`"),
+            ParseErrorKind::UnterminatedString => write!(f, "unterminated string"),
+            ParseErrorKind::InvalidEscape(c) => write!(f, "unknown escape `\\{}` in string", c),
+            ParseErrorKind::InvalidNumber(text) => write!(f, "invalid number `{}`", text),
+            ParseErrorKind::IntegerOutOfRange(text) => {
+                write!(f, "integer `{}` is out of range", text)
+            }
+            ParseErrorKind::UnknownLiteral(text) => write!(f, "unknown literal `{}`", text),
+            ParseErrorKind::TooDeep => {
+                write!(f, "nested more than {} levels deep", MAX_DEPTH)
+            }
+        }
     }
+}
 
-    fn peak(&self) -> char {
-        //dbg!(self.col);
-        self.input.chars().nth(self.col).unwrap()
-    }
+/// Why the source couldn't be read, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
 
-    fn is_eol(&self) -> bool {
-        self.col == self.input.len()
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.span)
     }
+}
 
-    fn next(&mut self) -> char {
-        let ret = self.input.chars().nth(self.col).unwrap();
-        self.col += 1;
-        ret
-    }
+impl error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AstKind {
+    Integer(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    Symbol(String),
+    List(Vec<Ast>),
+}
+
+/// An expression and the source it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ast {
+    pub kind: AstKind,
+    pub span: Span,
+}
 
-    /// Creates a string and consume chars from the input
-    /// while each char adheres to the pattern given in the `test`
-    /// function.
-    fn consume_while<F>(&mut self, test: F) -> String
-    where
-        F: Fn(char) -> bool,
-    {
-        let mut res = String::new();
-        while !self.is_eol() && test(self.peak()) {
-            res.push(self.next());
+/// Prints the expression back as source. Quotes come out as `'x`.
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            AstKind::Integer(n) => write!(f, "{}", n),
+            AstKind::Float(n) => write!(f, "{:?}", n),
+            AstKind::Str(string) => write!(f, "{:?}", string),
+            AstKind::Bool(true) => write!(f, "#t"),
+            AstKind::Bool(false) => write!(f, "#f"),
+            AstKind::Symbol(name) => write!(f, "{}", name),
+            AstKind::List(items) => match items.as_slice() {
+                [quote, quoted] if quote.kind == AstKind::Symbol("quote".to_string()) => {
+                    write!(f, "'{}", quoted)
+                }
+                _ => {
+                    write!(f, "(")?;
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, " ")?;
+                        }
+                        write!(f, "{}", item)?;
+                    }
+                    write!(f, ")")
+                }
+            },
         }
+    }
+}
+
+/// Builds expressions out of the tokens of one source string.
+struct Reader<'a> {
+    tokens: Tokenizer<'a>,
+    /// How many lists and quotes the token being read is inside of.
+    depth: usize,
+}
 
-        res
+impl<'a> Reader<'a> {
+    fn next_token(&mut self) -> Result<Option<Token>, ParseError> {
+        self.tokens.next().transpose()
     }
 
-    fn consume_number(&mut self) -> String {
-        self.consume_while(|c| c.is_numeric())
+    /// Reads the expression that starts with `token`.
+    fn read(&mut self, token: Token) -> Result<Ast, ParseError> {
+        let kind = match token.kind {
+            TokenKind::LeftParen => return self.nested(token.span, Self::read_list),
+            TokenKind::Quote => return self.nested(token.span, Self::read_quote),
+            TokenKind::RightParen => {
+                return Err(ParseError {
+                    kind: ParseErrorKind::UnexpectedCloseParen,
+                    span: token.span,
+                })
+            }
+            TokenKind::Integer(n) => AstKind::Integer(n),
+            TokenKind::Float(n) => AstKind::Float(n),
+            TokenKind::Str(string) => AstKind::Str(string),
+            TokenKind::Bool(b) => AstKind::Bool(b),
+            TokenKind::Symbol(name) => AstKind::Symbol(name),
+        };
+
+        Ok(Ast {
+            kind,
+            span: token.span,
+        })
     }
 
-    fn consume_whitespace(&mut self) {
-        self.consume_while(|c| c.is_whitespace());
+    /// Reads what `open` starts with `read`, one level deeper.
+    fn nested(
+        &mut self,
+        open: Span,
+        read: fn(&mut Self, Span) -> Result<Ast, ParseError>,
+    ) -> Result<Ast, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ParseError {
+                kind: ParseErrorKind::TooDeep,
+                span: open,
+            });
+        }
+
+        self.depth += 1;
+        let ast = read(self, open);
+        self.depth -= 1;
+        ast
     }
 
-    fn parse(&mut self) -> Option<Ast> {
-        let mut ast = None;
+    /// Reads the rest of a list whose `(` is at `open`.
+    fn read_list(&mut self, open: Span) -> Result<Ast, ParseError> {
+        let mut items = Vec::new();
 
-        if self.is_eol() {
-            return ast;
-        }
+        loop {
+            let token = self.next_token()?.ok_or(ParseError {
+                kind: ParseErrorKind::UnclosedList,
+                span: open,
+            })?;
+
+            if token.kind == TokenKind::RightParen {
+                return Ok(Ast {
+                    kind: AstKind::List(items),
+                    span: open.to(token.span),
+                });
+            }
 
-        self.consume_whitespace();
-        //dbg!(&self.input[self.col..]);
-
-        if self.peak().is_numeric() {
-            let number = self.consume_number();
-            ast = Some(Ast::Value(number.parse().unwrap()));
-        } else {
-            let operator = match self.next() {
-                '+' => Operator::Add,
-                '-' => Operator::Subtract,
-                '*' => Operator::Multiply,
-                '/' => Operator::Divide,
-                _ => panic!("Aiyo"),
-            };
-
-            ast = Some(Ast::Expression(Expr {
-                operator,
-                children: vec![self.parse().unwrap(), self.parse().unwrap()],
-            }));
+            items.push(self.read(token)?);
         }
+    }
 
-        //dbg!(&ast);
-        ast
+    /// Reads the expression after a `'` at `quote` as `(quote <expr>)`.
+    fn read_quote(&mut self, quote: Span) -> Result<Ast, ParseError> {
+        let token = match self.next_token()? {
+            Some(token) if token.kind != TokenKind::RightParen => token,
+            _ => {
+                return Err(ParseError {
+                    kind: ParseErrorKind::NothingToQuote,
+                    span: quote,
+                })
+            }
+        };
+
+        let quoted = self.read(token)?;
+        let span = quote.to(quoted.span);
+        let quote = Ast {
+            kind: AstKind::Symbol("quote".to_string()),
+            span: quote,
+        };
+
+        Ok(Ast {
+            kind: AstKind::List(vec![quote, quoted]),
+            span,
+        })
     }
 }
 
-// 8
-// Ast::Value(8) -> return 8
-//
-//
-//  + 3 5
-//      Ast::Expression(Expr {
-//        operator: Operator::Add
-//        children: [
-//          Ast::Value(3),
-//          Ast::Value(5),
-//        ]})        -> return 8
-//
-//
-//  + + 3 5 3
-//      Ast::Expression(Expr {
-//        operator: Operator::Add
-//        children: [
-//          Ast::Expression(Expr {
-//              operator: Operator::Add,
-//              children: [
-//                Ast::Value(3),
-//                Ast::Value(5),
-//              ]
-//          }),                     -> return 8
-//          Ast::Value(3),
-//        ]})                       -> return 11
-
-#[derive(Debug)]
-enum Operator {
-    Add,
-    Subtract,
-    Multiply,
-    Divide,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[derive(Debug)]
-pub struct Expr {
-    operator: Operator,
-    children: Vec<Ast>,
-}
+    fn read(source: &str) -> Ast {
+        let mut exprs = parse(source).unwrap();
+        assert_eq!(exprs.len(), 1);
+        exprs.remove(0)
+    }
 
-#[derive(Debug)]
-pub enum Ast {
-    Value(isize),
-    Expression(Expr),
-}
+    fn error(source: &str) -> ParseError {
+        parse(source).unwrap_err()
+    }
 
-impl Ast {
-    pub fn eval(&self) -> isize {
-        let expr = match self {
-            Ast::Expression(expr) => expr,
-            Ast::Value(n) => {
-                return *n;
-            }
+    #[test]
+    fn test_nested_lists() {
+        let ast = read("(+ 1 (* 2 3))");
+
+        let items = match &ast.kind {
+            AstKind::List(items) => items,
+            kind => panic!("expected a list, got {:?}", kind),
         };
-        let mut operands = expr.children.iter().map(|n| n.eval());
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].kind, AstKind::Symbol("+".to_string()));
+        assert_eq!(items[1].kind, AstKind::Integer(1));
+        assert_eq!(items[2].to_string(), "(* 2 3)");
+        assert_eq!((items[2].span.start, items[2].span.end), (5, 12));
+        assert_eq!((ast.span.start, ast.span.end), (0, 13));
+    }
+
+    #[test]
+    fn test_quote() {
+        let ast = read("'(a \"b\" 1.5 #t)");
 
-        match expr.operator {
-            Operator::Add => operands.sum(),
-            Operator::Subtract => operands.next().unwrap() - operands.next().unwrap(),
-            Operator::Multiply => operands.next().unwrap() * operands.next().unwrap(),
-            Operator::Divide => operands.next().unwrap() / operands.next().unwrap(),
+        assert_eq!(ast.to_string(), "'(a \"b\" 1.5 #t)");
+        match &ast.kind {
+            AstKind::List(items) => {
+                assert_eq!(items[0].kind, AstKind::Symbol("quote".to_string()));
+                assert_eq!((items[0].span.start, items[0].span.end), (0, 1));
+            }
+            kind => panic!("expected a list, got {:?}", kind),
         }
+        assert_eq!((ast.span.start, ast.span.end), (0, 15));
+    }
+
+    #[test]
+    fn test_several_expressions() {
+        let exprs = parse("1\n(f x)\n'y").unwrap();
+        let lines: Vec<usize> = exprs.iter().map(|expr| expr.span.line).collect();
+
+        assert_eq!(lines, vec![1, 2, 3]);
+        assert!(parse("  ; only a comment").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_empty_list() {
+        assert_eq!(read("()").kind, AstKind::List(Vec::new()));
+    }
+
+    #[test]
+    fn test_unclosed_list() {
+        let err = error("(+ 1\n  (* 2 3)");
+
+        assert_eq!(err.kind, ParseErrorKind::UnclosedList);
+        assert_eq!((err.span.line, err.span.column), (1, 1));
+    }
+
+    #[test]
+    fn test_unexpected_close_paren() {
+        let err = error("(+ 1 2))");
+
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedCloseParen);
+        assert_eq!(err.span.column, 8);
+        assert_eq!(err.to_string(), "unexpected `)` at 1:8");
+    }
+
+    #[test]
+    fn test_nothing_to_quote() {
+        assert_eq!(error("'").kind, ParseErrorKind::NothingToQuote);
+        assert_eq!(error("(a ')").kind, ParseErrorKind::NothingToQuote);
+    }
+
+    #[test]
+    fn test_too_deep() {
+        let err = error(&format!("(+ 1 {}", "(".repeat(50_000)));
+
+        assert_eq!(err.kind, ParseErrorKind::TooDeep);
+        assert_eq!(err.span.column, 5 + MAX_DEPTH);
+        assert_eq!(error(&"'".repeat(50_000)).kind, ParseErrorKind::TooDeep);
+
+        let deepest = format!("{}{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert!(parse(&deepest).is_ok());
+    }
+
+    #[test]
+    fn test_tokenizer_errors_come_through() {
+        assert_eq!(error("(\"open)").kind, ParseErrorKind::UnterminatedString);
     }
 }
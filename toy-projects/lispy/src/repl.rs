@@ -1,5 +1,8 @@
 use std::io::{self, Stdout, Write};
 
+use crate::error::LispError;
+use crate::token::Span;
+
 use crossterm::{
     cursor::{
         position, MoveLeft, MoveRight, MoveTo, MoveToColumn, MoveToNextLine, RestorePosition,
@@ -24,110 +27,106 @@ pub(crate) fn get_input(stdout: &mut Stdout) -> Result<ReplInput> {
 
     let mut buffer = String::new();
     loop {
-        match read()? {
-            Event::Key(KeyEvent { code, modifiers }) => {
-                if modifiers == KeyModifiers::CONTROL && code == KeyCode::Char('c') {
-                    return Ok(ReplInput::Exit);
+        if let Event::Key(KeyEvent { code, modifiers }) = read()? {
+            if modifiers == KeyModifiers::CONTROL && code == KeyCode::Char('c') {
+                return Ok(ReplInput::Exit);
+            }
+
+            match code {
+                KeyCode::Enter => {
+                    break;
                 }
+                KeyCode::Char(c) => {
+                    if cursor_pos == end_of_buffer_pos {
+                        buffer.push(c);
+                        stdout.queue(Print(c))?;
+                    } else {
+                        let insert_idx = (cursor_pos - start_of_buffer_pos) as usize;
+                        buffer.insert(insert_idx, c);
 
-                match code {
-                    KeyCode::Enter => {
-                        break;
-                    }
-                    KeyCode::Char(c) => {
-                        if cursor_pos == end_of_buffer_pos {
-                            buffer.push(c);
-                            stdout.queue(Print(c))?;
-                        } else {
-                            let insert_idx = (cursor_pos - start_of_buffer_pos) as usize;
-                            buffer.insert(insert_idx, c);
-
-                            stdout
-                                .queue(SavePosition)?
-                                .queue(Print(&buffer[insert_idx..]))?
-                                .queue(RestorePosition)?
-                                .queue(MoveRight(1))?;
-                        }
-
-                        cursor_pos += 1;
-                        end_of_buffer_pos += 1;
-
-                        stdout.flush()?;
+                        stdout
+                            .queue(SavePosition)?
+                            .queue(Print(&buffer[insert_idx..]))?
+                            .queue(RestorePosition)?
+                            .queue(MoveRight(1))?;
                     }
-                    KeyCode::Backspace => {
-                        if buffer.is_empty() {
-                            continue;
-                        }
 
-                        stdout
-                            .queue(MoveLeft(1))?
-                            .queue(Print(" "))?
-                            .queue(MoveLeft(1))?;
-
-                        if cursor_pos == end_of_buffer_pos {
-                            buffer.pop();
-                        } else {
-                            let remove_idx = (cursor_pos - start_of_buffer_pos - 1) as usize;
-                            buffer.remove(remove_idx);
-
-                            stdout
-                                .queue(SavePosition)?
-                                .queue(Print(format!("{} ", &buffer[remove_idx..])))?
-                                .queue(RestorePosition)?;
-                        }
-
-                        end_of_buffer_pos -= 1;
-                        cursor_pos -= 1;
-
-                        stdout.flush()?;
+                    cursor_pos += 1;
+                    end_of_buffer_pos += 1;
+
+                    stdout.flush()?;
+                }
+                KeyCode::Backspace => {
+                    if buffer.is_empty() {
+                        continue;
                     }
-                    KeyCode::Delete => {
-                        if cursor_pos == end_of_buffer_pos {
-                            continue;
-                        }
 
-                        let remove_idx = (cursor_pos - start_of_buffer_pos) as usize;
+                    stdout
+                        .queue(MoveLeft(1))?
+                        .queue(Print(" "))?
+                        .queue(MoveLeft(1))?;
+
+                    if cursor_pos == end_of_buffer_pos {
+                        buffer.pop();
+                    } else {
+                        let remove_idx = (cursor_pos - start_of_buffer_pos - 1) as usize;
                         buffer.remove(remove_idx);
 
                         stdout
                             .queue(SavePosition)?
                             .queue(Print(format!("{} ", &buffer[remove_idx..])))?
                             .queue(RestorePosition)?;
-                        stdout.flush()?;
                     }
-                    KeyCode::Left => {
-                        if cursor_pos == start_of_buffer_pos {
-                            continue;
-                        }
 
-                        cursor_pos -= 1;
-                        stdout.execute(MoveLeft(1))?;
-                    }
-                    KeyCode::Right => {
-                        if cursor_pos == end_of_buffer_pos {
-                            continue;
-                        }
+                    end_of_buffer_pos -= 1;
+                    cursor_pos -= 1;
 
-                        cursor_pos += 1;
-                        stdout.execute(MoveRight(1))?;
+                    stdout.flush()?;
+                }
+                KeyCode::Delete => {
+                    if cursor_pos == end_of_buffer_pos {
+                        continue;
                     }
-                    KeyCode::Home => {
-                        let move_to_col = start_of_buffer_pos + 1;
-                        stdout.execute(MoveToColumn(move_to_col))?; // cursor::position is 0 indexed, but Column is 1 indexed.
-                        cursor_pos = start_of_buffer_pos;
+
+                    let remove_idx = (cursor_pos - start_of_buffer_pos) as usize;
+                    buffer.remove(remove_idx);
+
+                    stdout
+                        .queue(SavePosition)?
+                        .queue(Print(format!("{} ", &buffer[remove_idx..])))?
+                        .queue(RestorePosition)?;
+                    stdout.flush()?;
+                }
+                KeyCode::Left => {
+                    if cursor_pos == start_of_buffer_pos {
+                        continue;
                     }
-                    KeyCode::End => {
-                        let move_to_col = end_of_buffer_pos + 1;
-                        stdout.execute(MoveToColumn(move_to_col))?;
-                        cursor_pos = end_of_buffer_pos;
+
+                    cursor_pos -= 1;
+                    stdout.execute(MoveLeft(1))?;
+                }
+                KeyCode::Right => {
+                    if cursor_pos == end_of_buffer_pos {
+                        continue;
                     }
-                    KeyCode::Up => {}
-                    KeyCode::Down => {}
-                    _ => {}
+
+                    cursor_pos += 1;
+                    stdout.execute(MoveRight(1))?;
+                }
+                KeyCode::Home => {
+                    let move_to_col = start_of_buffer_pos + 1;
+                    stdout.execute(MoveToColumn(move_to_col))?; // cursor::position is 0 indexed, but Column is 1 indexed.
+                    cursor_pos = start_of_buffer_pos;
+                }
+                KeyCode::End => {
+                    let move_to_col = end_of_buffer_pos + 1;
+                    stdout.execute(MoveToColumn(move_to_col))?;
+                    cursor_pos = end_of_buffer_pos;
                 }
+                KeyCode::Up => {}
+                KeyCode::Down => {}
+                _ => {}
             }
-
-            _ => {}
         }
     }
 
@@ -175,7 +174,121 @@ pub(crate) fn print_eval(stdout: &mut Stdout, output: String) -> Result<()> {
     Ok(())
 }
 
+/// Prints `err` in red, followed by the line of `source` it's about with
+/// carets under the part to blame.
+pub(crate) fn print_error(stdout: &mut Stdout, source: &str, err: &LispError) -> Result<()> {
+    let (line, carets) = underline(source, err.span());
+    stdout
+        .queue(MoveToNextLine(1))?
+        .queue(SetForegroundColor(Color::Red))?
+        .queue(Print("error"))?
+        .queue(ResetColor)?
+        .queue(Print(format!(": {}", err)))?
+        .queue(MoveToNextLine(1))?
+        .queue(Print(line))?
+        .queue(MoveToNextLine(1))?
+        .queue(SetForegroundColor(Color::Red))?
+        .queue(Print(carets))?
+        .queue(ResetColor)?
+        .queue(MoveToNextLine(1))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The line of `source` that `span` starts on, and a line of carets to print
+/// under the span. A span that goes on past the end of its line is cut off
+/// there, and an empty one still gets one caret.
+fn underline(source: &str, span: Span) -> (&str, String) {
+    let line = source.lines().nth(span.line - 1).unwrap_or("");
+    let indent = span.column - 1;
+    let width = source
+        .get(span.start..span.end)
+        .map_or(0, |text| text.chars().count())
+        .min(line.chars().count().saturating_sub(indent))
+        .max(1);
+
+    (line, format!("{}{}", " ".repeat(indent), "^".repeat(width)))
+}
+
 fn cursor_column() -> Result<u16> {
     let (col, _) = position()?;
     Ok(col)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Environment;
+    use crate::eval::eval;
+    use crate::parse::parse;
+
+    /// The first error reading or evaluating `source` runs into.
+    fn error(source: &str) -> LispError {
+        let env = Environment::global();
+        let exprs = match parse(source) {
+            Ok(exprs) => exprs,
+            Err(err) => return err.into(),
+        };
+
+        exprs
+            .iter()
+            .find_map(|ast| eval(ast, &env).err())
+            .expect("no error")
+    }
+
+    fn render(source: &str) -> (String, String) {
+        let err = error(source);
+        let (line, carets) = underline(source, err.span());
+        (err.to_string(), format!("{}\n{}", line, carets))
+    }
+
+    #[test]
+    fn test_eval_error() {
+        assert_eq!(
+            render("(+ 1 (/ 4 0))"),
+            (
+                "division by zero at 1:11".to_string(),
+                "(+ 1 (/ 4 0))\n          ^".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_whole_call() {
+        assert_eq!(
+            render("(car '(1) '(2))").1,
+            "(car '(1) '(2))\n^^^^^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            render("(+ 1 2))"),
+            (
+                "unexpected `)` at 1:8".to_string(),
+                "(+ 1 2))\n       ^".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_span_on_later_line() {
+        assert_eq!(render("1\n  (oops)").1, "  (oops)\n   ^^^^");
+    }
+
+    #[test]
+    fn test_span_cut_at_end_of_line() {
+        assert_eq!(render("(list 1\n  2").1, "(list 1\n^");
+        assert_eq!(render("  \"open").1, "  \"open\n  ^^^^^");
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        assert!(matches!(error("x"), LispError::UnboundSymbol { .. }));
+        assert!(matches!(error("(car)"), LispError::ArityMismatch { .. }));
+        assert!(matches!(error("(- \"a\")"), LispError::TypeError { .. }));
+        assert!(matches!(error("(/ 1 0)"), LispError::DivisionByZero { .. }));
+        assert!(matches!(error("#x"), LispError::Parse(_)));
+    }
+}
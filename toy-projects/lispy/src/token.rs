@@ -0,0 +1,329 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::num::IntErrorKind;
+use std::str::CharIndices;
+
+use crate::parse::{ParseError, ParseErrorKind};
+
+/// Where a piece of source text is. `start` and `end` are byte offsets, `end`
+/// being one past the last byte. `line` and `column` are where `start` is,
+/// both counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// A span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    LeftParen,
+    RightParen,
+    Quote,
+    Integer(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    Symbol(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits source text into tokens. Whitespace and comments, which run from
+/// `;` to the end of the line, are skipped.
+///
+/// It walks the source once, so every token is produced in time
+/// proportional to its length.
+pub struct Tokenizer<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            chars: source.char_indices().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    /// Consumes one char, keeping `line` and `column` up to date.
+    fn bump(&mut self) -> Option<(usize, char)> {
+        let (offset, c) = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some((offset, c))
+    }
+
+    /// Byte offset of the next char.
+    fn offset(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map_or(self.source.len(), |&(offset, _)| offset)
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == ';' {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.bump();
+                }
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Consumes the rest of a string literal, after the opening `"`.
+    fn string(&mut self) -> Result<TokenKind, ParseErrorKind> {
+        let mut string = String::new();
+
+        loop {
+            match self.bump() {
+                None => return Err(ParseErrorKind::UnterminatedString),
+                Some((_, '"')) => return Ok(TokenKind::Str(string)),
+                Some((_, '\\')) => {
+                    let escaped = match self.bump() {
+                        None => return Err(ParseErrorKind::UnterminatedString),
+                        Some((_, 'n')) => '\n',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'r')) => '\r',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '"')) => '"',
+                        Some((_, c)) => return Err(ParseErrorKind::InvalidEscape(c)),
+                    };
+                    string.push(escaped);
+                }
+                Some((_, c)) => string.push(c),
+            }
+        }
+    }
+
+    /// Consumes the rest of a number, boolean or symbol starting at `start`.
+    fn atom(&mut self, start: usize) -> Result<TokenKind, ParseErrorKind> {
+        while self.peek().is_some_and(|c| !is_delimiter(c)) {
+            self.bump();
+        }
+
+        let text = &self.source[start..self.offset()];
+        match text {
+            "#t" | "#true" => Ok(TokenKind::Bool(true)),
+            "#f" | "#false" => Ok(TokenKind::Bool(false)),
+            _ if text.starts_with('#') => Err(ParseErrorKind::UnknownLiteral(text.to_string())),
+            _ if looks_numeric(text) => number(text),
+            _ => Ok(TokenKind::Symbol(text.to_string())),
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.skip_whitespace_and_comments();
+
+        let (line, column) = (self.line, self.column);
+        let (start, c) = self.bump()?;
+        let kind = match c {
+            '(' => Ok(TokenKind::LeftParen),
+            ')' => Ok(TokenKind::RightParen),
+            '\'' => Ok(TokenKind::Quote),
+            '"' => self.string(),
+            _ => self.atom(start),
+        };
+
+        let span = Span {
+            start,
+            end: self.offset(),
+            line,
+            column,
+        };
+        Some(match kind {
+            Ok(kind) => Ok(Token { kind, span }),
+            Err(kind) => Err(ParseError { kind, span }),
+        })
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '\'' | '"' | ';')
+}
+
+/// Numbers start with a digit, optionally after a sign and a decimal point.
+/// Anything else, like `-` or `inf`, is a symbol.
+fn looks_numeric(text: &str) -> bool {
+    let text = text.strip_prefix(['+', '-']).unwrap_or(text);
+    let text = text.strip_prefix('.').unwrap_or(text);
+    text.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn number(text: &str) -> Result<TokenKind, ParseErrorKind> {
+    match text.parse::<i64>() {
+        Ok(n) => return Ok(TokenKind::Integer(n)),
+        Err(err)
+            if matches!(
+                err.kind(),
+                IntErrorKind::PosOverflow | IntErrorKind::NegOverflow
+            ) =>
+        {
+            return Err(ParseErrorKind::IntegerOutOfRange(text.to_string()))
+        }
+        Err(_) => {}
+    }
+
+    text.parse::<f64>()
+        .map(TokenKind::Float)
+        .map_err(|_| ParseErrorKind::InvalidNumber(text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        Tokenizer::new(source)
+            .map(|token| token.unwrap().kind)
+            .collect()
+    }
+
+    fn error(source: &str) -> ParseErrorKind {
+        Tokenizer::new(source)
+            .find_map(|token| token.err())
+            .unwrap()
+            .kind
+    }
+
+    fn symbol(name: &str) -> TokenKind {
+        TokenKind::Symbol(name.to_string())
+    }
+
+    #[test]
+    fn test_list() {
+        assert_eq!(
+            kinds("(+ 1 2.5)"),
+            vec![
+                TokenKind::LeftParen,
+                symbol("+"),
+                TokenKind::Integer(1),
+                TokenKind::Float(2.5),
+                TokenKind::RightParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_atoms() {
+        assert_eq!(
+            kinds("'foo #t #f -3 +.5 - -> list?"),
+            vec![
+                TokenKind::Quote,
+                symbol("foo"),
+                TokenKind::Bool(true),
+                TokenKind::Bool(false),
+                TokenKind::Integer(-3),
+                TokenKind::Float(0.5),
+                symbol("-"),
+                symbol("->"),
+                symbol("list?"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(
+            kinds(r#""hi there" "a\"b\n""#),
+            vec![
+                TokenKind::Str("hi there".to_string()),
+                TokenKind::Str("a\"b\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            kinds("; nothing here\n1 ; one\n"),
+            vec![TokenKind::Integer(1)]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let tokens: Vec<Token> = Tokenizer::new("(define x\n  \"é\")")
+            .map(Result::unwrap)
+            .collect();
+        let spans: Vec<(usize, usize, usize, usize)> = tokens
+            .iter()
+            .map(|token| {
+                let span = token.span;
+                (span.start, span.end, span.line, span.column)
+            })
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                (0, 1, 1, 1),
+                (1, 7, 1, 2),
+                (8, 9, 1, 9),
+                (12, 16, 2, 3),
+                (16, 17, 2, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(error("\"open"), ParseErrorKind::UnterminatedString);
+        assert_eq!(error(r#""\q""#), ParseErrorKind::InvalidEscape('q'));
+        assert_eq!(
+            error("12abc"),
+            ParseErrorKind::InvalidNumber("12abc".to_string())
+        );
+        assert_eq!(
+            error("99999999999999999999"),
+            ParseErrorKind::IntegerOutOfRange("99999999999999999999".to_string())
+        );
+        assert_eq!(
+            error("#nil"),
+            ParseErrorKind::UnknownLiteral("#nil".to_string())
+        );
+    }
+}
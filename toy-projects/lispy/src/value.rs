@@ -0,0 +1,132 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::builtins::Builtin;
+use crate::env::Environment;
+use crate::parse::Ast;
+
+/// What expressions evaluate to.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Symbol(String),
+    List(Vec<Value>),
+    Procedure(Procedure),
+    /// The result of expressions that have nothing to return, like an `if`
+    /// without an else branch whose test fails.
+    Nil,
+}
+
+impl Value {
+    /// Only `#f` and nil count as false.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Bool(false) | Value::Nil)
+    }
+
+    /// The name error messages use for the value's type.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "boolean",
+            Value::Str(_) => "string",
+            Value::Symbol(_) => "symbol",
+            Value::List(_) => "list",
+            Value::Procedure(_) => "procedure",
+            Value::Nil => "nil",
+        }
+    }
+}
+
+/// Lists are equal if their items are. Procedures are only equal to
+/// themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Symbol(a), Value::Symbol(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Procedure(a), Value::Procedure(b)) => a.is(b),
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Float(n) => write!(f, "{:?}", n),
+            Value::Bool(true) => write!(f, "#t"),
+            Value::Bool(false) => write!(f, "#f"),
+            Value::Str(string) => write!(f, "{:?}", string),
+            Value::Symbol(name) => write!(f, "{}", name),
+            Value::List(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+            Value::Procedure(procedure) => write!(f, "{}", procedure),
+            Value::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Procedure {
+    Builtin(Builtin),
+    Lambda(Rc<Lambda>),
+}
+
+impl Procedure {
+    /// The name arity errors use.
+    pub fn name(&self) -> &str {
+        match self {
+            Procedure::Builtin(builtin) => builtin.name,
+            Procedure::Lambda(lambda) => lambda.name.as_deref().unwrap_or("lambda"),
+        }
+    }
+
+    fn is(&self, other: &Procedure) -> bool {
+        match (self, other) {
+            (Procedure::Builtin(a), Procedure::Builtin(b)) => a.name == b.name,
+            (Procedure::Lambda(a), Procedure::Lambda(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Procedure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Procedure::Builtin(builtin) => write!(f, "#<builtin {}>", builtin.name),
+            Procedure::Lambda(lambda) => match &lambda.name {
+                Some(name) => write!(f, "#<procedure {}>", name),
+                None => write!(f, "#<procedure>"),
+            },
+        }
+    }
+}
+
+/// A procedure written in lisp. It closes over `env`, the environment it
+/// was created in, and runs `body` in a child of it.
+#[derive(Debug)]
+pub struct Lambda {
+    /// Set when the procedure was created with `(define (name ...) ...)`.
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub body: Vec<Ast>,
+    pub env: Environment,
+}
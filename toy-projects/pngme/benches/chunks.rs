@@ -0,0 +1,106 @@
+//! Compares owned and borrowed chunk parsing on a large in-memory PNG.
+//!
+//! Run with `cargo bench`. Prints the wall time, number of allocations and
+//! bytes allocated for each way of walking the chunks.
+
+use pngme::chunk::{Chunk, ChunkRef};
+use pngme::png::{ChunkRefs, Png};
+use pngme::ChunkType;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::convert::TryFrom;
+use std::hint::black_box;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Counts every allocation made through the global allocator.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const CHUNK_SIZE: usize = 1 << 20;
+const CHUNK_COUNT: usize = 256;
+const ITERATIONS: u32 = 5;
+
+/// Builds a PNG with `CHUNK_COUNT` IDAT chunks of `CHUNK_SIZE` bytes each.
+fn large_png() -> Vec<u8> {
+    let idat = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![0xAB; CHUNK_SIZE]);
+    let iend = Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new());
+
+    let mut chunks = vec![idat; CHUNK_COUNT];
+    chunks.push(iend);
+
+    Png::from_chunks(chunks).as_bytes()
+}
+
+/// Runs `f` a few times and reports the average time and allocations per run.
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        f();
+    }
+
+    let elapsed: Duration = start.elapsed() / ITERATIONS;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS as usize;
+    let bytes = (ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes) / ITERATIONS as usize;
+
+    println!(
+        "{:<32} {:>10.2?} {:>10} allocs {:>14} bytes",
+        name, elapsed, allocations, bytes
+    );
+}
+
+fn main() {
+    let bytes = large_png();
+    println!(
+        "{} chunks, {} MiB\n",
+        CHUNK_COUNT + 1,
+        bytes.len() / (1 << 20)
+    );
+
+    bench("Png::try_from (owned)", || {
+        black_box(Png::try_from(black_box(bytes.as_ref())).unwrap());
+    });
+
+    bench("ChunkRefs (borrowed)", || {
+        let total: u64 = ChunkRefs::new(black_box(&bytes))
+            .unwrap()
+            .map(|chunk| chunk.unwrap().length() as u64)
+            .sum();
+        black_box(total);
+    });
+
+    let png = Png::try_from(bytes.as_ref()).unwrap();
+
+    bench("Png::chunks cloned", || {
+        black_box(png.chunks().to_vec());
+    });
+
+    bench("Png::chunks borrowed", || {
+        let chunks: Vec<ChunkRef> = png.chunks().iter().map(Chunk::as_chunk_ref).collect();
+        black_box(chunks);
+    });
+
+    bench("Png::chunk_by_type (last chunk)", || {
+        black_box(png.chunk_by_type(black_box("IEND")));
+    });
+}
@@ -0,0 +1,22 @@
+//! Feeds arbitrary bytes to the chunk and PNG parsers.
+//!
+//! Run with `cargo +nightly fuzz run parse` from the pngme directory. Besides
+//! not panicking, anything that parses has to write back out byte for byte,
+//! since parsing checks every length and CRC.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::{Chunk, Png};
+use std::convert::TryFrom;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(chunk) = Chunk::try_from(data) {
+        assert_eq!(chunk.as_bytes(), data);
+    }
+
+    if let Ok(png) = Png::try_from(data) {
+        assert_eq!(png.as_bytes(), data);
+        let _ = png.validate();
+    }
+});
@@ -0,0 +1,838 @@
+//! Typed codecs for the ancillary chunks that describe color management,
+//! physical size, modification time, background and transparency.
+//!
+//! Each type decodes from a [`Chunk`] with `TryFrom` and encodes back with
+//! `to_chunk`. `bKGD` and `tRNS` are laid out differently for each color
+//! type, so those two are decoded with `from_chunk` and the image's color
+//! type instead. [`AncillaryChunk`] wraps all of them for code that just
+//! wants whatever a chunk holds.
+
+use crate::chunk::Chunk;
+use crate::codec::{be_u16, be_u32, check_type, checked_data, invalid};
+use crate::ihdr::ColorType;
+use crate::text::{check_keyword, compress, decompress, from_latin1, split_at_nul, to_latin1};
+use crate::{PngError, Result};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// `gAMA` and `cHRM` store fractions as integers scaled by this much.
+const SCALE: f64 = 100_000.0;
+
+/// The decoded contents of the `gAMA` chunk: the image's encoding gamma.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gamma {
+    /// The gamma times 100000, so 1/2.2 is stored as 45455.
+    pub gamma: u32,
+}
+
+impl Gamma {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "gAMA";
+    const LENGTH: usize = 4;
+
+    /// The gamma as a fraction.
+    pub fn value(self) -> f64 {
+        self.gamma as f64 / SCALE
+    }
+
+    /// Encodes this as a `gAMA` chunk.
+    pub fn to_chunk(self) -> Chunk {
+        Chunk::of_type(Self::CHUNK_TYPE, self.gamma.to_be_bytes().to_vec())
+    }
+}
+
+impl TryFrom<&Chunk> for Gamma {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = checked_data(chunk, Self::CHUNK_TYPE, Self::LENGTH)?;
+        let gamma = be_u32(data);
+        if gamma == 0 {
+            return Err(invalid(Self::CHUNK_TYPE, "gamma is 0".to_string()));
+        }
+
+        Ok(Self { gamma })
+    }
+}
+
+impl fmt::Display for Gamma {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "gamma {}", self.value())
+    }
+}
+
+/// The decoded contents of the `cHRM` chunk: the CIE 1931 x,y chromaticities
+/// of the white point and the three primaries.
+///
+/// Every coordinate is stored times 100000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chromaticities {
+    pub white: (u32, u32),
+    pub red: (u32, u32),
+    pub green: (u32, u32),
+    pub blue: (u32, u32),
+}
+
+impl Chromaticities {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "cHRM";
+    const LENGTH: usize = 32;
+
+    /// Encodes this as a `cHRM` chunk.
+    pub fn to_chunk(self) -> Chunk {
+        let data: Vec<u8> = [self.white, self.red, self.green, self.blue]
+            .iter()
+            .flat_map(|&(x, y)| [x.to_be_bytes(), y.to_be_bytes()].concat())
+            .collect();
+
+        Chunk::of_type(Self::CHUNK_TYPE, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Chromaticities {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = checked_data(chunk, Self::CHUNK_TYPE, Self::LENGTH)?;
+        let point = |i: usize| (be_u32(&data[i..i + 4]), be_u32(&data[i + 4..i + 8]));
+
+        Ok(Self {
+            white: point(0),
+            red: point(8),
+            green: point(16),
+            blue: point(24),
+        })
+    }
+}
+
+impl fmt::Display for Chromaticities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let points = [
+            ("white", self.white),
+            ("red", self.red),
+            ("green", self.green),
+            ("blue", self.blue),
+        ];
+
+        for (i, (name, (x, y))) in points.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} ({}, {})", name, *x as f64 / SCALE, *y as f64 / SCALE)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How colors outside the sRGB gamut should be mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual = 0,
+    RelativeColorimetric = 1,
+    Saturation = 2,
+    AbsoluteColorimetric = 3,
+}
+
+impl fmt::Display for RenderingIntent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RenderingIntent::Perceptual => "perceptual",
+            RenderingIntent::RelativeColorimetric => "relative colorimetric",
+            RenderingIntent::Saturation => "saturation",
+            RenderingIntent::AbsoluteColorimetric => "absolute colorimetric",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// The decoded contents of the `sRGB` chunk, which says the image is in the
+/// sRGB color space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StandardRgb {
+    pub intent: RenderingIntent,
+}
+
+impl StandardRgb {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "sRGB";
+    const LENGTH: usize = 1;
+
+    /// Encodes this as an `sRGB` chunk.
+    pub fn to_chunk(self) -> Chunk {
+        Chunk::of_type(Self::CHUNK_TYPE, vec![self.intent as u8])
+    }
+}
+
+impl TryFrom<&Chunk> for StandardRgb {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = checked_data(chunk, Self::CHUNK_TYPE, Self::LENGTH)?;
+        let intent = match data[0] {
+            0 => RenderingIntent::Perceptual,
+            1 => RenderingIntent::RelativeColorimetric,
+            2 => RenderingIntent::Saturation,
+            3 => RenderingIntent::AbsoluteColorimetric,
+            value => {
+                return Err(invalid(
+                    Self::CHUNK_TYPE,
+                    format!("unknown rendering intent {}", value),
+                ))
+            }
+        };
+
+        Ok(Self { intent })
+    }
+}
+
+impl fmt::Display for StandardRgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sRGB, {} rendering intent", self.intent)
+    }
+}
+
+/// The decoded contents of the `iCCP` chunk: an embedded ICC color profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfile {
+    /// Follows the same rules as text chunk keywords.
+    pub name: String,
+    /// The profile, uncompressed.
+    pub profile: Vec<u8>,
+}
+
+impl IccProfile {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "iCCP";
+
+    /// Encodes this as an `iCCP` chunk, checking the name and compressing
+    /// the profile.
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        check_keyword(&self.name).map_err(|reason| invalid(Self::CHUNK_TYPE, reason))?;
+
+        let mut data = to_latin1(&self.name).unwrap();
+        data.extend(&[0, 0]);
+        data.extend(compress(&self.profile)?);
+
+        Ok(Chunk::of_type(Self::CHUNK_TYPE, data))
+    }
+}
+
+impl TryFrom<&Chunk> for IccProfile {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_type(chunk, Self::CHUNK_TYPE)?;
+        let invalid = |reason: &str| invalid(Self::CHUNK_TYPE, reason.to_string());
+
+        let (name, rest) =
+            split_at_nul(chunk.data()).ok_or_else(|| invalid("profile name is not terminated"))?;
+        let name = from_latin1(name);
+        check_keyword(&name).map_err(|reason| invalid(&reason))?;
+
+        let (method, compressed) = rest
+            .split_first()
+            .ok_or_else(|| invalid("missing compression method"))?;
+        if *method != 0 {
+            return Err(invalid("unknown compression method"));
+        }
+
+        let profile = decompress(compressed).map_err(invalid)?;
+
+        Ok(Self { name, profile })
+    }
+}
+
+impl fmt::Display for IccProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ICC profile {:?}, {} bytes",
+            self.name,
+            self.profile.len()
+        )
+    }
+}
+
+/// What the pixel counts in a `pHYs` chunk are measured against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysicalUnit {
+    /// The counts only give the pixel aspect ratio.
+    Unknown = 0,
+    Meter = 1,
+}
+
+/// The decoded contents of the `pHYs` chunk: the intended pixel size or
+/// aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalDimensions {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    pub unit: PhysicalUnit,
+}
+
+impl PhysicalDimensions {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "pHYs";
+    const LENGTH: usize = 9;
+    const INCHES_PER_METER: f64 = 39.3701;
+
+    /// The resolution in dots per inch, if the unit is known.
+    pub fn dpi(self) -> Option<(f64, f64)> {
+        match self.unit {
+            PhysicalUnit::Meter => Some((
+                self.pixels_per_unit_x as f64 / Self::INCHES_PER_METER,
+                self.pixels_per_unit_y as f64 / Self::INCHES_PER_METER,
+            )),
+            PhysicalUnit::Unknown => None,
+        }
+    }
+
+    /// Encodes this as a `pHYs` chunk.
+    pub fn to_chunk(self) -> Chunk {
+        let data = [
+            &self.pixels_per_unit_x.to_be_bytes()[..],
+            &self.pixels_per_unit_y.to_be_bytes(),
+            &[self.unit as u8],
+        ]
+        .concat();
+
+        Chunk::of_type(Self::CHUNK_TYPE, data)
+    }
+}
+
+impl TryFrom<&Chunk> for PhysicalDimensions {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = checked_data(chunk, Self::CHUNK_TYPE, Self::LENGTH)?;
+        let unit = match data[8] {
+            0 => PhysicalUnit::Unknown,
+            1 => PhysicalUnit::Meter,
+            value => return Err(invalid(Self::CHUNK_TYPE, format!("unknown unit {}", value))),
+        };
+
+        Ok(Self {
+            pixels_per_unit_x: be_u32(&data[0..4]),
+            pixels_per_unit_y: be_u32(&data[4..8]),
+            unit,
+        })
+    }
+}
+
+impl fmt::Display for PhysicalDimensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dpi() {
+            Some((x, y)) => write!(
+                f,
+                "{}x{} pixels per meter ({:.0}x{:.0} DPI)",
+                self.pixels_per_unit_x, self.pixels_per_unit_y, x, y
+            ),
+            None => write!(
+                f,
+                "pixel aspect ratio {}:{}",
+                self.pixels_per_unit_x, self.pixels_per_unit_y
+            ),
+        }
+    }
+}
+
+/// The decoded contents of the `tIME` chunk: when the image was last
+/// changed, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastModified {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    /// Up to 60, to allow for leap seconds.
+    pub second: u8,
+}
+
+impl LastModified {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "tIME";
+    const LENGTH: usize = 7;
+
+    /// Encodes this as a `tIME` chunk.
+    pub fn to_chunk(self) -> Chunk {
+        let data = [
+            &self.year.to_be_bytes()[..],
+            &[self.month, self.day, self.hour, self.minute, self.second],
+        ]
+        .concat();
+
+        Chunk::of_type(Self::CHUNK_TYPE, data)
+    }
+}
+
+impl TryFrom<&Chunk> for LastModified {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = checked_data(chunk, Self::CHUNK_TYPE, Self::LENGTH)?;
+        let time = Self {
+            year: be_u16(&data[0..2]),
+            month: data[2],
+            day: data[3],
+            hour: data[4],
+            minute: data[5],
+            second: data[6],
+        };
+
+        let fields = [
+            ("month", time.month, 1..=12),
+            ("day", time.day, 1..=31),
+            ("hour", time.hour, 0..=23),
+            ("minute", time.minute, 0..=59),
+            ("second", time.second, 0..=60),
+        ];
+        for (name, value, range) in fields.iter() {
+            if !range.contains(value) {
+                return Err(invalid(
+                    Self::CHUNK_TYPE,
+                    format!("{} {} is out of range", name, value),
+                ));
+            }
+        }
+
+        Ok(time)
+    }
+}
+
+impl fmt::Display for LastModified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "modified {:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// The decoded contents of the `bKGD` chunk: a color to show the image
+/// against. Which variant is used depends on the color type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    /// An index into the palette, for indexed images.
+    Indexed(u8),
+    /// A gray level, for grayscale images with or without alpha.
+    Gray(u16),
+    /// A color, for RGB images with or without alpha.
+    Rgb(u16, u16, u16),
+}
+
+impl Background {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "bKGD";
+
+    /// Decodes a `bKGD` chunk from an image with the given color type.
+    pub fn from_chunk(chunk: &Chunk, color_type: ColorType) -> Result<Self> {
+        check_type(chunk, Self::CHUNK_TYPE)?;
+
+        match color_type {
+            ColorType::Indexed => {
+                let data = checked_data(chunk, Self::CHUNK_TYPE, 1)?;
+                Ok(Background::Indexed(data[0]))
+            }
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                let data = checked_data(chunk, Self::CHUNK_TYPE, 2)?;
+                Ok(Background::Gray(be_u16(data)))
+            }
+            ColorType::Rgb | ColorType::Rgba => {
+                let data = checked_data(chunk, Self::CHUNK_TYPE, 6)?;
+                let (red, green, blue) = rgb(data);
+                Ok(Background::Rgb(red, green, blue))
+            }
+        }
+    }
+
+    /// Encodes this as a `bKGD` chunk.
+    pub fn to_chunk(self) -> Chunk {
+        let data = match self {
+            Background::Indexed(index) => vec![index],
+            Background::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Background::Rgb(red, green, blue) => {
+                [red.to_be_bytes(), green.to_be_bytes(), blue.to_be_bytes()].concat()
+            }
+        };
+
+        Chunk::of_type(Self::CHUNK_TYPE, data)
+    }
+}
+
+impl fmt::Display for Background {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Background::Indexed(index) => write!(f, "background is palette entry {}", index),
+            Background::Gray(gray) => write!(f, "background is gray {}", gray),
+            Background::Rgb(red, green, blue) => {
+                write!(f, "background is RGB ({}, {}, {})", red, green, blue)
+            }
+        }
+    }
+}
+
+/// The decoded contents of the `tRNS` chunk: simple transparency for images
+/// without an alpha channel. Which variant is used depends on the color type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    /// Alpha values for the first palette entries, for indexed images.
+    /// Entries past the end are opaque.
+    Indexed(Vec<u8>),
+    /// The gray level that is fully transparent.
+    Gray(u16),
+    /// The color that is fully transparent.
+    Rgb(u16, u16, u16),
+}
+
+impl Transparency {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "tRNS";
+
+    /// Decodes a `tRNS` chunk from an image with the given color type.
+    /// Images with an alpha channel can't have one.
+    pub fn from_chunk(chunk: &Chunk, color_type: ColorType) -> Result<Self> {
+        check_type(chunk, Self::CHUNK_TYPE)?;
+
+        match color_type {
+            ColorType::Indexed => {
+                if chunk.data().len() > 256 {
+                    return Err(invalid(
+                        Self::CHUNK_TYPE,
+                        format!(
+                            "{} alpha values is more than a palette holds",
+                            chunk.length()
+                        ),
+                    ));
+                }
+                Ok(Transparency::Indexed(chunk.data().to_vec()))
+            }
+            ColorType::Grayscale => {
+                let data = checked_data(chunk, Self::CHUNK_TYPE, 2)?;
+                Ok(Transparency::Gray(be_u16(data)))
+            }
+            ColorType::Rgb => {
+                let data = checked_data(chunk, Self::CHUNK_TYPE, 6)?;
+                let (red, green, blue) = rgb(data);
+                Ok(Transparency::Rgb(red, green, blue))
+            }
+            ColorType::GrayscaleAlpha | ColorType::Rgba => Err(invalid(
+                Self::CHUNK_TYPE,
+                format!("not allowed for {} images", color_type),
+            )),
+        }
+    }
+
+    /// Encodes this as a `tRNS` chunk.
+    pub fn to_chunk(&self) -> Chunk {
+        let data = match self {
+            Transparency::Indexed(alpha) => alpha.clone(),
+            Transparency::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Transparency::Rgb(red, green, blue) => {
+                [red.to_be_bytes(), green.to_be_bytes(), blue.to_be_bytes()].concat()
+            }
+        };
+
+        Chunk::of_type(Self::CHUNK_TYPE, data)
+    }
+}
+
+impl fmt::Display for Transparency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transparency::Indexed(alpha) => {
+                write!(f, "alpha for {} palette entries", alpha.len())
+            }
+            Transparency::Gray(gray) => write!(f, "gray {} is transparent", gray),
+            Transparency::Rgb(red, green, blue) => {
+                write!(f, "RGB ({}, {}, {}) is transparent", red, green, blue)
+            }
+        }
+    }
+}
+
+/// Any of the chunks in this module, decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AncillaryChunk {
+    Gamma(Gamma),
+    Chromaticities(Chromaticities),
+    StandardRgb(StandardRgb),
+    IccProfile(IccProfile),
+    PhysicalDimensions(PhysicalDimensions),
+    LastModified(LastModified),
+    Background(Background),
+    Transparency(Transparency),
+}
+
+impl AncillaryChunk {
+    /// Decodes `chunk` if it has one of the types in this module, or returns
+    /// `None` for any other chunk.
+    ///
+    /// `bKGD` and `tRNS` need the image's color type, so without one they
+    /// fail to decode.
+    pub fn decode(chunk: &Chunk, color_type: Option<ColorType>) -> Option<Result<Self>> {
+        let with_color_type = |decode: fn(&Chunk, ColorType) -> Result<Self>| {
+            let chunk_type = chunk.chunk_type().to_string();
+            color_type
+                .ok_or_else(|| invalid(&chunk_type, "the image header is missing".to_string()))
+                .and_then(|color_type| decode(chunk, color_type))
+        };
+
+        let decoded = match &chunk.chunk_type().bytes() {
+            b"gAMA" => Gamma::try_from(chunk).map(AncillaryChunk::Gamma),
+            b"cHRM" => Chromaticities::try_from(chunk).map(AncillaryChunk::Chromaticities),
+            b"sRGB" => StandardRgb::try_from(chunk).map(AncillaryChunk::StandardRgb),
+            b"iCCP" => IccProfile::try_from(chunk).map(AncillaryChunk::IccProfile),
+            b"pHYs" => PhysicalDimensions::try_from(chunk).map(AncillaryChunk::PhysicalDimensions),
+            b"tIME" => LastModified::try_from(chunk).map(AncillaryChunk::LastModified),
+            b"bKGD" => with_color_type(|chunk, color_type| {
+                Background::from_chunk(chunk, color_type).map(AncillaryChunk::Background)
+            }),
+            b"tRNS" => with_color_type(|chunk, color_type| {
+                Transparency::from_chunk(chunk, color_type).map(AncillaryChunk::Transparency)
+            }),
+            _ => return None,
+        };
+
+        Some(decoded)
+    }
+
+    /// Encodes this back into a chunk.
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        Ok(match self {
+            AncillaryChunk::Gamma(gamma) => gamma.to_chunk(),
+            AncillaryChunk::Chromaticities(chromaticities) => chromaticities.to_chunk(),
+            AncillaryChunk::StandardRgb(srgb) => srgb.to_chunk(),
+            AncillaryChunk::IccProfile(profile) => profile.to_chunk()?,
+            AncillaryChunk::PhysicalDimensions(dimensions) => dimensions.to_chunk(),
+            AncillaryChunk::LastModified(time) => time.to_chunk(),
+            AncillaryChunk::Background(background) => background.to_chunk(),
+            AncillaryChunk::Transparency(transparency) => transparency.to_chunk(),
+        })
+    }
+}
+
+impl fmt::Display for AncillaryChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AncillaryChunk::Gamma(gamma) => gamma.fmt(f),
+            AncillaryChunk::Chromaticities(chromaticities) => chromaticities.fmt(f),
+            AncillaryChunk::StandardRgb(srgb) => srgb.fmt(f),
+            AncillaryChunk::IccProfile(profile) => profile.fmt(f),
+            AncillaryChunk::PhysicalDimensions(dimensions) => dimensions.fmt(f),
+            AncillaryChunk::LastModified(time) => time.fmt(f),
+            AncillaryChunk::Background(background) => background.fmt(f),
+            AncillaryChunk::Transparency(transparency) => transparency.fmt(f),
+        }
+    }
+}
+
+fn rgb(data: &[u8]) -> (u16, u16, u16) {
+    (
+        be_u16(&data[0..2]),
+        be_u16(&data[2..4]),
+        be_u16(&data[4..6]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::Png;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    #[test]
+    fn test_decode_from_png() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+
+        let srgb = StandardRgb::try_from(png.chunk_by_type("sRGB").unwrap()).unwrap();
+        assert_eq!(srgb.intent, RenderingIntent::Perceptual);
+
+        let gamma = Gamma::try_from(png.chunk_by_type("gAMA").unwrap()).unwrap();
+        assert_eq!(gamma.gamma, 45455);
+        assert_eq!(gamma.to_string(), "gamma 0.45455");
+
+        let dimensions = PhysicalDimensions::try_from(png.chunk_by_type("pHYs").unwrap()).unwrap();
+        assert_eq!(dimensions.unit, PhysicalUnit::Meter);
+    }
+
+    #[test]
+    fn test_round_trip_from_png() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+
+        for chunk_type in ["sRGB", "gAMA", "pHYs"].iter() {
+            let chunk = png.chunk_by_type(chunk_type).unwrap();
+            let decoded = AncillaryChunk::decode(chunk, None).unwrap().unwrap();
+            assert_eq!(decoded.to_chunk().unwrap().as_bytes(), chunk.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_unknown_chunk_is_not_decoded() {
+        let chunk = Chunk::of_type("ruSt", b"hi");
+        assert!(AncillaryChunk::decode(&chunk, None).is_none());
+    }
+
+    #[test]
+    fn test_wrong_length() {
+        let result = Gamma::try_from(&Chunk::of_type("gAMA", [0, 1]));
+        assert!(matches!(result, Err(PngError::InvalidChunkData { .. })));
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let result = Gamma::try_from(&Chunk::of_type("sRGB", [0, 0, 0, 1]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_chromaticities() {
+        let chromaticities = Chromaticities {
+            white: (31270, 32900),
+            red: (64000, 33000),
+            green: (30000, 60000),
+            blue: (15000, 6000),
+        };
+        let chunk = chromaticities.to_chunk();
+
+        assert_eq!(chunk.length(), 32);
+        assert_eq!(Chromaticities::try_from(&chunk).unwrap(), chromaticities);
+        assert_eq!(
+            chromaticities.to_string(),
+            "white (0.3127, 0.329), red (0.64, 0.33), green (0.3, 0.6), blue (0.15, 0.06)"
+        );
+    }
+
+    #[test]
+    fn test_unknown_rendering_intent() {
+        assert!(StandardRgb::try_from(&Chunk::of_type("sRGB", [4])).is_err());
+    }
+
+    #[test]
+    fn test_icc_profile() {
+        let profile = IccProfile {
+            name: "Display P3".to_string(),
+            profile: vec![7; 500],
+        };
+        let chunk = profile.to_chunk().unwrap();
+
+        assert!(chunk.data().starts_with(b"Display P3\0\0"));
+        assert_eq!(IccProfile::try_from(&chunk).unwrap(), profile);
+        assert_eq!(profile.to_string(), "ICC profile \"Display P3\", 500 bytes");
+    }
+
+    #[test]
+    fn test_icc_profile_bad_name() {
+        let profile = IccProfile {
+            name: " padded".to_string(),
+            profile: Vec::new(),
+        };
+        assert!(profile.to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_icc_profile_corrupt() {
+        let chunk = Chunk::of_type("iCCP", b"name\0\0not zlib");
+        assert!(IccProfile::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_physical_dimensions() {
+        let dimensions = PhysicalDimensions {
+            pixels_per_unit_x: 2835,
+            pixels_per_unit_y: 2835,
+            unit: PhysicalUnit::Meter,
+        };
+        assert_eq!(
+            dimensions.to_string(),
+            "2835x2835 pixels per meter (72x72 DPI)"
+        );
+
+        let aspect = PhysicalDimensions {
+            pixels_per_unit_x: 2,
+            pixels_per_unit_y: 1,
+            unit: PhysicalUnit::Unknown,
+        };
+        assert_eq!(aspect.dpi(), None);
+        assert_eq!(aspect.to_string(), "pixel aspect ratio 2:1");
+        assert_eq!(
+            PhysicalDimensions::try_from(&aspect.to_chunk()).unwrap(),
+            aspect
+        );
+    }
+
+    #[test]
+    fn test_last_modified() {
+        let time = LastModified {
+            year: 2024,
+            month: 2,
+            day: 29,
+            hour: 23,
+            minute: 59,
+            second: 60,
+        };
+        let chunk = time.to_chunk();
+
+        assert_eq!(chunk.data(), &[0x07, 0xe8, 2, 29, 23, 59, 60]);
+        assert_eq!(LastModified::try_from(&chunk).unwrap(), time);
+        assert_eq!(time.to_string(), "modified 2024-02-29 23:59:60 UTC");
+    }
+
+    #[test]
+    fn test_last_modified_out_of_range() {
+        let chunk = Chunk::of_type("tIME", [0x07, 0xe8, 13, 1, 0, 0, 0]);
+        assert!(LastModified::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_background_depends_on_color_type() {
+        let chunk = Chunk::of_type("bKGD", [0, 255]);
+
+        assert_eq!(
+            Background::from_chunk(&chunk, ColorType::Grayscale).unwrap(),
+            Background::Gray(255)
+        );
+        assert!(Background::from_chunk(&chunk, ColorType::Indexed).is_err());
+        assert!(Background::from_chunk(&chunk, ColorType::Rgba).is_err());
+
+        let rgb = Background::Rgb(1, 2, 3);
+        assert_eq!(
+            Background::from_chunk(&rgb.to_chunk(), ColorType::Rgb).unwrap(),
+            rgb
+        );
+    }
+
+    #[test]
+    fn test_transparency() {
+        let alpha = Transparency::Indexed(vec![0, 128, 255]);
+        assert_eq!(
+            Transparency::from_chunk(&alpha.to_chunk(), ColorType::Indexed).unwrap(),
+            alpha
+        );
+        assert_eq!(alpha.to_string(), "alpha for 3 palette entries");
+
+        let gray = Chunk::of_type("tRNS", [0, 0]);
+        assert_eq!(
+            Transparency::from_chunk(&gray, ColorType::Grayscale).unwrap(),
+            Transparency::Gray(0)
+        );
+        assert!(Transparency::from_chunk(&gray, ColorType::GrayscaleAlpha).is_err());
+    }
+
+    #[test]
+    fn test_decode_needs_color_type() {
+        let chunk = Chunk::of_type("tRNS", [0, 0]);
+
+        assert!(AncillaryChunk::decode(&chunk, None).unwrap().is_err());
+        assert_eq!(
+            AncillaryChunk::decode(&chunk, Some(ColorType::Grayscale))
+                .unwrap()
+                .unwrap(),
+            AncillaryChunk::Transparency(Transparency::Gray(0))
+        );
+    }
+}
@@ -0,0 +1,686 @@
+//! Animated PNG (APNG) frames: reading the animation chunks, and
+//! extracting or dropping frames.
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::codec::{be_u16, be_u32, check_type, checked_data, invalid};
+use crate::ihdr::ImageHeader;
+use crate::png::Png;
+use crate::{PngError, Result};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// The decoded contents of the `acTL` chunk, which marks a PNG as animated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    /// How many times to play the animation. 0 means forever.
+    pub num_plays: u32,
+}
+
+impl AnimationControl {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "acTL";
+    const LENGTH: usize = 8;
+
+    /// Encodes this as an `acTL` chunk.
+    pub fn to_chunk(self) -> Chunk {
+        let data = [self.num_frames.to_be_bytes(), self.num_plays.to_be_bytes()].concat();
+        Chunk::new(ChunkType::try_from(*b"acTL").unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = checked_data(chunk, Self::CHUNK_TYPE, Self::LENGTH)?;
+
+        Ok(Self {
+            num_frames: be_u32(&data[0..4]),
+            num_plays: be_u32(&data[4..8]),
+        })
+    }
+}
+
+impl fmt::Display for AnimationControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} frame(s), ", self.num_frames)?;
+        match self.num_plays {
+            0 => write!(f, "loops forever"),
+            plays => write!(f, "plays {} time(s)", plays),
+        }
+    }
+}
+
+/// What happens to a frame's region before the next frame is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    None = 0,
+    Background = 1,
+    Previous = 2,
+}
+
+/// How a frame is drawn over what is already on the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    Source = 0,
+    Over = 1,
+}
+
+/// The decoded contents of an `fcTL` chunk, which starts a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    /// The frame is shown for `delay_num / delay_den` seconds. A `delay_den`
+    /// of 0 means hundredths of a second.
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "fcTL";
+    const LENGTH: usize = 26;
+
+    /// How long the frame is shown, in milliseconds.
+    pub fn delay_ms(&self) -> u32 {
+        let den = match self.delay_den {
+            0 => 100,
+            den => den as u32,
+        };
+
+        self.delay_num as u32 * 1000 / den
+    }
+
+    /// Encodes this as an `fcTL` chunk.
+    pub fn to_chunk(self) -> Chunk {
+        let data = [
+            &self.sequence_number.to_be_bytes()[..],
+            &self.width.to_be_bytes(),
+            &self.height.to_be_bytes(),
+            &self.x_offset.to_be_bytes(),
+            &self.y_offset.to_be_bytes(),
+            &self.delay_num.to_be_bytes(),
+            &self.delay_den.to_be_bytes(),
+            &[self.dispose_op as u8, self.blend_op as u8],
+        ]
+        .concat();
+
+        Chunk::new(ChunkType::try_from(*b"fcTL").unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = checked_data(chunk, Self::CHUNK_TYPE, Self::LENGTH)?;
+        let invalid = |reason: String| invalid(Self::CHUNK_TYPE, reason);
+
+        let width = be_u32(&data[4..8]);
+        let height = be_u32(&data[8..12]);
+        if width == 0 || height == 0 {
+            return Err(invalid(format!("frame size {}x{} is empty", width, height)));
+        }
+
+        let dispose_op = match data[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            value => return Err(invalid(format!("unknown dispose op {}", value))),
+        };
+        let blend_op = match data[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            value => return Err(invalid(format!("unknown blend op {}", value))),
+        };
+
+        Ok(Self {
+            sequence_number: be_u32(&data[0..4]),
+            width,
+            height,
+            x_offset: be_u32(&data[12..16]),
+            y_offset: be_u32(&data[16..20]),
+            delay_num: be_u16(&data[20..22]),
+            delay_den: be_u16(&data[22..24]),
+            dispose_op,
+            blend_op,
+        })
+    }
+}
+
+impl fmt::Display for FrameControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dispose = match self.dispose_op {
+            DisposeOp::None => "none",
+            DisposeOp::Background => "background",
+            DisposeOp::Previous => "previous",
+        };
+        let blend = match self.blend_op {
+            BlendOp::Source => "source",
+            BlendOp::Over => "over",
+        };
+
+        write!(
+            f,
+            "{}x{} at ({}, {}), {} ms, dispose {}, blend {}",
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+            self.delay_ms(),
+            dispose,
+            blend
+        )
+    }
+}
+
+/// The decoded contents of an `fdAT` chunk: a piece of a frame's compressed
+/// image data, laid out like an `IDAT` with a sequence number in front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameData {
+    pub sequence_number: u32,
+    pub data: Vec<u8>,
+}
+
+impl FrameData {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "fdAT";
+
+    /// Encodes this as an `fdAT` chunk.
+    pub fn to_chunk(&self) -> Chunk {
+        let data = [&self.sequence_number.to_be_bytes()[..], &self.data].concat();
+        Chunk::new(ChunkType::try_from(*b"fdAT").unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameData {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        check_type(chunk, Self::CHUNK_TYPE)?;
+        if chunk.data().len() < 4 {
+            return Err(invalid(
+                Self::CHUNK_TYPE,
+                "missing the sequence number".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            sequence_number: be_u32(&chunk.data()[0..4]),
+            data: chunk.data()[4..].to_vec(),
+        })
+    }
+}
+
+/// One frame of an animation, with its image data gathered from every
+/// `IDAT` or `fdAT` chunk that belongs to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub control: FrameControl,
+    /// Compressed image data, as it would be stored in `IDAT` chunks.
+    pub data: Vec<u8>,
+    /// The frame is the PNG's default image, so its data is in `IDAT`
+    /// chunks rather than `fdAT` ones.
+    pub is_default_image: bool,
+}
+
+/// The frames of an animated PNG.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    /// Gathers the frames of an animated PNG, checking that the `fcTL` and
+    /// `fdAT` sequence numbers run from 0 without gaps and that the frame
+    /// count matches `acTL`.
+    pub fn from_png(png: &Png) -> Result<Self> {
+        let header = png.header()?;
+        let mut control = None;
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut next_sequence_number = 0;
+        let mut seen_idat = false;
+
+        for chunk in png.chunks() {
+            match &chunk.chunk_type().bytes() {
+                b"acTL" => {
+                    if seen_idat {
+                        return Err(invalid_animation("acTL comes after IDAT"));
+                    }
+                    control = Some(AnimationControl::try_from(chunk)?);
+                }
+                b"fcTL" => {
+                    let frame_control = FrameControl::try_from(chunk)?;
+                    check_sequence(frame_control.sequence_number, &mut next_sequence_number)?;
+                    check_region(&frame_control, &header, frames.len(), !seen_idat)?;
+
+                    frames.push(Frame {
+                        control: frame_control,
+                        data: Vec::new(),
+                        is_default_image: !seen_idat,
+                    });
+                }
+                b"IDAT" => {
+                    seen_idat = true;
+                    if let Some(frame) = frames.last_mut().filter(|f| f.is_default_image) {
+                        frame.data.extend_from_slice(chunk.data());
+                    }
+                }
+                b"fdAT" => {
+                    let frame_data = FrameData::try_from(chunk)?;
+                    check_sequence(frame_data.sequence_number, &mut next_sequence_number)?;
+
+                    match frames.last_mut().filter(|f| !f.is_default_image) {
+                        Some(frame) => frame.data.extend(frame_data.data),
+                        None => return Err(invalid_animation("fdAT comes before any fcTL")),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let control = control.ok_or_else(|| invalid_animation("there is no acTL chunk"))?;
+        if control.num_frames as usize != frames.len() {
+            return Err(invalid_animation(&format!(
+                "acTL promises {} frame(s) but there are {}",
+                control.num_frames,
+                frames.len()
+            )));
+        }
+        if let Some(index) = frames.iter().position(|frame| frame.data.is_empty()) {
+            return Err(invalid_animation(&format!(
+                "frame {} has no image data",
+                index
+            )));
+        }
+
+        Ok(Self { control, frames })
+    }
+
+    /// The frame at `index`, counting from 0.
+    pub fn frame(&self, index: usize) -> Result<&Frame> {
+        self.frames.get(index).ok_or(PngError::FrameNotFound {
+            index,
+            frames: self.frames.len(),
+        })
+    }
+}
+
+/// Builds a standalone, still PNG out of one frame. The frame is taken as
+/// stored, so frames that only cover part of the canvas or blend over the
+/// previous one come out as just that region.
+pub fn extract_frame(png: &Png, index: usize) -> Result<Png> {
+    let animation = Animation::from_png(png)?;
+    let frame = animation.frame(index)?;
+
+    let header = ImageHeader {
+        width: frame.control.width,
+        height: frame.control.height,
+        ..png.header()?
+    };
+
+    // Keep the palette, colour space and other chunks that say how to read
+    // the pixels, but nothing about the animation.
+    let mut chunks = vec![header.to_chunk()];
+    chunks.extend(
+        png.chunks()
+            .iter()
+            .take_while(|chunk| chunk.chunk_type().bytes() != *b"IDAT")
+            .filter(|chunk| !is_animation_chunk(chunk) && chunk.chunk_type().bytes() != *b"IHDR")
+            .cloned(),
+    );
+    chunks.push(Chunk::new(
+        ChunkType::try_from(*b"IEND").unwrap(),
+        Vec::new(),
+    ));
+
+    let mut frame_png = Png::from_chunks(chunks);
+    frame_png.replace_image_data(&frame.data);
+
+    Ok(frame_png)
+}
+
+/// Removes a frame from an animation and renumbers the chunks after it.
+///
+/// Dropping the default image's frame keeps its `IDAT` chunks, which become
+/// an image that is shown only by viewers that don't support APNG.
+pub fn drop_frame(png: &Png, index: usize) -> Result<Png> {
+    let animation = Animation::from_png(png)?;
+    animation.frame(index)?;
+    if animation.frames.len() == 1 {
+        return Err(invalid_animation("can't drop the only frame"));
+    }
+
+    let mut chunks = Vec::with_capacity(png.chunks().len());
+    let mut frame_index = None;
+    let mut next_sequence_number = 0;
+
+    for chunk in png.chunks() {
+        match &chunk.chunk_type().bytes() {
+            b"acTL" => {
+                let control = AnimationControl {
+                    num_frames: animation.control.num_frames - 1,
+                    ..animation.control
+                };
+                chunks.push(control.to_chunk());
+            }
+            b"fcTL" | b"fdAT" => {
+                if chunk.chunk_type().bytes() == *b"fcTL" {
+                    frame_index = Some(frame_index.map_or(0, |i| i + 1));
+                }
+                if frame_index == Some(index) {
+                    continue;
+                }
+
+                let mut data = chunk.data().to_vec();
+                data[..4].copy_from_slice(&u32::to_be_bytes(next_sequence_number));
+                next_sequence_number += 1;
+                chunks.push(Chunk::new(*chunk.chunk_type(), data));
+            }
+            _ => chunks.push(chunk.clone()),
+        }
+    }
+
+    Ok(Png::from_chunks(chunks))
+}
+
+/// Returns true for the chunks only animated PNGs have.
+pub fn is_animation_chunk(chunk: &Chunk) -> bool {
+    matches!(&chunk.chunk_type().bytes(), b"acTL" | b"fcTL" | b"fdAT")
+}
+
+fn check_sequence(sequence_number: u32, next: &mut u32) -> Result<()> {
+    if sequence_number != *next {
+        return Err(invalid_animation(&format!(
+            "expected sequence number {} but found {}",
+            next, sequence_number
+        )));
+    }
+
+    *next += 1;
+    Ok(())
+}
+
+/// Makes sure the frame fits on the canvas, and that the default image's
+/// frame covers all of it as the spec requires. A first frame that comes
+/// after `IDAT` isn't the default image and can be any size.
+fn check_region(
+    control: &FrameControl,
+    header: &ImageHeader,
+    index: usize,
+    is_default_image: bool,
+) -> Result<()> {
+    let right = control.x_offset as u64 + control.width as u64;
+    let bottom = control.y_offset as u64 + control.height as u64;
+    if right > header.width as u64 || bottom > header.height as u64 {
+        return Err(invalid_animation(&format!(
+            "frame {} ({}) doesn't fit in the {}x{} image",
+            index, control, header.width, header.height
+        )));
+    }
+
+    let full = control.x_offset == 0
+        && control.y_offset == 0
+        && control.width == header.width
+        && control.height == header.height;
+    if is_default_image && !full {
+        return Err(invalid_animation(
+            "the default image's frame doesn't cover the image",
+        ));
+    }
+
+    Ok(())
+}
+
+fn invalid_animation(reason: &str) -> PngError {
+    PngError::InvalidAnimation {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::PixelData;
+
+    const APNG_FILE: &[u8] = include_bytes!("../tests/fixtures/animated.png");
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    fn apng() -> Png {
+        Png::try_from(APNG_FILE).unwrap()
+    }
+
+    /// The animated fixture with its chunks changed by `edit`.
+    fn edited_apng<F: FnOnce(&mut Vec<Chunk>)>(edit: F) -> Png {
+        let mut chunks = apng().chunks().to_vec();
+        edit(&mut chunks);
+        Png::from_chunks(chunks)
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_animation_control_round_trip() {
+        let png = apng();
+        let chunk = png.chunk_by_type("acTL").unwrap();
+        let control = AnimationControl::try_from(chunk).unwrap();
+
+        assert_eq!(
+            control,
+            AnimationControl {
+                num_frames: 3,
+                num_plays: 0
+            }
+        );
+        assert_eq!(control.to_string(), "3 frame(s), loops forever");
+        assert_eq!(control.to_chunk().as_bytes(), chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_frame_control_round_trip() {
+        let png = apng();
+        for chunk in png.chunks_by_type("fcTL") {
+            let control = FrameControl::try_from(chunk).unwrap();
+            assert_eq!(control.to_chunk().as_bytes(), chunk.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_frame_data_round_trip() {
+        let png = apng();
+        for chunk in png.chunks_by_type("fdAT") {
+            let data = FrameData::try_from(chunk).unwrap();
+            assert_eq!(data.to_chunk().as_bytes(), chunk.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_invalid_frame_control() {
+        let mut data = apng().chunk_by_type("fcTL").unwrap().data().to_vec();
+        data[24] = 3;
+        let chunk = Chunk::new(ChunkType::try_from(*b"fcTL").unwrap(), data);
+
+        assert!(matches!(
+            FrameControl::try_from(&chunk),
+            Err(PngError::InvalidChunkData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_wrong_chunk_type() {
+        let png = apng();
+        let chunk = png.chunk_by_type("IHDR").unwrap();
+
+        assert!(AnimationControl::try_from(chunk).is_err());
+        assert!(FrameControl::try_from(chunk).is_err());
+        assert!(FrameData::try_from(chunk).is_err());
+    }
+
+    #[test]
+    fn test_animation_from_png() {
+        let animation = Animation::from_png(&apng()).unwrap();
+        let frames = &animation.frames;
+
+        assert_eq!(frames.len(), 3);
+        assert!(frames[0].is_default_image);
+        assert!(!frames[1].is_default_image);
+        assert_eq!(frames[0].data, apng().image_data());
+        assert_eq!(
+            frames[2].control.to_string(),
+            "8x8 at (4, 4), 250 ms, dispose none, blend over"
+        );
+    }
+
+    #[test]
+    fn test_frame_data_spans_chunks() {
+        let animation = Animation::from_png(&apng()).unwrap();
+        let header = ImageHeader {
+            width: 16,
+            height: 16,
+            ..apng().header().unwrap()
+        };
+
+        let pixels = PixelData::decode(header, &animation.frames[1].data).unwrap();
+        assert_eq!(&pixels.data()[..4], &[0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn test_still_png_is_not_animated() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        assert!(matches!(
+            Animation::from_png(&png),
+            Err(PngError::InvalidAnimation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sequence_gap() {
+        let png = edited_apng(|chunks| {
+            chunks.remove(5);
+        });
+        assert!(matches!(
+            Animation::from_png(&png),
+            Err(PngError::InvalidAnimation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reordered_sequence() {
+        let png = edited_apng(|chunks| chunks.swap(5, 6));
+        assert!(matches!(
+            Animation::from_png(&png),
+            Err(PngError::InvalidAnimation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_frame_count_mismatch() {
+        let png = edited_apng(|chunks| {
+            chunks[1] = AnimationControl {
+                num_frames: 4,
+                num_plays: 0,
+            }
+            .to_chunk()
+        });
+        assert!(matches!(
+            Animation::from_png(&png),
+            Err(PngError::InvalidAnimation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_frame_outside_canvas() {
+        let png = edited_apng(|chunks| {
+            let mut control = FrameControl::try_from(&chunks[7]).unwrap();
+            control.x_offset = 12;
+            chunks[7] = control.to_chunk();
+        });
+        assert!(matches!(
+            Animation::from_png(&png),
+            Err(PngError::InvalidAnimation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_extract_frame() {
+        let frame_png = extract_frame(&apng(), 2).unwrap();
+
+        assert_eq!(chunk_types(&frame_png), ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(frame_png.validate(), []);
+
+        let header = frame_png.header().unwrap();
+        assert_eq!((header.width, header.height), (8, 8));
+        let pixels = PixelData::decode(header, &frame_png.image_data()).unwrap();
+        assert_eq!(&pixels.data()[..4], &[0, 0, 255, 128]);
+    }
+
+    #[test]
+    fn test_extract_missing_frame() {
+        assert!(matches!(
+            extract_frame(&apng(), 3),
+            Err(PngError::FrameNotFound {
+                index: 3,
+                frames: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_drop_frame() {
+        let png = drop_frame(&apng(), 1).unwrap();
+        let animation = Animation::from_png(&png).unwrap();
+
+        assert_eq!(animation.control.num_frames, 2);
+        assert_eq!(animation.frames[1].control.width, 8);
+        assert_eq!(animation.frames[1].control.sequence_number, 1);
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_drop_default_image_frame() {
+        let png = drop_frame(&apng(), 0).unwrap();
+        let animation = Animation::from_png(&png).unwrap();
+
+        assert_eq!(animation.frames.len(), 2);
+        assert!(animation.frames.iter().all(|frame| !frame.is_default_image));
+        assert_eq!(png.image_data(), apng().image_data());
+    }
+
+    #[test]
+    fn test_drop_default_image_then_first_frame() {
+        let png = drop_frame(&drop_frame(&apng(), 1).unwrap(), 0).unwrap();
+        let animation = Animation::from_png(&png).unwrap();
+
+        assert_eq!(animation.frames.len(), 1);
+        assert_eq!(animation.frames[0].control.width, 8);
+        assert!(!animation.frames[0].is_default_image);
+        assert!(extract_frame(&png, 0).is_ok());
+    }
+
+    #[test]
+    fn test_drop_only_frame() {
+        let png = drop_frame(&drop_frame(&apng(), 2).unwrap(), 1).unwrap();
+
+        assert!(matches!(
+            drop_frame(&png, 0),
+            Err(PngError::InvalidAnimation { .. })
+        ));
+    }
+}
@@ -0,0 +1,737 @@
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use pngme::message;
+use pngme::report::DataEncoding;
+use std::path::PathBuf;
+
+// Subcommands:
+//  Encode:
+//      Parameters: Files, Chunk type, Message, Output file(optional), --passphrase,
+//                  --file, --part-size, --out-dir, --jobs
+//  Decode
+//      Parameters: File Path, Chunk type, --passphrase, --output, --format, --data
+//  Remove
+//      Parameters: Files, Chunk type, --out-dir, --jobs
+//  Print
+//      Parameters: Files, --jobs, --format, --data
+//  Validate
+//      Parameters: File Path
+//  Text
+//      List:   File Path
+//      Get:    File Path, Keyword
+//      Set:    File Path, Keyword, Text, --compress, --lang, --translated
+//      Delete: File Path, Keyword
+//  Stego
+//      Capacity: File Path
+//      Hide:     File Path, Message, Output file(optional), --passphrase
+//      Reveal:   File Path, --passphrase
+//  Diff
+//      Parameters: Old file path, New file path, --format
+//  Sanitize
+//      Parameters: Files, Output file(optional), --keep, --strip, --out-dir, --jobs
+//  Repair
+//      Parameters: File Path, Output file, --fix-crc
+//  Frames
+//      List:    File Path
+//      Extract: File Path, Frame index, Output file
+//      Drop:    File Path, Frame index, Output file(optional)
+//  Keygen
+//      Parameters: Key file
+//  Sign
+//      Parameters: File Path, --key, Output file(optional)
+//  Verify
+//      Parameters: File Path, --key
+//
+// "Files" is a PNG file, a directory of them or a glob pattern.
+
+pub enum PngMeArgs {
+    Encode(EncodeArgs),
+    Decode(DecodeArgs),
+    Remove(RemoveArgs),
+    Print(PrintArgs),
+    Validate(ValidateArgs),
+    Text(TextArgs),
+    Stego(StegoArgs),
+    Frames(FramesArgs),
+    Diff(DiffArgs),
+    Sanitize(SanitizeArgs),
+    Repair(RepairArgs),
+    Keygen(KeygenArgs),
+    Sign(SignArgs),
+    Verify(VerifyArgs),
+}
+
+/// The files a command that can run on many files at once works on.
+pub struct BatchArgs {
+    /// A PNG file, a directory of them or a glob pattern.
+    pub input: String,
+    /// Where to write the result when `input` is a single file.
+    pub output: Option<PathBuf>,
+    /// Mirror the results into this directory instead of editing in place.
+    pub out_dir: Option<PathBuf>,
+    /// How many files to work on at once, 0 for one per CPU.
+    pub jobs: usize,
+}
+
+pub struct EncodeArgs {
+    pub files: BatchArgs,
+    pub chunk_type: String,
+    pub message: String,
+    pub passphrase: Option<String>,
+    /// `message` is the path of a file to hide rather than the message itself.
+    pub from_file: bool,
+    pub part_size: usize,
+}
+
+pub struct DecodeArgs {
+    pub file_path: PathBuf,
+    pub chunk_type: String,
+    pub passphrase: Option<String>,
+    /// Write the message to this file instead of printing it.
+    pub output: Option<PathBuf>,
+    pub format: OutputFormat,
+    /// Include the message bytes in JSON output, encoded like this.
+    pub data: Option<DataEncoding>,
+}
+
+pub struct RemoveArgs {
+    pub files: BatchArgs,
+    pub chunk_type: String,
+}
+
+pub struct PrintArgs {
+    pub files: BatchArgs,
+    pub format: OutputFormat,
+    /// Include the data of each chunk in JSON output, encoded like this.
+    pub data: Option<DataEncoding>,
+}
+
+pub struct ValidateArgs {
+    pub file_path: PathBuf,
+}
+
+pub enum TextArgs {
+    List(TextListArgs),
+    Get(TextGetArgs),
+    Set(TextSetArgs),
+    Delete(TextDeleteArgs),
+}
+
+pub struct TextListArgs {
+    pub file_path: PathBuf,
+}
+
+pub struct TextGetArgs {
+    pub file_path: PathBuf,
+    pub keyword: String,
+}
+
+pub struct TextSetArgs {
+    pub file_path: PathBuf,
+    pub keyword: String,
+    pub text: String,
+    pub compress: bool,
+    pub language_tag: Option<String>,
+    pub translated_keyword: Option<String>,
+}
+
+pub struct TextDeleteArgs {
+    pub file_path: PathBuf,
+    pub keyword: String,
+}
+
+pub enum StegoArgs {
+    Capacity(StegoCapacityArgs),
+    Hide(StegoHideArgs),
+    Reveal(StegoRevealArgs),
+}
+
+pub struct StegoCapacityArgs {
+    pub file_path: PathBuf,
+}
+
+pub struct StegoHideArgs {
+    pub file_path: PathBuf,
+    pub message: String,
+    pub output: Option<PathBuf>,
+    pub passphrase: Option<String>,
+}
+
+pub struct StegoRevealArgs {
+    pub file_path: PathBuf,
+    pub passphrase: Option<String>,
+}
+
+pub struct DiffArgs {
+    pub old_file_path: PathBuf,
+    pub new_file_path: PathBuf,
+    pub format: OutputFormat,
+}
+
+pub struct SanitizeArgs {
+    pub files: BatchArgs,
+    pub keep: Vec<String>,
+    pub strip: Vec<String>,
+}
+
+pub struct RepairArgs {
+    pub file_path: PathBuf,
+    pub output: PathBuf,
+    /// Keep chunks with a bad CRC, fixing the CRC, instead of dropping them.
+    pub fix_crcs: bool,
+}
+
+pub struct KeygenArgs {
+    /// Where to write the signing key. The public key goes next to it, with
+    /// `.pub` added to the name.
+    pub key_file: PathBuf,
+}
+
+pub struct SignArgs {
+    pub file_path: PathBuf,
+    /// A file holding the signing key in hex.
+    pub key_file: PathBuf,
+    pub output: Option<PathBuf>,
+}
+
+pub struct VerifyArgs {
+    pub file_path: PathBuf,
+    /// A file holding the public key in hex.
+    pub key_file: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+pub enum FramesArgs {
+    List(FramesListArgs),
+    Extract(FramesExtractArgs),
+    Drop(FramesDropArgs),
+}
+
+pub struct FramesListArgs {
+    pub file_path: PathBuf,
+}
+
+pub struct FramesExtractArgs {
+    pub file_path: PathBuf,
+    pub index: usize,
+    pub output: PathBuf,
+}
+
+pub struct FramesDropArgs {
+    pub file_path: PathBuf,
+    pub index: usize,
+    pub output: Option<PathBuf>,
+}
+
+fn key_arg(help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name("key")
+        .long("key")
+        .short("k")
+        .takes_value(true)
+        .required(true)
+        .help(help)
+}
+
+fn passphrase_arg() -> Arg<'static, 'static> {
+    Arg::with_name("passphrase")
+        .long("passphrase")
+        .takes_value(true)
+        .env("PNGME_PASSPHRASE")
+        .hide_env_values(true)
+        .help("Encrypt or decrypt the message with this passphrase")
+}
+
+fn format_arg() -> Arg<'static, 'static> {
+    Arg::with_name("format")
+        .long("format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .help("How to print the result")
+}
+
+fn data_arg() -> Arg<'static, 'static> {
+    Arg::with_name("data")
+        .long("data")
+        .takes_value(true)
+        .possible_values(&["hex", "base64"])
+        .help("Include the data in JSON output, encoded like this")
+}
+
+fn files_arg() -> Arg<'static, 'static> {
+    Arg::with_name("input file")
+        .index(1)
+        .required(true)
+        .help("A PNG file, a directory of PNG files or a glob pattern")
+}
+
+fn out_dir_arg() -> Arg<'static, 'static> {
+    Arg::with_name("out dir")
+        .long("out-dir")
+        .takes_value(true)
+        .help("Write results here, mirroring the input directories, instead of in place")
+}
+
+fn jobs_arg() -> Arg<'static, 'static> {
+    Arg::with_name("jobs")
+        .long("jobs")
+        .short("j")
+        .takes_value(true)
+        .validator(|value| match value.parse::<usize>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err("must be a number of files, or 0 for one per CPU".to_string()),
+        })
+        .help("How many files to work on at once")
+}
+
+fn frame_index_arg() -> Arg<'static, 'static> {
+    Arg::with_name("frame index")
+        .index(2)
+        .required(true)
+        .validator(|value| match value.parse::<usize>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err("must be a frame number, counting from 0".to_string()),
+        })
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("pngme")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommands(vec![
+            SubCommand::with_name("encode")
+                .about("Encode image with message")
+                .args(&[
+                    files_arg(),
+                    Arg::with_name("chunk type").index(2).required(true),
+                    Arg::with_name("message").index(3).required(true),
+                    Arg::with_name("output file").index(4).required(false),
+                    passphrase_arg(),
+                    Arg::with_name("file")
+                        .long("file")
+                        .help("Treat the message as the path of a file to hide"),
+                    Arg::with_name("part size")
+                        .long("part-size")
+                        .takes_value(true)
+                        .validator(|value| match value.parse::<usize>() {
+                            Ok(size) if size > 0 => Ok(()),
+                            _ => Err("must be a positive number of bytes".to_string()),
+                        })
+                        .help("Split messages larger than this many bytes over several chunks"),
+                    out_dir_arg(),
+                    jobs_arg(),
+                ]),
+            SubCommand::with_name("decode")
+                .about("Decode message in image")
+                .args(&[
+                    Arg::with_name("input file").index(1).required(true),
+                    Arg::with_name("chunk type").index(2).required(true),
+                    passphrase_arg(),
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("Write the message to this file instead of printing it"),
+                    format_arg(),
+                    data_arg(),
+                ]),
+            SubCommand::with_name("remove")
+                .about("Remove message from image")
+                .args(&[
+                    files_arg(),
+                    Arg::with_name("chunk type").index(2).required(true),
+                    out_dir_arg(),
+                    jobs_arg(),
+                ]),
+            SubCommand::with_name("print")
+                .about("Print chunks in image")
+                .args(&[files_arg(), jobs_arg(), format_arg(), data_arg()]),
+            SubCommand::with_name("validate")
+                .about("Check image against the PNG chunk ordering rules")
+                .args(&[Arg::with_name("input file").index(1).required(true)]),
+            SubCommand::with_name("text")
+                .about("Manage tEXt, zTXt and iTXt metadata in image")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommands(vec![
+                    SubCommand::with_name("list")
+                        .about("List textual metadata")
+                        .args(&[Arg::with_name("input file").index(1).required(true)]),
+                    SubCommand::with_name("get")
+                        .about("Print the text stored under a keyword")
+                        .args(&[
+                            Arg::with_name("input file").index(1).required(true),
+                            Arg::with_name("keyword").index(2).required(true),
+                        ]),
+                    SubCommand::with_name("set")
+                        .about("Store text under a keyword, replacing any existing text")
+                        .args(&[
+                            Arg::with_name("input file").index(1).required(true),
+                            Arg::with_name("keyword").index(2).required(true),
+                            Arg::with_name("text").index(3).required(true),
+                            Arg::with_name("compress")
+                                .long("compress")
+                                .help("Deflate the text (zTXt, or compressed iTXt)"),
+                            Arg::with_name("lang")
+                                .long("lang")
+                                .takes_value(true)
+                                .help("Store as iTXt with this language tag"),
+                            Arg::with_name("translated")
+                                .long("translated")
+                                .takes_value(true)
+                                .help("Store as iTXt with this translation of the keyword"),
+                        ]),
+                    SubCommand::with_name("delete")
+                        .about("Remove all text stored under a keyword")
+                        .args(&[
+                            Arg::with_name("input file").index(1).required(true),
+                            Arg::with_name("keyword").index(2).required(true),
+                        ]),
+                ]),
+            SubCommand::with_name("stego")
+                .about("Hide a message in the pixels of an image")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommands(vec![
+                    SubCommand::with_name("capacity")
+                        .about("Print how many bytes of message the image can hide")
+                        .args(&[Arg::with_name("input file").index(1).required(true)]),
+                    SubCommand::with_name("hide")
+                        .about("Hide a message in the low bits of the pixels")
+                        .args(&[
+                            Arg::with_name("input file").index(1).required(true),
+                            Arg::with_name("message").index(2).required(true),
+                            Arg::with_name("output file").index(3).required(false),
+                            passphrase_arg(),
+                        ]),
+                    SubCommand::with_name("reveal")
+                        .about("Print the message hidden in the pixels")
+                        .args(&[
+                            Arg::with_name("input file").index(1).required(true),
+                            passphrase_arg(),
+                        ]),
+                ]),
+            SubCommand::with_name("diff")
+                .about("Show the chunks that differ between two images")
+                .args(&[
+                    Arg::with_name("old file").index(1).required(true),
+                    Arg::with_name("new file").index(2).required(true),
+                    format_arg(),
+                ]),
+            SubCommand::with_name("sanitize")
+                .about("Strip private chunks and metadata, keeping the image intact")
+                .args(&[
+                    files_arg(),
+                    Arg::with_name("output file").index(2).required(false),
+                    Arg::with_name("keep")
+                        .long("keep")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Keep chunks of this type"),
+                    Arg::with_name("strip")
+                        .long("strip")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Strip chunks of this type, unless they are critical"),
+                    out_dir_arg(),
+                    jobs_arg(),
+                ]),
+            SubCommand::with_name("repair")
+                .about("Salvage what can be read from a damaged image")
+                .args(&[
+                    Arg::with_name("input file").index(1).required(true),
+                    Arg::with_name("output file").index(2).required(true),
+                    Arg::with_name("fix crc").long("fix-crc").help(
+                        "Keep chunks with a bad checksum and fix it, instead of dropping them",
+                    ),
+                ]),
+            SubCommand::with_name("keygen")
+                .about("Generate a key pair for signing images")
+                .args(&[Arg::with_name("key file").index(1).required(true).help(
+                    "Write the signing key here, and the public key to the same path with .pub added",
+                )]),
+            SubCommand::with_name("sign")
+                .about("Sign every chunk of image, storing the signature in a chunk")
+                .args(&[
+                    Arg::with_name("input file").index(1).required(true),
+                    key_arg("File holding the signing key, as written by keygen"),
+                    Arg::with_name("output file").index(2).required(false),
+                ]),
+            SubCommand::with_name("verify")
+                .about("Check that image was signed by a key and not changed since")
+                .args(&[
+                    Arg::with_name("input file").index(1).required(true),
+                    key_arg("File holding the public key, as written by keygen"),
+                ]),
+            SubCommand::with_name("frames")
+                .about("Inspect and edit the frames of an animated PNG")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommands(vec![
+                    SubCommand::with_name("list")
+                        .about("List the frames of the animation")
+                        .args(&[Arg::with_name("input file").index(1).required(true)]),
+                    SubCommand::with_name("extract")
+                        .about("Save one frame as a still PNG")
+                        .args(&[
+                            Arg::with_name("input file").index(1).required(true),
+                            frame_index_arg(),
+                            Arg::with_name("output file").index(3).required(true),
+                        ]),
+                    SubCommand::with_name("drop")
+                        .about("Remove one frame from the animation")
+                        .args(&[
+                            Arg::with_name("input file").index(1).required(true),
+                            frame_index_arg(),
+                            Arg::with_name("output file").index(3).required(false),
+                        ]),
+                ]),
+        ])
+}
+
+/// Parses the command line arguments into a `PngMeArgs`. Exits the process
+/// with clap's usage message if they are invalid.
+pub fn parse() -> PngMeArgs {
+    let matches = app().get_matches();
+
+    match matches.subcommand() {
+        ("encode", Some(matches)) => PngMeArgs::Encode(EncodeArgs::from(matches)),
+        ("decode", Some(matches)) => PngMeArgs::Decode(DecodeArgs::from(matches)),
+        ("remove", Some(matches)) => PngMeArgs::Remove(RemoveArgs::from(matches)),
+        ("print", Some(matches)) => PngMeArgs::Print(PrintArgs::from(matches)),
+        ("validate", Some(matches)) => PngMeArgs::Validate(ValidateArgs::from(matches)),
+        ("text", Some(matches)) => PngMeArgs::Text(TextArgs::from(matches)),
+        ("stego", Some(matches)) => PngMeArgs::Stego(StegoArgs::from(matches)),
+        ("frames", Some(matches)) => PngMeArgs::Frames(FramesArgs::from(matches)),
+        ("diff", Some(matches)) => PngMeArgs::Diff(DiffArgs::from(matches)),
+        ("sanitize", Some(matches)) => PngMeArgs::Sanitize(SanitizeArgs::from(matches)),
+        ("repair", Some(matches)) => PngMeArgs::Repair(RepairArgs::from(matches)),
+        ("keygen", Some(matches)) => PngMeArgs::Keygen(KeygenArgs::from(matches)),
+        ("sign", Some(matches)) => PngMeArgs::Sign(SignArgs::from(matches)),
+        ("verify", Some(matches)) => PngMeArgs::Verify(VerifyArgs::from(matches)),
+        _ => unreachable!("clap requires a subcommand"),
+    }
+}
+
+// The `unwrap`s below are fine because clap already made sure that required
+// args are present and validated the ones that need parsing.
+
+impl From<&ArgMatches<'_>> for BatchArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            input: matches.value_of("input file").unwrap().to_string(),
+            output: matches.value_of("output file").map(PathBuf::from),
+            out_dir: matches.value_of("out dir").map(PathBuf::from),
+            jobs: matches
+                .value_of("jobs")
+                .map_or(0, |jobs| jobs.parse().unwrap()),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for EncodeArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            files: BatchArgs::from(matches),
+            chunk_type: matches.value_of("chunk type").unwrap().to_string(),
+            message: matches.value_of("message").unwrap().to_string(),
+            passphrase: matches.value_of("passphrase").map(String::from),
+            from_file: matches.is_present("file"),
+            part_size: matches
+                .value_of("part size")
+                .map_or(message::DEFAULT_PART_SIZE, |size| size.parse().unwrap()),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for DecodeArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            file_path: matches.value_of("input file").unwrap().into(),
+            chunk_type: matches.value_of("chunk type").unwrap().to_string(),
+            passphrase: matches.value_of("passphrase").map(String::from),
+            output: matches.value_of("output").map(PathBuf::from),
+            format: OutputFormat::from(matches),
+            data: data_encoding(matches),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for RemoveArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            files: BatchArgs::from(matches),
+            chunk_type: matches.value_of("chunk type").unwrap().to_string(),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for PrintArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            files: BatchArgs::from(matches),
+            format: OutputFormat::from(matches),
+            data: data_encoding(matches),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for ValidateArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            file_path: matches.value_of("input file").unwrap().into(),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for TextArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            ("list", Some(matches)) => TextArgs::List(TextListArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+            }),
+            ("get", Some(matches)) => TextArgs::Get(TextGetArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+                keyword: matches.value_of("keyword").unwrap().to_string(),
+            }),
+            ("set", Some(matches)) => TextArgs::Set(TextSetArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+                keyword: matches.value_of("keyword").unwrap().to_string(),
+                text: matches.value_of("text").unwrap().to_string(),
+                compress: matches.is_present("compress"),
+                language_tag: matches.value_of("lang").map(String::from),
+                translated_keyword: matches.value_of("translated").map(String::from),
+            }),
+            ("delete", Some(matches)) => TextArgs::Delete(TextDeleteArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+                keyword: matches.value_of("keyword").unwrap().to_string(),
+            }),
+            _ => unreachable!("clap requires a subcommand"),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for StegoArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            ("capacity", Some(matches)) => StegoArgs::Capacity(StegoCapacityArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+            }),
+            ("hide", Some(matches)) => StegoArgs::Hide(StegoHideArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+                message: matches.value_of("message").unwrap().to_string(),
+                output: matches.value_of("output file").map(PathBuf::from),
+                passphrase: matches.value_of("passphrase").map(String::from),
+            }),
+            ("reveal", Some(matches)) => StegoArgs::Reveal(StegoRevealArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+                passphrase: matches.value_of("passphrase").map(String::from),
+            }),
+            _ => unreachable!("clap requires a subcommand"),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for DiffArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            old_file_path: matches.value_of("old file").unwrap().into(),
+            new_file_path: matches.value_of("new file").unwrap().into(),
+            format: OutputFormat::from(matches),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for SanitizeArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        let values = |name| {
+            matches
+                .values_of(name)
+                .map(|values| values.map(String::from).collect())
+                .unwrap_or_default()
+        };
+
+        Self {
+            files: BatchArgs::from(matches),
+            keep: values("keep"),
+            strip: values("strip"),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for RepairArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            file_path: matches.value_of("input file").unwrap().into(),
+            output: matches.value_of("output file").unwrap().into(),
+            fix_crcs: matches.is_present("fix crc"),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for KeygenArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            key_file: matches.value_of("key file").unwrap().into(),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for SignArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            file_path: matches.value_of("input file").unwrap().into(),
+            key_file: matches.value_of("key").unwrap().into(),
+            output: matches.value_of("output file").map(PathBuf::from),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for VerifyArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        Self {
+            file_path: matches.value_of("input file").unwrap().into(),
+            key_file: matches.value_of("key").unwrap().into(),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for OutputFormat {
+    fn from(matches: &ArgMatches) -> Self {
+        match matches.value_of("format") {
+            Some("json") => OutputFormat::Json,
+            _ => OutputFormat::Text,
+        }
+    }
+}
+
+fn data_encoding(matches: &ArgMatches) -> Option<DataEncoding> {
+    match matches.value_of("data") {
+        Some("hex") => Some(DataEncoding::Hex),
+        Some("base64") => Some(DataEncoding::Base64),
+        _ => None,
+    }
+}
+
+impl From<&ArgMatches<'_>> for FramesArgs {
+    fn from(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            ("list", Some(matches)) => FramesArgs::List(FramesListArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+            }),
+            ("extract", Some(matches)) => FramesArgs::Extract(FramesExtractArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+                index: matches.value_of("frame index").unwrap().parse().unwrap(),
+                output: matches.value_of("output file").unwrap().into(),
+            }),
+            ("drop", Some(matches)) => FramesArgs::Drop(FramesDropArgs {
+                file_path: matches.value_of("input file").unwrap().into(),
+                index: matches.value_of("frame index").unwrap().parse().unwrap(),
+                output: matches.value_of("output file").map(PathBuf::from),
+            }),
+            _ => unreachable!("clap requires a subcommand"),
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use pngme::{PngError, Result};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// One file for a command to process, and where its result goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+/// How one job went, with everything the command printed for it.
+#[derive(Debug)]
+pub struct JobResult {
+    pub job: Job,
+    pub output: Vec<u8>,
+    pub result: Result<()>,
+}
+
+/// The files a command runs on, expanded from a single file, a directory or
+/// a glob pattern.
+///
+/// Results are written over the inputs unless an output directory is given.
+/// Then each result goes to the same path relative to the output directory
+/// as its input has relative to the directory that was searched, or to the
+/// part of the glob pattern before the first wildcard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Batch {
+    pub jobs: Vec<Job>,
+    /// True when `input` named one file rather than a group of them.
+    pub single: bool,
+}
+
+impl Batch {
+    /// Expands `input`. Directories are searched recursively for `.png`
+    /// files, glob patterns can match any file.
+    pub fn expand(input: &str, out_dir: Option<&Path>) -> Result<Self> {
+        let path = Path::new(input);
+
+        let (inputs, base, single) = if path.is_dir() {
+            let mut inputs = Vec::new();
+            find_pngs(path, &mut inputs)?;
+            (inputs, path.to_path_buf(), false)
+        } else if !path.exists() && is_pattern(input) {
+            (glob_files(input)?, glob_base(input), false)
+        } else {
+            let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            (vec![path.to_path_buf()], base, true)
+        };
+
+        if inputs.is_empty() {
+            return Err(PngError::NoInputFiles {
+                input: input.to_string(),
+            });
+        }
+
+        let jobs = inputs
+            .into_iter()
+            .map(|input| {
+                let output = match out_dir {
+                    Some(out_dir) => out_dir.join(input.strip_prefix(&base).unwrap_or(&input)),
+                    None => input.clone(),
+                };
+
+                Job { input, output }
+            })
+            .collect();
+
+        Ok(Self { jobs, single })
+    }
+
+    /// Sends a single file's result to `output` instead.
+    pub fn with_output(mut self, output: &Path) -> Result<Self> {
+        if !self.single {
+            return Err(PngError::OutputFileForBatch);
+        }
+
+        self.jobs[0].output = output.to_path_buf();
+        Ok(self)
+    }
+
+    /// Runs `process` on every job, spread over `threads` threads (0 picks
+    /// one per CPU). Results come back in job order, whatever order the jobs
+    /// finish in.
+    ///
+    /// `process` gets a buffer to print to, so the output of jobs running at
+    /// the same time doesn't get mixed up. Parent directories of outputs
+    /// that differ from their input are created first.
+    pub fn run<F>(&self, threads: usize, process: F) -> Result<Vec<JobResult>>
+    where
+        F: Fn(&Job, &mut dyn Write) -> Result<()> + Sync,
+    {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|err| PngError::Io(io::Error::other(err)))?;
+
+        let run_job = |job: &Job| {
+            let mut output = Vec::new();
+            let result = create_parent(job).and_then(|_| process(job, &mut output));
+
+            JobResult {
+                job: job.clone(),
+                output,
+                result,
+            }
+        };
+
+        Ok(pool.install(|| self.jobs.par_iter().map(run_job).collect()))
+    }
+}
+
+/// Counts how many jobs of a batch succeeded and failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Summary {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+impl Summary {
+    pub fn of(results: &[JobResult]) -> Self {
+        let failed = results.iter().filter(|job| job.result.is_err()).count();
+
+        Self {
+            succeeded: results.len() - failed,
+            failed,
+        }
+    }
+
+    /// `Ok` if every job succeeded.
+    pub fn into_result(self) -> Result<()> {
+        if self.failed == 0 {
+            return Ok(());
+        }
+
+        Err(PngError::BatchFailed {
+            failed: self.failed,
+            total: self.succeeded + self.failed,
+        })
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} succeeded, {} failed", self.succeeded, self.failed)
+    }
+}
+
+fn create_parent(job: &Job) -> Result<()> {
+    if job.output == job.input {
+        return Ok(());
+    }
+
+    match job.output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => Ok(fs::create_dir_all(parent)?),
+        _ => Ok(()),
+    }
+}
+
+/// Adds every `.png` file under `dir` to `files`, in sorted order.
+fn find_pngs(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            find_pngs(&path, files)?;
+        } else if is_png(&path) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+}
+
+fn is_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+fn glob_files(pattern: &str) -> Result<Vec<PathBuf>> {
+    let paths = glob::glob(pattern).map_err(|err| PngError::InvalidPattern {
+        pattern: pattern.to_string(),
+        reason: err.msg.to_string(),
+    })?;
+
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(io::Error::from)?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// The directories at the start of `pattern` that don't contain wildcards.
+fn glob_base(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    let components: Vec<Component> = Path::new(pattern).components().collect();
+
+    // The last component names the files, even if it has no wildcards.
+    for component in &components[..components.len().saturating_sub(1)] {
+        if is_pattern(&component.as_os_str().to_string_lossy()) {
+            break;
+        }
+        base.push(component);
+    }
+
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(FIXTURES).join(name)
+    }
+
+    fn inputs(batch: &Batch) -> Vec<PathBuf> {
+        batch.jobs.iter().map(|job| job.input.clone()).collect()
+    }
+
+    #[test]
+    fn test_single_file() {
+        let input = fixture("dice.png");
+        let batch = Batch::expand(input.to_str().unwrap(), None).unwrap();
+
+        assert!(batch.single);
+        assert_eq!(
+            batch.jobs,
+            [Job {
+                input: input.clone(),
+                output: input
+            }]
+        );
+    }
+
+    #[test]
+    fn test_single_file_to_out_dir() {
+        let input = fixture("dice.png");
+        let batch = Batch::expand(input.to_str().unwrap(), Some(Path::new("out"))).unwrap();
+
+        assert_eq!(batch.jobs[0].output, Path::new("out/dice.png"));
+    }
+
+    #[test]
+    fn test_directory() {
+        let batch = Batch::expand(FIXTURES, Some(Path::new("out"))).unwrap();
+
+        assert!(!batch.single);
+        assert_eq!(
+            inputs(&batch),
+            [
+                fixture("animated.png"),
+                fixture("dice.png"),
+                fixture("huge.png")
+            ]
+        );
+        assert_eq!(batch.jobs[1].output, Path::new("out/dice.png"));
+    }
+
+    #[test]
+    fn test_glob() {
+        let pattern = format!("{}/d*.png", FIXTURES);
+        let batch = Batch::expand(&pattern, Some(Path::new("out"))).unwrap();
+
+        assert!(!batch.single);
+        assert_eq!(inputs(&batch), [fixture("dice.png")]);
+        assert_eq!(batch.jobs[0].output, Path::new("out/dice.png"));
+    }
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("images/*.png"), Path::new("images"));
+        assert_eq!(glob_base("images/**/*.png"), Path::new("images"));
+        assert_eq!(glob_base("a/b*/c/*.png"), Path::new("a"));
+        assert_eq!(glob_base("*.png"), Path::new(""));
+    }
+
+    #[test]
+    fn test_no_matches() {
+        let pattern = format!("{}/*.jpeg", FIXTURES);
+
+        assert!(matches!(
+            Batch::expand(&pattern, None),
+            Err(PngError::NoInputFiles { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(matches!(
+            Batch::expand("[.png", None),
+            Err(PngError::InvalidPattern { .. })
+        ));
+    }
+
+    #[test]
+    fn test_output_file_needs_single_input() {
+        let batch = Batch::expand(FIXTURES, None).unwrap();
+
+        assert!(matches!(
+            batch.with_output(Path::new("out.png")),
+            Err(PngError::OutputFileForBatch)
+        ));
+    }
+
+    #[test]
+    fn test_run_keeps_job_order() {
+        let batch = Batch::expand(FIXTURES, None).unwrap();
+        let results = batch
+            .run(2, |job, out| {
+                if job.input.ends_with("animated.png") {
+                    return Err(PngError::NoHiddenMessage);
+                }
+                Ok(writeln!(
+                    out,
+                    "{}",
+                    job.input.file_name().unwrap().to_string_lossy()
+                )?)
+            })
+            .unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].result.is_err());
+        assert_eq!(results[1].output, b"dice.png\n");
+        assert_eq!(results[2].output, b"huge.png\n");
+        assert_eq!(
+            Summary::of(&results),
+            Summary {
+                succeeded: 2,
+                failed: 1
+            }
+        );
+        assert!(matches!(
+            Summary::of(&results).into_result(),
+            Err(PngError::BatchFailed {
+                failed: 1,
+                total: 3
+            })
+        ));
+    }
+}
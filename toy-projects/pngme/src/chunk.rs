@@ -1,10 +1,17 @@
+//! A single chunk: its length, type, data and CRC.
+
 use crate::chunk_type::ChunkType;
-use crate::{Error, StrError};
-use crc::crc32::checksum_ieee;
+use crate::{PngError, Result};
+use crc::crc32::{self, Hasher32};
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+/// A chunk read from, or to be written to, a PNG.
+///
+/// The CRC is computed by `Chunk::new`, or checked against the data when a
+/// chunk is parsed, so a `Chunk` always holds a valid one.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Chunk {
     length: u32,
     chunk_type: ChunkType,
@@ -12,29 +19,37 @@ pub struct Chunk {
     crc: u32,
 }
 
-impl<'a> Chunk {
-    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-        let chunk_type_and_data_bytes: Vec<u8> = chunk_type
-            .bytes()
-            .iter()
-            .chain(data.iter())
-            .copied()
-            .collect::<Vec<u8>>();
+impl Chunk {
+    /// Size of the length, chunk type and CRC fields that surround the data.
+    pub const OVERHEAD: usize = 12;
 
-        let crc = checksum_ieee(chunk_type_and_data_bytes.as_ref());
-        let length: u32 = data.len() as u32;
+    /// The most data a chunk can hold, as the spec limits the length field
+    /// to 2^31 - 1.
+    pub const MAX_LENGTH: usize = i32::MAX as usize;
 
-        let value: Vec<u8> = length
-            .to_be_bytes()
-            .iter()
-            .chain(chunk_type_and_data_bytes.iter())
-            .chain(crc.to_be_bytes().iter())
-            .copied()
-            .collect();
+    /// Creates a chunk and computes its CRC.
+    ///
+    /// Panics if `data` is too long for the length field, so callers with
+    /// data of arbitrary size should check it against `MAX_LENGTH` first.
+    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
+        let crc = checksum(&chunk_type.bytes(), &data);
+        let length = u32::try_from(data.len()).expect("chunk data is too long");
+
+        Self {
+            length,
+            chunk_type,
+            data,
+            crc,
+        }
+    }
 
-        Self::try_from(value.as_ref()).unwrap()
+    /// Creates a chunk whose type is known to be valid, such as one of the
+    /// types this crate decodes. Panics if `chunk_type` isn't 4 letters.
+    pub(crate) fn of_type(chunk_type: &str, data: impl Into<Vec<u8>>) -> Self {
+        Self::new(ChunkType::from_str(chunk_type).unwrap(), data.into())
     }
 
+    /// The chunk as it is stored in a file: length, type, data and CRC.
     pub fn as_bytes(&self) -> Vec<u8> {
         self.length
             .to_be_bytes()
@@ -46,54 +61,154 @@ impl<'a> Chunk {
             .collect()
     }
 
+    /// Number of bytes of data.
     pub fn length(&self) -> u32 {
         self.length
     }
 
+    /// The four letter name of the chunk.
     pub fn chunk_type(&self) -> &ChunkType {
         &self.chunk_type
     }
 
-    pub fn data_as_string(&self) -> Result<String, Error> {
-        Ok(String::from_utf8(self.data.to_owned()).unwrap())
+    /// The chunk's data, without the length, type and CRC fields.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Borrows this chunk as a `ChunkRef`.
+    pub fn as_chunk_ref(&self) -> ChunkRef<'_> {
+        ChunkRef {
+            chunk_type: self.chunk_type,
+            data: &self.data,
+            crc: self.crc,
+        }
+    }
+
+    /// The data as UTF-8 text.
+    pub fn data_as_string(&self) -> Result<String> {
+        Ok(String::from_utf8(self.data.clone())?)
     }
 
+    /// The CRC of the chunk type and data.
     pub fn crc(&self) -> u32 {
         self.crc
     }
 }
 
+fn checksum(chunk_type: &[u8], data: &[u8]) -> u32 {
+    let mut digest = crc32::Digest::new(crc32::IEEE);
+    digest.write(chunk_type);
+    digest.write(data);
+    digest.sum32()
+}
+
 impl TryFrom<&[u8]> for Chunk {
-    type Error = Error;
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        ChunkRef::try_from(value).map(Chunk::from)
+    }
+}
+
+impl From<ChunkRef<'_>> for Chunk {
+    fn from(chunk: ChunkRef) -> Self {
+        Self {
+            length: chunk.length(),
+            chunk_type: chunk.chunk_type,
+            data: chunk.data.to_vec(),
+            crc: chunk.crc,
+        }
+    }
+}
+
+/// A chunk that borrows its data from the bytes it was parsed from, so
+/// parsing one doesn't allocate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    crc: u32,
+}
+
+impl<'a> ChunkRef<'a> {
+    /// Number of bytes of data.
+    pub fn length(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    /// The four letter name of the chunk.
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    /// The chunk's data, borrowed from the parsed bytes.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The CRC of the chunk type and data.
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
 
-    fn try_from(value: &[u8]) -> crate::Result<Self> {
-        if value.len() < 12 {
-            return Err(Box::new(StrError("Length must be at least 12")));
+    /// Copies the data into an owned `Chunk`.
+    pub fn to_chunk(self) -> Chunk {
+        Chunk::from(self)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = PngError;
+
+    fn try_from(value: &'a [u8]) -> Result<Self> {
+        if value.len() < Chunk::OVERHEAD {
+            return Err(PngError::TruncatedChunk {
+                index: None,
+                offset: 0,
+                needed: Chunk::OVERHEAD,
+                available: value.len(),
+            });
         }
 
+        // The length check above makes these splits and conversions infallible.
         let (length_bytes, rest) = value.split_at(4);
         let length = u32::from_be_bytes(length_bytes.try_into().unwrap());
 
         let (chunk_type_bytes, rest) = rest.split_at(4);
         let chunk_type_bytes: [u8; 4] = chunk_type_bytes.try_into().unwrap();
-        let chunk_type = ChunkType::try_from(chunk_type_bytes).unwrap();
+        let chunk_type =
+            ChunkType::try_from(chunk_type_bytes).map_err(|_| PngError::InvalidChunkType {
+                index: None,
+                offset: 4,
+                bytes: chunk_type_bytes.to_vec(),
+            })?;
 
         let (data_bytes, crc_bytes) = rest.split_at(rest.len() - 4);
         let crc: u32 = u32::from_be_bytes(crc_bytes.try_into().unwrap());
-        let to_be_checksum: Vec<u8> = chunk_type_bytes.iter().chain(data_bytes).copied().collect();
 
-        if length != data_bytes.len() as u32 {
-            return Err(Box::new(StrError("Invalid chunk length")));
+        if length as usize != data_bytes.len() {
+            return Err(PngError::LengthMismatch {
+                index: None,
+                offset: 0,
+                declared: length,
+                actual: data_bytes.len(),
+            });
         }
 
-        if checksum_ieee(&to_be_checksum) != crc {
-            return Err(Box::new(StrError("Invalid chunk checksum")));
+        let expected_crc = checksum(&chunk_type_bytes, data_bytes);
+        if expected_crc != crc {
+            return Err(PngError::CrcMismatch {
+                index: None,
+                offset: value.len() - 4,
+                expected: expected_crc,
+                actual: crc,
+            });
         }
 
         Ok(Self {
-            length,
             chunk_type,
-            data: data_bytes.to_owned(),
+            data: data_bytes,
             crc,
         })
     }
@@ -101,13 +216,15 @@ impl TryFrom<&[u8]> for Chunk {
 
 impl fmt::Display for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.chunk_type().bytes())
+        write!(f, "{} ({} bytes)", self.chunk_type(), self.length())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
 
     fn testing_chunk() -> Chunk {
         let data_length: u32 = 42;
@@ -201,6 +318,102 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_chunk_crc_mismatch() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        let crc_offset = chunk_data.len() - 4;
+        chunk_data[crc_offset..].copy_from_slice(&2882656333u32.to_be_bytes());
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(
+            chunk,
+            Err(PngError::CrcMismatch {
+                index: None,
+                offset: 50,
+                expected: 2882656334,
+                actual: 2882656333,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_too_short() {
+        let chunk = Chunk::try_from([0, 0, 0, 0, 82, 117].as_ref());
+
+        assert!(matches!(
+            chunk,
+            Err(PngError::TruncatedChunk {
+                needed: 12,
+                available: 6,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_length_mismatch() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        chunk_data[..4].copy_from_slice(&100u32.to_be_bytes());
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(
+            chunk,
+            Err(PngError::LengthMismatch {
+                declared: 100,
+                actual: 42,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_invalid_type() {
+        #[rustfmt::skip]
+        let chunk_data = [
+            0, 0, 0, 0,       // length
+            82, 117, 49, 116, // Chunk Type (bad)
+            0, 0, 0, 0,       // CRC
+        ];
+
+        let chunk = Chunk::try_from(chunk_data.as_ref());
+
+        assert!(matches!(
+            chunk,
+            Err(PngError::InvalidChunkType { offset: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_new_chunk_matches_parsed_chunk() {
+        let chunk_type = ChunkType::try_from(*b"RuSt").unwrap();
+        let message = b"This is where your secret message will be!".to_vec();
+        let chunk = Chunk::new(chunk_type, message);
+
+        assert_eq!(chunk.as_bytes(), testing_chunk().as_bytes());
+    }
+
+    #[test]
+    fn test_chunk_ref_borrows_data() {
+        let chunk_data = testing_chunk().as_bytes();
+        let chunk = ChunkRef::try_from(chunk_data.as_ref()).unwrap();
+
+        assert_eq!(chunk.length(), 42);
+        assert_eq!(chunk.chunk_type().to_string(), "RuSt");
+        assert_eq!(chunk.crc(), 2882656334);
+        assert_eq!(chunk.data().as_ptr(), chunk_data[8..].as_ptr());
+        assert_eq!(chunk, testing_chunk().as_chunk_ref());
+    }
+
+    #[test]
+    fn test_chunk_ref_to_chunk() {
+        let chunk_data = testing_chunk().as_bytes();
+        let chunk = ChunkRef::try_from(chunk_data.as_ref()).unwrap().to_chunk();
+
+        assert_eq!(chunk.as_bytes(), chunk_data);
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -220,5 +433,28 @@ mod tests {
         let chunk: Chunk = TryFrom::try_from(chunk_data.as_ref()).unwrap();
 
         let _chunk_string = format!("{}", chunk);
+        assert_eq!(chunk.to_string(), "RuSt (42 bytes)");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(chunk_type in "[a-zA-Z]{4}", data in vec(any::<u8>(), 0..256)) {
+            let chunk = Chunk::new(ChunkType::from_str(&chunk_type)?, data);
+            prop_assert_eq!(Chunk::try_from(chunk.as_bytes().as_slice())?, chunk);
+        }
+
+        #[test]
+        fn prop_arbitrary_bytes_dont_panic(bytes in vec(any::<u8>(), 0..64)) {
+            let _ = Chunk::try_from(bytes.as_slice());
+        }
+
+        #[test]
+        fn prop_length_must_match(declared in any::<u32>(), data in vec(any::<u8>(), 0..64)) {
+            let mut bytes = Chunk::new(ChunkType::from_str("ruSt")?, data).as_bytes();
+            bytes[..4].copy_from_slice(&declared.to_be_bytes());
+
+            let result = Chunk::try_from(bytes.as_slice());
+            prop_assert_eq!(result.is_ok(), declared as usize == bytes.len() - Chunk::OVERHEAD);
+        }
     }
 }
@@ -1,18 +1,30 @@
+//! The four letter names of chunks and the properties their letter case
+//! encodes.
+
+use crate::PngError;
+use serde::{Serialize, Serializer};
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
 use std::str;
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Clone)]
+/// The four ASCII letters that name a chunk, like `IHDR` or `tEXt`.
+///
+/// The case of each letter is a flag; see the `is_*` methods. Parse one with
+/// `FromStr` or `TryFrom<[u8; 4]>`, which reject anything but letters.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
 
 impl ChunkType {
+    /// The four letters as bytes.
     pub fn bytes(&self) -> [u8; 4] {
         self.bytes
     }
 
+    /// Returns true if the chunk type is allowed by the current spec, which
+    /// means its reserved bit is clear.
     pub fn is_valid(&self) -> bool {
         self.is_reserved_bit_valid()
     }
@@ -44,42 +56,49 @@ impl ChunkType {
 
 impl fmt::Display for ChunkType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            str::from_utf8(&self.bytes)
-                .expect("Invalid UTF-8")
-                .to_string()
-        )
+        write!(f, "{}", str::from_utf8(&self.bytes).expect("Invalid UTF-8"))
+    }
+}
+
+impl Serialize for ChunkType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
     }
 }
 
 impl FromStr for ChunkType {
-    type Err = String;
+    type Err = PngError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bytes: [u8; 4] = s.as_bytes().try_into().expect("str with length 4");
+        let bytes: [u8; 4] = s
+            .as_bytes()
+            .try_into()
+            .map_err(|_| invalid_chunk_type(s.as_bytes()))?;
 
-        if !bytes.iter().map(|b| b.is_ascii_alphabetic()).all(|b| b) {
-            return Err("ASCII alphabets only bish".into());
-        }
-
-        Ok(Self { bytes })
+        Self::try_from(bytes)
     }
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
-    type Error = String;
+    type Error = PngError;
 
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
-        if let true = value.iter().map(u8::is_ascii).all(|v| v) {
+        if value.iter().all(u8::is_ascii_alphabetic) {
             Ok(Self { bytes: value })
         } else {
-            Err("Invalid ascii byte found".to_string())
+            Err(invalid_chunk_type(&value))
         }
     }
 }
 
+fn invalid_chunk_type(bytes: &[u8]) -> PngError {
+    PngError::InvalidChunkType {
+        index: None,
+        offset: 0,
+        bytes: bytes.to_vec(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,6 +183,21 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_wrong_length() {
+        let chunk = ChunkType::from_str("RuStacean");
+        assert!(matches!(
+            chunk,
+            Err(PngError::InvalidChunkType { ref bytes, .. }) if bytes == b"RuStacean"
+        ));
+    }
+
+    #[test]
+    pub fn test_chunk_type_from_non_alphabetic_bytes() {
+        let chunk = ChunkType::try_from([32, 117, 83, 116]);
+        assert!(matches!(chunk, Err(PngError::InvalidChunkType { .. })));
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
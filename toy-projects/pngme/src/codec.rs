@@ -0,0 +1,57 @@
+//! Helpers shared by the modules that decode the data of specific chunk
+//! types.
+
+use crate::chunk::Chunk;
+use crate::{PngError, Result};
+use std::convert::TryInto;
+
+/// Makes sure `chunk` is of type `chunk_type`.
+pub(crate) fn check_type(chunk: &Chunk, chunk_type: &str) -> Result<()> {
+    if chunk.chunk_type().bytes() != chunk_type.as_bytes() {
+        return Err(invalid(
+            chunk_type,
+            format!("found a {} chunk", chunk.chunk_type()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The data of `chunk`, after making sure it is of type `chunk_type` and
+/// holds exactly `length` bytes.
+pub(crate) fn checked_data<'a>(
+    chunk: &'a Chunk,
+    chunk_type: &str,
+    length: usize,
+) -> Result<&'a [u8]> {
+    check_type(chunk, chunk_type)?;
+    if chunk.data().len() != length {
+        return Err(invalid(
+            chunk_type,
+            format!(
+                "expected {} bytes of data but found {}",
+                length,
+                chunk.data().len()
+            ),
+        ));
+    }
+
+    Ok(chunk.data())
+}
+
+/// Reads 2 big endian bytes.
+pub(crate) fn be_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes(bytes.try_into().unwrap())
+}
+
+/// Reads 4 big endian bytes.
+pub(crate) fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes.try_into().unwrap())
+}
+
+pub(crate) fn invalid(chunk_type: &str, reason: String) -> PngError {
+    PngError::InvalidChunkData {
+        chunk_type: chunk_type.to_string(),
+        reason,
+    }
+}
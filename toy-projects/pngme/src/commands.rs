@@ -0,0 +1,848 @@
+use crate::args::{
+    BatchArgs, DecodeArgs, DiffArgs, EncodeArgs, FramesArgs, FramesDropArgs, FramesExtractArgs,
+    FramesListArgs, KeygenArgs, OutputFormat, PrintArgs, RemoveArgs, RepairArgs, SanitizeArgs,
+    SignArgs, StegoArgs, StegoCapacityArgs, StegoHideArgs, StegoRevealArgs, TextArgs,
+    TextDeleteArgs, TextGetArgs, TextListArgs, TextSetArgs, ValidateArgs, VerifyArgs,
+};
+use crate::batch::{Batch, Job, JobResult, Summary};
+use pngme::ancillary::AncillaryChunk;
+use pngme::apng::{self, Animation, AnimationControl, FrameControl};
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::container::{self, Container};
+use pngme::crypto;
+use pngme::ihdr::ImageHeader;
+use pngme::message::{self, FirstMessage};
+use pngme::pixels::PixelData;
+use pngme::png::Png;
+use pngme::repair::{self, RepairOptions};
+use pngme::report::{ChunkReport, DataEncoding, RiffChunkReport};
+use pngme::riff::{self, Riff};
+use pngme::sanitize::SanitizePolicy;
+use pngme::signature;
+use pngme::stego;
+use pngme::stream::{PngReader, PngWriter};
+use pngme::text::{TextEncoding, TextEntry};
+use pngme::validate::Validator;
+use pngme::{PngError, Result};
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+type FileReader = PngReader<BufReader<File>>;
+type FileWriter = PngWriter<BufWriter<File>>;
+
+/// Encodes a message into PNG files and saves the results
+pub fn encode(args: EncodeArgs) -> Result<()> {
+    let message = if args.from_file {
+        fs::read(&args.message)?
+    } else {
+        args.message.into_bytes()
+    };
+    let message = seal_message(message, &args.passphrase)?;
+    let (chunk_type, part_size) = (&args.chunk_type, args.part_size);
+
+    for_each_file(&args.files, OutputFormat::Text, |job, _| {
+        if is_riff_file(&job.input)? {
+            return edit_riff(&job.input, &job.output, |riff| {
+                container::add_message(riff, chunk_type, &message, part_size).map(|_| ())
+            });
+        }
+
+        // Checks the chunk type against PNG's rules.
+        let chunk_type = *Png::new_chunk(chunk_type, Vec::new())?.chunk_type();
+        let chunks = message::split(chunk_type, &message, part_size)?;
+        rewrite(&job.input, &job.output, |reader, writer| {
+            insert_before_iend(reader, writer, chunks, |_| true)
+        })
+    })
+}
+
+/// Searches for a message hidden in a PNG file and prints the message if one is found
+pub fn decode(args: DecodeArgs) -> Result<()> {
+    let (message, reports) = if is_riff_file(&args.file_path)? {
+        read_riff_message(&args.file_path, &args.chunk_type)?
+    } else {
+        read_png_message(&args.file_path, &args.chunk_type)?
+    };
+
+    let message = open_message(&message, &args.passphrase)?;
+    if let Some(output) = &args.output {
+        fs::write(output, &message)?;
+    }
+
+    match args.format {
+        OutputFormat::Text if args.output.is_none() => println!("{}", String::from_utf8(message)?),
+        OutputFormat::Text => {}
+        OutputFormat::Json => {
+            let json = serde_json::json!({
+                "chunk_type": args.chunk_type,
+                "chunks": reports,
+                "length": message.len(),
+                "message": std::str::from_utf8(&message).ok(),
+                "data": args.data.map(|encoding| encoding.encode(&message)),
+            });
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the message stored in `chunk_type` chunks of a PNG, along with a
+/// report on each of those chunks.
+fn read_png_message(path: &Path, chunk_type: &str) -> Result<(Vec<u8>, serde_json::Value)> {
+    let mut chunks = Vec::new();
+    let mut reports = Vec::new();
+    let mut reader = open_png(path)?;
+    let mut offset = reader.offset();
+    let mut index = 0;
+
+    while let Some(chunk) = reader.next() {
+        let chunk = chunk?;
+
+        if chunk.chunk_type().bytes() == chunk_type.as_bytes() {
+            reports.push(ChunkReport::new(index, offset, &chunk, None));
+            chunks.push(chunk);
+        }
+
+        index += 1;
+        offset = reader.offset();
+    }
+
+    if chunks.is_empty() {
+        return Err(PngError::ChunkNotFound {
+            chunk_type: chunk_type.to_string(),
+        });
+    }
+
+    Ok((message::assemble(&chunks)?, serde_json::to_value(reports)?))
+}
+
+/// The RIFF counterpart of `read_png_message`.
+fn read_riff_message(path: &Path, chunk_type: &str) -> Result<(Vec<u8>, serde_json::Value)> {
+    let riff = read_riff(path)?;
+    let message = container::read_message(&riff, chunk_type)?;
+    let reports: Vec<RiffChunkReport> = container::chunk_offsets(&riff)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, chunk))| chunk.id().bytes() == chunk_type.as_bytes())
+        .map(|(index, (offset, chunk))| RiffChunkReport::new(index, offset, chunk, None))
+        .collect();
+
+    Ok((message, serde_json::to_value(reports)?))
+}
+
+/// Removes a chunk from PNG files and saves the results. If the chunk is
+/// part of a message split over several chunks, every part is removed.
+pub fn remove(args: RemoveArgs) -> Result<()> {
+    for_each_file(&args.files, OutputFormat::Text, |job, out| {
+        remove_chunk(job, &args.chunk_type, out)
+    })
+}
+
+fn remove_chunk(job: &Job, chunk_type: &str, out: &mut dyn Write) -> Result<()> {
+    let removed = if is_riff_file(&job.input)? {
+        let mut removed = 0;
+        edit_riff(&job.input, &job.output, |riff| {
+            removed = container::remove_message(riff, chunk_type)?.len();
+            Ok(())
+        })?;
+        removed
+    } else {
+        remove_png_chunk(job, chunk_type)?
+    };
+
+    match removed {
+        1 => writeln!(out, "Removed chunk {}", chunk_type)?,
+        count => writeln!(out, "Removed {} {} chunks", count, chunk_type)?,
+    }
+
+    Ok(())
+}
+
+/// Streams a PNG through, leaving out the chunks `remove_chunk` describes,
+/// and returns how many were left out.
+fn remove_png_chunk(job: &Job, chunk_type: &str) -> Result<usize> {
+    let mut first = FirstMessage::new();
+    let mut removed = 0;
+
+    rewrite(&job.input, &job.output, |reader, writer| {
+        for chunk in reader {
+            let chunk = chunk?;
+            if chunk.chunk_type().bytes() == chunk_type.as_bytes() && first.includes(chunk.data()) {
+                removed += 1;
+            } else {
+                writer.write_chunk(&chunk)?;
+            }
+        }
+
+        if removed == 0 {
+            return Err(PngError::ChunkNotFound {
+                chunk_type: chunk_type.to_string(),
+            });
+        }
+
+        Ok(())
+    })?;
+
+    Ok(removed)
+}
+
+/// Prints all of the chunks in PNG files
+pub fn print_chunks(args: PrintArgs) -> Result<()> {
+    for_each_file(&args.files, args.format, |job, out| match args.format {
+        OutputFormat::Text => print_file(&job.input, out),
+        OutputFormat::Json => print_file_json(&job.input, args.data, out),
+    })
+}
+
+fn print_file(path: &Path, out: &mut dyn Write) -> Result<()> {
+    if is_riff_file(path)? {
+        for chunk in read_riff(path)?.chunks() {
+            writeln!(out, "{}", chunk)?;
+        }
+
+        return Ok(());
+    }
+
+    // bKGD and tRNS can only be decoded once the color type is known.
+    let mut color_type = None;
+
+    for chunk in open_png(path)? {
+        let chunk = chunk?;
+        writeln!(out, "{}", chunk)?;
+
+        let details = match &chunk.chunk_type().bytes() {
+            b"IHDR" => Some(ImageHeader::try_from(&chunk).map(|header| {
+                color_type = Some(header.color_type);
+                header.to_string()
+            })),
+            b"acTL" => Some(AnimationControl::try_from(&chunk).map(|control| control.to_string())),
+            b"fcTL" => Some(FrameControl::try_from(&chunk).map(|control| control.to_string())),
+            _ => AncillaryChunk::decode(&chunk, color_type)
+                .map(|decoded| decoded.map(|decoded| decoded.to_string())),
+        };
+
+        match details {
+            Some(Ok(details)) => writeln!(out, "    {}", details)?,
+            Some(Err(err)) => writeln!(out, "    {}", err)?,
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn print_file_json(path: &Path, data: Option<DataEncoding>, out: &mut dyn Write) -> Result<()> {
+    let chunks = if is_riff_file(path)? {
+        let riff = read_riff(path)?;
+        let reports: Vec<RiffChunkReport> = container::chunk_offsets(&riff)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (offset, chunk))| RiffChunkReport::new(index, offset, chunk, data))
+            .collect();
+        serde_json::to_value(reports)?
+    } else {
+        let mut reports = Vec::new();
+        let mut reader = open_png(path)?;
+        let mut offset = reader.offset();
+
+        while let Some(chunk) = reader.next() {
+            reports.push(ChunkReport::new(reports.len(), offset, &chunk?, data));
+            offset = reader.offset();
+        }
+        serde_json::to_value(reports)?
+    };
+
+    let json = serde_json::json!({ "chunks": chunks });
+    writeln!(out, "{}", serde_json::to_string_pretty(&json)?)?;
+
+    Ok(())
+}
+
+/// Checks a PNG file against the chunk ordering rules and prints every
+/// problem found
+pub fn validate(args: ValidateArgs) -> Result<()> {
+    let mut validator = Validator::new();
+    for chunk in open_png(&args.file_path)? {
+        validator.check(&chunk?);
+    }
+
+    let violations = validator.finish();
+    if violations.is_empty() {
+        println!("OK");
+        return Ok(());
+    }
+
+    for violation in &violations {
+        println!("{}", violation);
+    }
+
+    Err(PngError::InvalidStructure {
+        violations: violations.len(),
+    })
+}
+
+/// Lists, reads or edits the textual metadata in a PNG file
+pub fn text(args: TextArgs) -> Result<()> {
+    match args {
+        TextArgs::List(args) => text_list(args),
+        TextArgs::Get(args) => text_get(args),
+        TextArgs::Set(args) => text_set(args),
+        TextArgs::Delete(args) => text_delete(args),
+    }
+}
+
+fn text_list(args: TextListArgs) -> Result<()> {
+    for chunk in open_png(&args.file_path)? {
+        let chunk = chunk?;
+
+        if TextEntry::is_text_chunk(chunk.chunk_type()) {
+            match TextEntry::try_from(&chunk) {
+                Ok(entry) => println!("{}", entry),
+                Err(err) => println!("{}", err),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn text_get(args: TextGetArgs) -> Result<()> {
+    for chunk in open_png(&args.file_path)? {
+        if let Some(entry) = text_entry(&chunk?, &args.keyword) {
+            println!("{}", entry.text);
+            return Ok(());
+        }
+    }
+
+    Err(PngError::TextNotFound {
+        keyword: args.keyword,
+    })
+}
+
+fn text_set(args: TextSetArgs) -> Result<()> {
+    let TextSetArgs {
+        file_path,
+        keyword,
+        text,
+        compress,
+        language_tag,
+        translated_keyword,
+    } = args;
+
+    let mut entry = TextEntry::new(&keyword, &text);
+
+    if language_tag.is_some() || translated_keyword.is_some() {
+        entry.encoding = TextEncoding::International {
+            compressed: compress,
+            language_tag: language_tag.unwrap_or_default(),
+            translated_keyword: translated_keyword.unwrap_or_default(),
+        };
+    } else if compress {
+        entry.encoding = match entry.encoding {
+            TextEncoding::Plain => TextEncoding::Compressed,
+            TextEncoding::International {
+                language_tag,
+                translated_keyword,
+                ..
+            } => TextEncoding::International {
+                compressed: true,
+                language_tag,
+                translated_keyword,
+            },
+            encoding => encoding,
+        };
+    }
+
+    let chunk = entry.to_chunk()?;
+
+    rewrite(&file_path, &file_path, |reader, writer| {
+        insert_before_iend(reader, writer, vec![chunk], |chunk| {
+            text_entry(chunk, &keyword).is_none()
+        })
+    })
+}
+
+fn text_delete(args: TextDeleteArgs) -> Result<()> {
+    let mut removed = 0;
+
+    rewrite(&args.file_path, &args.file_path, |reader, writer| {
+        for chunk in reader {
+            let chunk = chunk?;
+
+            if text_entry(&chunk, &args.keyword).is_some() {
+                removed += 1;
+            } else {
+                writer.write_chunk(&chunk)?;
+            }
+        }
+
+        if removed == 0 {
+            return Err(PngError::TextNotFound {
+                keyword: args.keyword.clone(),
+            });
+        }
+
+        Ok(())
+    })?;
+
+    println!("Removed {} text chunk(s)", removed);
+
+    Ok(())
+}
+
+/// Hides messages in, or reads them from, the pixels of a PNG file
+pub fn stego(args: StegoArgs) -> Result<()> {
+    match args {
+        StegoArgs::Capacity(args) => stego_capacity(args),
+        StegoArgs::Hide(args) => stego_hide(args),
+        StegoArgs::Reveal(args) => stego_reveal(args),
+    }
+}
+
+fn stego_capacity(args: StegoCapacityArgs) -> Result<()> {
+    let png = read_png(&args.file_path)?;
+    println!("{} bytes", stego::capacity(&png.header()?)?);
+
+    Ok(())
+}
+
+fn stego_hide(args: StegoHideArgs) -> Result<()> {
+    let mut png = read_png(&args.file_path)?;
+    let mut pixels = PixelData::decode(png.header()?, &png.image_data())?;
+
+    let message = seal_message(args.message.into_bytes(), &args.passphrase)?;
+    stego::embed(&mut pixels, &message)?;
+    png.replace_image_data(&pixels.encode()?);
+
+    save_png(&png, args.output.as_ref().unwrap_or(&args.file_path))
+}
+
+fn stego_reveal(args: StegoRevealArgs) -> Result<()> {
+    let png = read_png(&args.file_path)?;
+    let pixels = PixelData::decode(png.header()?, &png.image_data())?;
+    let message = stego::extract(&pixels)?;
+
+    let message = open_message(&message, &args.passphrase)?;
+    println!("{}", String::from_utf8(message)?);
+
+    Ok(())
+}
+
+/// Prints the chunk-level differences between two PNG files
+pub fn diff(args: DiffArgs) -> Result<()> {
+    let old = read_png(&args.old_file_path)?;
+    let new = read_png(&args.new_file_path)?;
+    let diff = old.diff(&new);
+
+    match args.format {
+        OutputFormat::Text => print!("{}", diff),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+    }
+
+    Ok(())
+}
+
+/// Strips private chunks and metadata from PNG files and prints what was
+/// removed
+pub fn sanitize(args: SanitizeArgs) -> Result<()> {
+    let mut policy = SanitizePolicy::new();
+    for chunk_type in &args.keep {
+        policy.keep.insert(ChunkType::from_str(chunk_type)?);
+    }
+    for chunk_type in &args.strip {
+        policy.strip.insert(ChunkType::from_str(chunk_type)?);
+    }
+
+    for_each_file(&args.files, OutputFormat::Text, |job, out| {
+        sanitize_file(job, &policy, out)
+    })
+}
+
+fn sanitize_file(job: &Job, policy: &SanitizePolicy, out: &mut dyn Write) -> Result<()> {
+    let mut removed = Vec::new();
+
+    rewrite(&job.input, &job.output, |reader, writer| {
+        for chunk in reader {
+            let chunk = chunk?;
+
+            match policy.strip_reason(chunk.chunk_type()) {
+                Some(reason) => removed.push((*chunk.chunk_type(), chunk.length(), reason)),
+                None => writer.write_chunk(&chunk)?,
+            }
+        }
+
+        Ok(())
+    })?;
+
+    for (chunk_type, length, reason) in &removed {
+        writeln!(out, "Removed {} ({} bytes, {})", chunk_type, length, reason)?;
+    }
+    writeln!(out, "Removed {} chunk(s)", removed.len())?;
+
+    Ok(())
+}
+
+/// Salvages the readable chunks of a damaged PNG file into a new file and
+/// prints what had to be fixed
+pub fn repair(args: RepairArgs) -> Result<()> {
+    let options = RepairOptions {
+        fix_crcs: args.fix_crcs,
+    };
+    let repair = repair::recover(&fs::read(&args.file_path)?, options)?;
+    save_png(&repair.png, &args.output)?;
+
+    for fix in &repair.fixes {
+        println!("{}", fix);
+    }
+    println!(
+        "Recovered {} chunk(s), made {} fix(es)",
+        repair.png.chunks().len(),
+        repair.fixes.len()
+    );
+
+    Ok(())
+}
+
+/// Lists, extracts or drops the frames of an animated PNG file
+pub fn frames(args: FramesArgs) -> Result<()> {
+    match args {
+        FramesArgs::List(args) => frames_list(args),
+        FramesArgs::Extract(args) => frames_extract(args),
+        FramesArgs::Drop(args) => frames_drop(args),
+    }
+}
+
+fn frames_list(args: FramesListArgs) -> Result<()> {
+    let animation = Animation::from_png(&read_png(&args.file_path)?)?;
+    println!("{}", animation.control);
+
+    for (index, frame) in animation.frames.iter().enumerate() {
+        let default_image = if frame.is_default_image {
+            " (default image)"
+        } else {
+            ""
+        };
+        println!("Frame {}: {}{}", index, frame.control, default_image);
+    }
+
+    Ok(())
+}
+
+fn frames_extract(args: FramesExtractArgs) -> Result<()> {
+    let frame_png = apng::extract_frame(&read_png(&args.file_path)?, args.index)?;
+    save_png(&frame_png, &args.output)
+}
+
+fn frames_drop(args: FramesDropArgs) -> Result<()> {
+    let png = apng::drop_frame(&read_png(&args.file_path)?, args.index)?;
+    save_png(&png, args.output.as_ref().unwrap_or(&args.file_path))?;
+
+    println!("Dropped frame {}", args.index);
+
+    Ok(())
+}
+
+/// Generates a signing key and writes it and its public key to files
+pub fn keygen(args: KeygenArgs) -> Result<()> {
+    let mut public_path = args.key_file.clone().into_os_string();
+    public_path.push(".pub");
+    let public_path = PathBuf::from(public_path);
+
+    let key = signature::generate_key();
+    write_new(&args.key_file, &signature::signing_key_to_hex(&key), 0o600)?;
+    write_new(
+        &public_path,
+        &signature::verifying_key_to_hex(&key.verifying_key()),
+        0o644,
+    )?;
+
+    println!(
+        "Wrote signing key to {} and public key to {}",
+        args.key_file.display(),
+        public_path.display()
+    );
+
+    Ok(())
+}
+
+/// Writes `contents` and a newline to a file that must not exist yet, so
+/// keys are never overwritten by accident. `mode` sets the permissions on
+/// Unix.
+fn write_new(path: &Path, contents: &str, mode: u32) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut file = options.open(path)?;
+    writeln!(file, "{}", contents)?;
+
+    Ok(())
+}
+
+/// Signs every chunk of a PNG file and stores the signature in the file
+pub fn sign(args: SignArgs) -> Result<()> {
+    let key = signature::signing_key_from_hex(&fs::read_to_string(&args.key_file)?)?;
+    let mut png = read_png(&args.file_path)?;
+    signature::sign(&mut png, &key)?;
+    save_png(&png, args.output.as_ref().unwrap_or(&args.file_path))?;
+
+    println!(
+        "Signed with {}",
+        signature::verifying_key_to_hex(&key.verifying_key())
+    );
+
+    Ok(())
+}
+
+/// Checks a PNG file's signature against a public key
+pub fn verify(args: VerifyArgs) -> Result<()> {
+    let key = signature::verifying_key_from_hex(&fs::read_to_string(&args.key_file)?)?;
+    signature::verify(&read_png(&args.file_path)?, &key)?;
+
+    println!("OK");
+
+    Ok(())
+}
+
+/// Runs `process` on every file `files` names.
+///
+/// A single file is processed as usual, printing straight to stdout. A
+/// directory or glob pattern is processed in parallel, then each file is
+/// listed with whether it worked and what it printed, followed by a summary.
+/// With JSON output, that listing is one JSON document holding what
+/// `process` printed for each file. Fails if any of the files did.
+fn for_each_file<F>(files: &BatchArgs, format: OutputFormat, process: F) -> Result<()>
+where
+    F: Fn(&Job, &mut dyn Write) -> Result<()> + Sync,
+{
+    let mut batch = Batch::expand(&files.input, files.out_dir.as_deref())?;
+    if let Some(output) = &files.output {
+        batch = batch.with_output(output)?;
+    }
+
+    if batch.single {
+        return process(&batch.jobs[0], &mut io::stdout());
+    }
+
+    let results = batch.run(files.jobs, process)?;
+    let summary = Summary::of(&results);
+    if format == OutputFormat::Json {
+        print_batch_json(&results, summary)?;
+        return summary.into_result();
+    }
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    for job in &results {
+        let path = job.job.input.display();
+        match &job.result {
+            Ok(()) => writeln!(stdout, "{}: ok", path)?,
+            Err(err) => writeln!(stdout, "{}: failed: {}", path, err)?,
+        }
+
+        for line in String::from_utf8_lossy(&job.output).lines() {
+            writeln!(stdout, "    {}", line)?;
+        }
+    }
+
+    writeln!(stdout, "{}", summary)?;
+
+    summary.into_result()
+}
+
+fn print_batch_json(results: &[JobResult], summary: Summary) -> Result<()> {
+    let mut files = Vec::with_capacity(results.len());
+    for job in results {
+        let file = job.job.input.display().to_string();
+        files.push(match &job.result {
+            Ok(()) => serde_json::json!({
+                "file": file,
+                "result": serde_json::from_slice::<serde_json::Value>(&job.output)?,
+            }),
+            Err(err) => serde_json::json!({ "file": file, "error": err.to_string() }),
+        });
+    }
+
+    let json = serde_json::json!({
+        "files": files,
+        "succeeded": summary.succeeded,
+        "failed": summary.failed,
+    });
+    println!("{}", serde_json::to_string_pretty(&json)?);
+
+    Ok(())
+}
+
+/// Encrypts `message` if a passphrase was given.
+fn seal_message(message: Vec<u8>, passphrase: &Option<String>) -> Result<Vec<u8>> {
+    match passphrase {
+        Some(passphrase) => crypto::encrypt(&message, passphrase),
+        None => Ok(message),
+    }
+}
+
+/// Decrypts `data` if a passphrase was given, and refuses to hand back an
+/// encrypted message as if it were plain text when none was.
+fn open_message(data: &[u8], passphrase: &Option<String>) -> Result<Vec<u8>> {
+    match passphrase {
+        Some(passphrase) => crypto::decrypt(data, passphrase),
+        None if crypto::is_encrypted(data) => Err(PngError::PassphraseRequired),
+        None => Ok(data.to_vec()),
+    }
+}
+
+/// Decodes `chunk` if it is a textual chunk with the given keyword.
+fn text_entry(chunk: &Chunk, keyword: &str) -> Option<TextEntry> {
+    if !TextEntry::is_text_chunk(chunk.chunk_type()) {
+        return None;
+    }
+
+    TextEntry::try_from(chunk)
+        .ok()
+        .filter(|entry| entry.keyword == keyword)
+}
+
+/// Copies every chunk that `keep` returns true for, and writes `new_chunks`
+/// right before IEND so the result is still a well-formed PNG. If there is
+/// no IEND, `new_chunks` go at the end.
+fn insert_before_iend<F>(
+    reader: FileReader,
+    writer: &mut FileWriter,
+    new_chunks: Vec<Chunk>,
+    mut keep: F,
+) -> Result<()>
+where
+    F: FnMut(&Chunk) -> bool,
+{
+    let mut new_chunks = Some(new_chunks);
+
+    for chunk in reader {
+        let chunk = chunk?;
+
+        if chunk.chunk_type().bytes() == *b"IEND" {
+            for new_chunk in new_chunks.take().unwrap_or_default() {
+                writer.write_chunk(&new_chunk)?;
+            }
+        }
+
+        if keep(&chunk) {
+            writer.write_chunk(&chunk)?;
+        }
+    }
+
+    for new_chunk in new_chunks.unwrap_or_default() {
+        writer.write_chunk(&new_chunk)?;
+    }
+
+    Ok(())
+}
+
+fn open_png(path: &Path) -> Result<FileReader> {
+    PngReader::new(BufReader::new(File::open(path)?))
+}
+
+/// Loads a whole PNG file, for edits that can't be done one chunk at a time.
+fn read_png(path: &Path) -> Result<Png> {
+    Png::try_from(fs::read(path)?.as_slice())
+}
+
+/// Writes a PNG that was loaded and edited in memory to `output`.
+fn save_png(png: &Png, output: &Path) -> Result<()> {
+    write_png(output, |writer| {
+        for chunk in png.chunks() {
+            writer.write_chunk(chunk)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Streams the PNG at `input` through `edit` into `output`.
+fn rewrite<F>(input: &Path, output: &Path, edit: F) -> Result<()>
+where
+    F: FnOnce(FileReader, &mut FileWriter) -> Result<()>,
+{
+    let reader = open_png(input)?;
+    write_png(output, |writer| edit(reader, writer))
+}
+
+/// Returns true if the file at `path` starts like a RIFF file, such as a
+/// WebP image, rather than a PNG.
+fn is_riff_file(path: &Path) -> Result<bool> {
+    let mut start = Vec::with_capacity(Riff::SIGNATURE.len());
+    File::open(path)?
+        .take(Riff::SIGNATURE.len() as u64)
+        .read_to_end(&mut start)?;
+    Ok(riff::is_riff(&start))
+}
+
+fn read_riff(path: &Path) -> Result<Riff> {
+    Riff::try_from(fs::read(path)?.as_slice())
+}
+
+/// Loads the RIFF file at `input`, applies `edit` and writes the result to
+/// `output`.
+fn edit_riff<F>(input: &Path, output: &Path, edit: F) -> Result<()>
+where
+    F: FnOnce(&mut Riff) -> Result<()>,
+{
+    let mut riff = read_riff(input)?;
+    edit(&mut riff)?;
+    write_atomically(output, |file| {
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&riff.as_bytes())?;
+        Ok(writer.flush()?)
+    })
+}
+
+/// Writes the chunks `write` produces to `output` as a PNG.
+fn write_png<F>(output: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut FileWriter) -> Result<()>,
+{
+    write_atomically(output, |file| {
+        let mut writer = PngWriter::new(BufWriter::new(file))?;
+        write(&mut writer)?;
+        writer.finish()?;
+        Ok(())
+    })
+}
+
+/// Creates `output` with whatever `write` writes to it.
+///
+/// The result goes to a temporary file next to `output` that is renamed over
+/// it at the end, so `output` can be the file being read and is left alone
+/// if anything fails.
+fn write_atomically<F>(output: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(File) -> Result<()>,
+{
+    let tmp_path = tmp_path(output);
+
+    let result = File::create(&tmp_path)
+        .map_err(PngError::from)
+        .and_then(write)
+        .and_then(|_| Ok(fs::rename(&tmp_path, output)?));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    file_name.push(".pngme-tmp");
+    path.with_file_name(file_name)
+}
@@ -0,0 +1,354 @@
+//! What the chunk-based formats pngme handles have in common, so messages
+//! can be hidden in, read from and removed from any of them the same way.
+//!
+//! PNG and RIFF (WebP) files are both a header followed by a list of chunks
+//! named by four bytes, but they frame each chunk differently: PNG has a
+//! big-endian length and a CRC, RIFF a little-endian length and padding to
+//! an even size. [`Container`] hides those differences behind parsing,
+//! writing and editing the list of chunks.
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::message::{self, FirstMessage};
+use crate::png::Png;
+use crate::riff::{FourCc, Riff, RiffChunk};
+use crate::{PngError, Result};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// A chunk of a [`Container`]: four bytes naming it and its data.
+pub trait ContainerChunk: Clone + fmt::Display {
+    /// The four bytes that name the chunk.
+    fn type_bytes(&self) -> [u8; 4];
+
+    /// The chunk's data, without any framing.
+    fn data(&self) -> &[u8];
+
+    /// The chunk as it is stored in a file.
+    fn as_bytes(&self) -> Vec<u8>;
+}
+
+/// A file made of a header and a list of chunks.
+pub trait Container: Sized {
+    /// The kind of chunk the file holds.
+    type Chunk: ContainerChunk;
+
+    /// Size of everything before the first chunk.
+    const HEADER_LEN: usize;
+
+    /// Parses a whole file.
+    fn parse(bytes: &[u8]) -> Result<Self>;
+
+    /// The whole file, ready to be written out.
+    fn as_bytes(&self) -> Vec<u8>;
+
+    /// Every chunk, in file order.
+    fn chunks(&self) -> &[Self::Chunk];
+
+    /// Creates a chunk, checking that `chunk_type` is a name the format
+    /// allows.
+    fn new_chunk(chunk_type: &str, data: Vec<u8>) -> Result<Self::Chunk>;
+
+    /// Where new chunks should go. In a PNG that is before `IEND`, in RIFF
+    /// at the end.
+    fn insert_position(&self) -> usize;
+
+    /// Inserts a chunk so that it ends up at `index`.
+    fn insert_chunk_at(&mut self, index: usize, chunk: Self::Chunk) -> Result<()>;
+
+    /// Keeps only the chunks `keep` returns true for, in order, and returns
+    /// the ones that were removed.
+    fn retain<F>(&mut self, keep: F) -> Vec<Self::Chunk>
+    where
+        F: FnMut(&Self::Chunk) -> bool;
+}
+
+impl ContainerChunk for Chunk {
+    fn type_bytes(&self) -> [u8; 4] {
+        self.chunk_type().bytes()
+    }
+
+    fn data(&self) -> &[u8] {
+        Chunk::data(self)
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        Chunk::as_bytes(self)
+    }
+}
+
+impl Container for Png {
+    type Chunk = Chunk;
+
+    const HEADER_LEN: usize = Png::STANDARD_HEADER.len();
+
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        Png::try_from(bytes)
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        Png::as_bytes(self)
+    }
+
+    fn chunks(&self) -> &[Chunk] {
+        Png::chunks(self)
+    }
+
+    fn new_chunk(chunk_type: &str, data: Vec<u8>) -> Result<Chunk> {
+        let chunk_type = ChunkType::from_str(chunk_type)?;
+        if !chunk_type.is_valid() {
+            return Err(PngError::InvalidChunkType {
+                index: None,
+                offset: 0,
+                bytes: chunk_type.bytes().to_vec(),
+            });
+        }
+
+        Ok(Chunk::new(chunk_type, data))
+    }
+
+    fn insert_position(&self) -> usize {
+        self.chunks()
+            .iter()
+            .position(|chunk| chunk.chunk_type().bytes() == *b"IEND")
+            .unwrap_or_else(|| self.chunks().len())
+    }
+
+    fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        Png::insert_chunk_at(self, index, chunk)
+    }
+
+    fn retain<F>(&mut self, keep: F) -> Vec<Chunk>
+    where
+        F: FnMut(&Chunk) -> bool,
+    {
+        Png::retain(self, keep)
+    }
+}
+
+impl ContainerChunk for RiffChunk {
+    fn type_bytes(&self) -> [u8; 4] {
+        self.id().bytes()
+    }
+
+    fn data(&self) -> &[u8] {
+        RiffChunk::data(self)
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        RiffChunk::as_bytes(self)
+    }
+}
+
+impl Container for Riff {
+    type Chunk = RiffChunk;
+
+    const HEADER_LEN: usize = Riff::HEADER_LEN;
+
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        Riff::try_from(bytes)
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        Riff::as_bytes(self)
+    }
+
+    fn chunks(&self) -> &[RiffChunk] {
+        Riff::chunks(self)
+    }
+
+    fn new_chunk(chunk_type: &str, data: Vec<u8>) -> Result<RiffChunk> {
+        Ok(RiffChunk::new(FourCc::from_str(chunk_type)?, data))
+    }
+
+    fn insert_position(&self) -> usize {
+        self.chunks().len()
+    }
+
+    fn insert_chunk_at(&mut self, index: usize, chunk: RiffChunk) -> Result<()> {
+        Riff::insert_chunk_at(self, index, chunk)
+    }
+
+    fn retain<F>(&mut self, keep: F) -> Vec<RiffChunk>
+    where
+        F: FnMut(&RiffChunk) -> bool,
+    {
+        Riff::retain(self, keep)
+    }
+}
+
+/// Stores `payload` in new chunks of type `chunk_type`, split into parts of
+/// at most `part_size` bytes like `message::split` does, and returns how
+/// many chunks it took.
+pub fn add_message<C: Container>(
+    file: &mut C,
+    chunk_type: &str,
+    payload: &[u8],
+    part_size: usize,
+) -> Result<usize> {
+    let parts = message::split_payload(payload, part_size)?;
+    let count = parts.len();
+
+    let start = file.insert_position();
+    for (position, data) in (start..).zip(parts) {
+        file.insert_chunk_at(position, C::new_chunk(chunk_type, data)?)?;
+    }
+
+    Ok(count)
+}
+
+/// Puts back together the message stored in the chunks of type
+/// `chunk_type`.
+pub fn read_message<C: Container>(file: &C, chunk_type: &str) -> Result<Vec<u8>> {
+    let parts: Vec<&[u8]> = chunks_of_type(file, chunk_type)
+        .map(ContainerChunk::data)
+        .collect();
+    if parts.is_empty() {
+        return Err(PngError::ChunkNotFound {
+            chunk_type: chunk_type.to_string(),
+        });
+    }
+
+    message::assemble_payload(chunk_type, &parts)
+}
+
+/// Removes the first chunk of type `chunk_type` and returns what was
+/// removed. If that chunk is part of a split message, the other parts after
+/// it go too.
+pub fn remove_message<C: Container>(file: &mut C, chunk_type: &str) -> Result<Vec<C::Chunk>> {
+    let mut first = FirstMessage::new();
+    let removed = file.retain(|chunk| {
+        chunk.type_bytes() != chunk_type.as_bytes() || !first.includes(chunk.data())
+    });
+
+    if removed.is_empty() {
+        return Err(PngError::ChunkNotFound {
+            chunk_type: chunk_type.to_string(),
+        });
+    }
+
+    Ok(removed)
+}
+
+/// Every chunk of type `chunk_type`, in file order.
+pub fn chunks_of_type<'a, C: Container>(
+    file: &'a C,
+    chunk_type: &'a str,
+) -> impl Iterator<Item = &'a C::Chunk> + 'a {
+    file.chunks()
+        .iter()
+        .filter(move |chunk| chunk.type_bytes() == chunk_type.as_bytes())
+}
+
+/// Pairs each chunk with the offset its first byte is at in the file.
+pub fn chunk_offsets<C: Container>(file: &C) -> Vec<(usize, &C::Chunk)> {
+    let mut offset = C::HEADER_LEN;
+    file.chunks()
+        .iter()
+        .map(|chunk| {
+            let start = offset;
+            offset += chunk.as_bytes().len();
+            (start, chunk)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+    const WEBP_FILE: &[u8] = include_bytes!("../tests/fixtures/tiny.webp");
+
+    fn chunk_types<C: Container>(file: &C) -> Vec<String> {
+        file.chunks()
+            .iter()
+            .map(|chunk| String::from_utf8_lossy(&chunk.type_bytes()).into_owned())
+            .collect()
+    }
+
+    /// Adds, reads and removes a message split over three chunks.
+    fn round_trip<C: Container>(bytes: &[u8]) -> C {
+        let mut file = C::parse(bytes).unwrap();
+        let payload: Vec<u8> = (0..250).map(|i| i as u8).collect();
+
+        assert_eq!(add_message(&mut file, "ruSt", &payload, 100).unwrap(), 3);
+        let file = C::parse(&file.as_bytes()).unwrap();
+        assert_eq!(read_message(&file, "ruSt").unwrap(), payload);
+
+        let mut without = C::parse(&file.as_bytes()).unwrap();
+        assert_eq!(remove_message(&mut without, "ruSt").unwrap().len(), 3);
+        assert_eq!(without.as_bytes(), bytes);
+
+        file
+    }
+
+    #[test]
+    fn test_png_message_round_trip() {
+        let png: Png = round_trip(PNG_FILE);
+        let types = chunk_types(&png);
+
+        assert_eq!(types[types.len() - 4..], ["ruSt", "ruSt", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_riff_message_round_trip() {
+        let riff: Riff = round_trip(WEBP_FILE);
+
+        assert_eq!(chunk_types(&riff), ["VP8L", "ruSt", "ruSt", "ruSt"]);
+    }
+
+    #[test]
+    fn test_single_chunk_message() {
+        let mut riff = Riff::parse(WEBP_FILE).unwrap();
+        add_message(&mut riff, "MSG ", b"hello", 100).unwrap();
+
+        assert_eq!(read_message(&riff, "MSG ").unwrap(), b"hello");
+        assert_eq!(riff.chunk_by_id("MSG ").unwrap().data(), b"hello");
+    }
+
+    #[test]
+    fn test_remove_only_first_plain_chunk() {
+        let mut png = Png::parse(PNG_FILE).unwrap();
+        add_message(&mut png, "ruSt", b"one", 100).unwrap();
+        add_message(&mut png, "ruSt", b"two", 100).unwrap();
+
+        assert_eq!(remove_message(&mut png, "ruSt").unwrap().len(), 1);
+        assert_eq!(read_message(&png, "ruSt").unwrap(), b"two");
+    }
+
+    #[test]
+    fn test_missing_message() {
+        let mut riff = Riff::parse(WEBP_FILE).unwrap();
+
+        assert!(matches!(
+            read_message(&riff, "ruSt"),
+            Err(PngError::ChunkNotFound { .. })
+        ));
+        assert!(matches!(
+            remove_message(&mut riff, "ruSt"),
+            Err(PngError::ChunkNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_type_rules_follow_the_format() {
+        // RIFF codes can have spaces and digits, PNG types can't.
+        assert!(Riff::new_chunk("XMP ", Vec::new()).is_ok());
+        assert!(Png::new_chunk("XMP ", Vec::new()).is_err());
+        // PNG types must have the reserved bit clear.
+        assert!(Png::new_chunk("ruse", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_chunk_offsets() {
+        let riff = Riff::parse(WEBP_FILE).unwrap();
+        assert_eq!(chunk_offsets(&riff)[0].0, 12);
+
+        let png = Png::parse(PNG_FILE).unwrap();
+        let offsets = chunk_offsets(&png);
+        assert_eq!(offsets[0].0, 8);
+        assert_eq!(offsets[1].0, 8 + 12 + 13);
+    }
+}
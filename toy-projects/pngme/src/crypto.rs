@@ -0,0 +1,185 @@
+//! Passphrase encryption for messages stored in chunks.
+
+use crate::{PngError, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+// Layout of a sealed message:
+//
+//   | MAGIC (8) | VERSION (1) | salt (16) | nonce (12) | ciphertext + tag |
+//
+// The key is derived from the passphrase and salt with Argon2id, and the
+// message is sealed with ChaCha20-Poly1305. The header bytes before the
+// ciphertext are passed as associated data, so tampering with them also fails
+// authentication.
+
+const MAGIC: &[u8; 8] = b"PNGMEenc";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+const TAG_LEN: usize = 16;
+
+/// Returns true if `payload` looks like a message sealed by `encrypt`.
+pub fn is_encrypted(payload: &[u8]) -> bool {
+    payload.starts_with(MAGIC)
+}
+
+/// Seals `plaintext` with a key derived from `passphrase`. A fresh salt and
+/// nonce are generated for every call and stored in the returned payload.
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut payload = Vec::with_capacity(HEADER_LEN + plaintext.len() + TAG_LEN);
+    payload.extend_from_slice(MAGIC);
+    payload.push(VERSION);
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+
+    let cipher = cipher(passphrase, &salt)?;
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: &payload,
+            },
+        )
+        .map_err(|_| invalid_payload("encryption failed"))?;
+
+    payload.extend(ciphertext);
+    Ok(payload)
+}
+
+/// Opens a payload sealed by `encrypt`. Fails with `WrongPassphrase` if the
+/// passphrase is wrong or the payload was changed.
+pub fn decrypt(payload: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    if !is_encrypted(payload) {
+        return Err(invalid_payload("message is not encrypted"));
+    }
+    if payload.len() < HEADER_LEN + TAG_LEN {
+        return Err(invalid_payload("message is too short"));
+    }
+
+    let (header, ciphertext) = payload.split_at(HEADER_LEN);
+    if header[MAGIC.len()] != VERSION {
+        return Err(invalid_payload("unknown encryption version"));
+    }
+
+    let salt = &header[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+    let nonce = Nonce::from_slice(&header[HEADER_LEN - NONCE_LEN..]);
+
+    cipher(passphrase, salt)?
+        .decrypt(
+            nonce,
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| PngError::WrongPassphrase)
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|_| invalid_payload("key derivation failed"))?;
+
+    Ok(ChaCha20Poly1305::new(&key))
+}
+
+fn invalid_payload(reason: &str) -> PngError {
+    PngError::InvalidPayload {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let payload = encrypt(b"This is a secret message!", "hunter2").unwrap();
+
+        assert!(is_encrypted(&payload));
+        assert_eq!(
+            decrypt(&payload, "hunter2").unwrap(),
+            b"This is a secret message!"
+        );
+    }
+
+    #[test]
+    fn test_ciphertext_hides_message() {
+        let payload = encrypt(b"This is a secret message!", "hunter2").unwrap();
+
+        assert!(!payload.windows(6).any(|window| window == b"secret"));
+    }
+
+    #[test]
+    fn test_fresh_salt_and_nonce() {
+        let first = encrypt(b"message", "hunter2").unwrap();
+        let second = encrypt(b"message", "hunter2").unwrap();
+
+        assert_ne!(first[..HEADER_LEN], second[..HEADER_LEN]);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let payload = encrypt(b"message", "hunter2").unwrap();
+
+        assert!(matches!(
+            decrypt(&payload, "hunter3"),
+            Err(PngError::WrongPassphrase)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_payload() {
+        for &i in [MAGIC.len() + 1, HEADER_LEN - 1, HEADER_LEN].iter() {
+            let mut payload = encrypt(b"message", "hunter2").unwrap();
+            payload[i] ^= 1;
+
+            assert!(matches!(
+                decrypt(&payload, "hunter2"),
+                Err(PngError::WrongPassphrase)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_not_encrypted() {
+        assert!(!is_encrypted(b"plain message"));
+        assert!(matches!(
+            decrypt(b"plain message", "hunter2"),
+            Err(PngError::InvalidPayload { .. })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_payload() {
+        let payload = encrypt(b"message", "hunter2").unwrap();
+
+        assert!(matches!(
+            decrypt(&payload[..HEADER_LEN + 4], "hunter2"),
+            Err(PngError::InvalidPayload { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        let mut payload = encrypt(b"message", "hunter2").unwrap();
+        payload[MAGIC.len()] = 2;
+
+        assert!(matches!(
+            decrypt(&payload, "hunter2"),
+            Err(PngError::InvalidPayload { .. })
+        ));
+    }
+}
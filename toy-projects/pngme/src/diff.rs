@@ -0,0 +1,362 @@
+//! Chunk-level differences between two PNGs.
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// One difference between the chunks of two PNGs.
+///
+/// Chunks are matched by type and by how many chunks of that type come
+/// before them, so the second `tEXt` in one file is compared with the second
+/// `tEXt` in the other. Indexes are chunk positions in the old (`from`) and
+/// new (`to`) file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ChunkChange {
+    /// The chunk is only in the new file.
+    Added { chunk_type: ChunkType, index: usize },
+    /// The chunk is only in the old file.
+    Removed { chunk_type: ChunkType, index: usize },
+    /// The chunk is in both files but not in the same order relative to the
+    /// other chunks.
+    Moved {
+        chunk_type: ChunkType,
+        from: usize,
+        to: usize,
+    },
+    /// The chunk is in both files with different data.
+    Changed {
+        chunk_type: ChunkType,
+        from: usize,
+        to: usize,
+        old_length: u32,
+        new_length: u32,
+    },
+}
+
+impl fmt::Display for ChunkChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkChange::Added { chunk_type, index } => {
+                write!(f, "+ {} added at {}", chunk_type, index)
+            }
+            ChunkChange::Removed { chunk_type, index } => {
+                write!(f, "- {} removed from {}", chunk_type, index)
+            }
+            ChunkChange::Moved {
+                chunk_type,
+                from,
+                to,
+            } => write!(f, "> {} moved from {} to {}", chunk_type, from, to),
+            ChunkChange::Changed {
+                chunk_type,
+                from,
+                to,
+                old_length,
+                new_length,
+            } => write!(
+                f,
+                "~ {} changed at {} -> {} ({} -> {} bytes)",
+                chunk_type, from, to, old_length, new_length
+            ),
+        }
+    }
+}
+
+/// Every chunk-level difference between two PNGs, as returned by
+/// `Png::diff`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct PngDiff {
+    pub changes: Vec<ChunkChange>,
+}
+
+impl PngDiff {
+    /// Compares two lists of chunks. Removals come first in old file order,
+    /// then everything else in new file order.
+    pub fn between(old: &[Chunk], new: &[Chunk]) -> Self {
+        let pairs = match_chunks(old, new);
+        let mut matched_old = vec![false; old.len()];
+        let mut matched_new = vec![None; new.len()];
+        for &(from, to) in &pairs {
+            matched_old[from] = true;
+            matched_new[to] = Some(from);
+        }
+
+        let mut changes: Vec<ChunkChange> = old
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !matched_old[*index])
+            .map(|(index, chunk)| ChunkChange::Removed {
+                chunk_type: *chunk.chunk_type(),
+                index,
+            })
+            .collect();
+
+        let in_order = in_order(&pairs);
+        for (to, chunk) in new.iter().enumerate() {
+            let chunk_type = *chunk.chunk_type();
+            let from = match matched_new[to] {
+                Some(from) => from,
+                None => {
+                    changes.push(ChunkChange::Added {
+                        chunk_type,
+                        index: to,
+                    });
+                    continue;
+                }
+            };
+
+            if !in_order.contains(&from) {
+                changes.push(ChunkChange::Moved {
+                    chunk_type,
+                    from,
+                    to,
+                });
+            }
+
+            let old_chunk = &old[from];
+            if old_chunk.crc() != chunk.crc() || old_chunk.data() != chunk.data() {
+                changes.push(ChunkChange::Changed {
+                    chunk_type,
+                    from,
+                    to,
+                    old_length: old_chunk.length(),
+                    new_length: chunk.length(),
+                });
+            }
+        }
+
+        Self { changes }
+    }
+
+    /// Returns true if the two files have the same chunks.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for PngDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Pairs up `(old index, new index)` of chunks with the same type and the
+/// same number of earlier chunks of that type, in old file order.
+fn match_chunks(old: &[Chunk], new: &[Chunk]) -> Vec<(usize, usize)> {
+    let mut new_by_type: HashMap<ChunkType, Vec<usize>> = HashMap::new();
+    for (index, chunk) in new.iter().enumerate() {
+        new_by_type
+            .entry(*chunk.chunk_type())
+            .or_default()
+            .push(index);
+    }
+
+    let mut seen: HashMap<ChunkType, usize> = HashMap::new();
+    let mut pairs = Vec::new();
+    for (from, chunk) in old.iter().enumerate() {
+        let nth = seen.entry(*chunk.chunk_type()).or_insert(0);
+        if let Some(&to) = new_by_type
+            .get(chunk.chunk_type())
+            .and_then(|indexes| indexes.get(*nth))
+        {
+            pairs.push((from, to));
+        }
+        *nth += 1;
+    }
+
+    pairs
+}
+
+/// The old indexes of the longest run of pairs that kept their relative
+/// order. Every other matched chunk counts as moved, which keeps the number
+/// of reported moves as small as possible.
+fn in_order(pairs: &[(usize, usize)]) -> HashSet<usize> {
+    // Longest increasing subsequence of the new indexes, O(n log n).
+    // `tails[k]` is the pair ending the best run of length k + 1 so far.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+
+    for (i, &(_, to)) in pairs.iter().enumerate() {
+        let k = tails.partition_point(|&tail| pairs[tail].1 < to);
+        previous[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut kept = HashSet::with_capacity(tails.len());
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        kept.insert(pairs[i].0);
+        next = previous[i];
+    }
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::Png;
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    fn chunk_type(chunk_type: &str) -> ChunkType {
+        ChunkType::from_str(chunk_type).unwrap()
+    }
+
+    fn dice() -> Png {
+        Png::try_from(PNG_FILE).unwrap()
+    }
+
+    /// The dice image with its chunks changed by `edit`.
+    fn edited_dice<F: FnOnce(&mut Vec<Chunk>)>(edit: F) -> Png {
+        let mut chunks = dice().chunks().to_vec();
+        edit(&mut chunks);
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_identical() {
+        let diff = dice().diff(&dice());
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No differences\n");
+    }
+
+    #[test]
+    fn test_added() {
+        let new = edited_dice(|chunks| chunks.insert(6, Chunk::of_type("teSt", "new")));
+
+        assert_eq!(
+            dice().diff(&new).changes,
+            [ChunkChange::Added {
+                chunk_type: chunk_type("teSt"),
+                index: 6
+            }]
+        );
+    }
+
+    #[test]
+    fn test_removed() {
+        let new = edited_dice(|chunks| {
+            chunks.remove(5);
+        });
+
+        assert_eq!(
+            dice().diff(&new).changes,
+            [ChunkChange::Removed {
+                chunk_type: chunk_type("RuSt"),
+                index: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn test_changed() {
+        let new = edited_dice(|chunks| chunks[5] = Chunk::of_type("RuSt", "different"));
+        let old_length = dice().chunks()[5].length();
+
+        assert_eq!(
+            dice().diff(&new).changes,
+            [ChunkChange::Changed {
+                chunk_type: chunk_type("RuSt"),
+                from: 5,
+                to: 5,
+                old_length,
+                new_length: 9,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_moved() {
+        let new = edited_dice(|chunks| {
+            let srgb = chunks.remove(1);
+            chunks.insert(3, srgb);
+        });
+
+        assert_eq!(
+            dice().diff(&new).changes,
+            [ChunkChange::Moved {
+                chunk_type: chunk_type("sRGB"),
+                from: 1,
+                to: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn test_repeated_types_match_by_position() {
+        let old = Png::from_chunks(vec![
+            Chunk::of_type("tEXt", "a"),
+            Chunk::of_type("tEXt", "b"),
+        ]);
+        let new = Png::from_chunks(vec![
+            Chunk::of_type("tEXt", "a"),
+            Chunk::of_type("tEXt", "c"),
+            Chunk::of_type("tEXt", "d"),
+        ]);
+
+        assert_eq!(
+            old.diff(&new).changes,
+            [
+                ChunkChange::Changed {
+                    chunk_type: chunk_type("tEXt"),
+                    from: 1,
+                    to: 1,
+                    old_length: 1,
+                    new_length: 1,
+                },
+                ChunkChange::Added {
+                    chunk_type: chunk_type("tEXt"),
+                    index: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_several_changes() {
+        let new = edited_dice(|chunks| {
+            chunks.remove(2);
+            chunks.swap(0, 1);
+            chunks.push(Chunk::of_type("teSt", "after IEND"));
+        });
+        let diff = dice().diff(&new);
+
+        assert_eq!(diff.changes.len(), 3);
+        assert_eq!(
+            diff.to_string(),
+            "- gAMA removed from 2\n\
+             > IHDR moved from 0 to 1\n\
+             + teSt added at 6\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let new = edited_dice(|chunks| chunks.insert(6, Chunk::of_type("teSt", "new")));
+        let json = serde_json::to_string(&dice().diff(&new)).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"changes":[{"change":"added","chunk_type":"teSt","index":6}]}"#
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! The error type shared by the whole crate.
+
+use crate::chunk::Chunk;
+use std::error;
+use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+/// Everything that can go wrong while reading, editing or writing a PNG.
+///
+/// Offsets are byte offsets into the slice that was being parsed. When a
+/// chunk is parsed as part of a whole `Png`, `index` is the position of the
+/// chunk in the file and `offset` is relative to the start of the file.
+#[derive(Debug)]
+pub enum PngError {
+    /// The first 8 bytes are not the PNG signature.
+    InvalidSignature { found: Vec<u8> },
+    /// The first 12 bytes are not a RIFF header.
+    InvalidRiffHeader { found: Vec<u8> },
+    /// A chunk type that isn't made of 4 ASCII letters, or that has its
+    /// reserved bit set where a chunk is being created. Also used for a RIFF
+    /// `FourCc` that isn't 4 printable ASCII characters.
+    InvalidChunkType {
+        index: Option<usize>,
+        offset: usize,
+        bytes: Vec<u8>,
+    },
+    /// The input ended before the chunk did.
+    TruncatedChunk {
+        index: Option<usize>,
+        offset: usize,
+        needed: usize,
+        available: usize,
+    },
+    /// The chunk's length field doesn't match the number of data bytes.
+    LengthMismatch {
+        index: Option<usize>,
+        offset: usize,
+        declared: u32,
+        actual: usize,
+    },
+    /// The CRC stored in the chunk doesn't match the one computed from its
+    /// type and data. `expected` is the computed CRC, `actual` the stored one.
+    CrcMismatch {
+        index: Option<usize>,
+        offset: usize,
+        expected: u32,
+        actual: u32,
+    },
+    /// The chunk is well-formed but its data doesn't follow the layout
+    /// the spec gives for its type.
+    InvalidChunkData { chunk_type: String, reason: String },
+    /// There is more data than fits in one chunk.
+    ChunkTooLarge { length: usize },
+    /// No chunk with the given type exists.
+    ChunkNotFound { chunk_type: String },
+    /// A chunk position past the end of the file.
+    ChunkIndexOutOfRange { index: usize, chunks: usize },
+    /// Some parts of a message split over several chunks are missing.
+    MissingParts {
+        chunk_type: String,
+        missing: Vec<u32>,
+        count: u32,
+    },
+    /// The parts of a split message are all there but not in order.
+    PartsOutOfOrder { chunk_type: String },
+    /// A split message was put back together but doesn't match the checksum
+    /// it was stored with.
+    MessageChecksumMismatch { expected: u32, actual: u32 },
+    /// The animation chunks of an APNG don't fit together.
+    InvalidAnimation { reason: String },
+    /// The animation has no frame with the given index.
+    FrameNotFound { index: usize, frames: usize },
+    /// No textual chunk with the given keyword exists.
+    TextNotFound { keyword: String },
+    /// The file was read fine but breaks the spec's structural rules.
+    InvalidStructure { violations: usize },
+    /// A damaged file doesn't hold a single readable chunk.
+    NothingRecovered,
+    /// The message is encrypted but no passphrase was given.
+    PassphraseRequired,
+    /// An encrypted message couldn't be opened: either the passphrase is
+    /// wrong or the message was changed after it was sealed.
+    WrongPassphrase,
+    /// The chunk's data isn't an encrypted message, or is a damaged one.
+    InvalidPayload { reason: String },
+    /// The image uses a pixel format the operation can't handle.
+    UnsupportedImage { reason: String },
+    /// The pixel buffer doesn't hold 4 bytes for every pixel of an image
+    /// this size, or the image is empty.
+    InvalidImageSize {
+        width: u32,
+        height: u32,
+        length: usize,
+    },
+    /// The message doesn't fit in the image's pixels.
+    MessageTooLarge { size: usize, capacity: usize },
+    /// The image's pixels don't carry a hidden message.
+    NoHiddenMessage,
+    /// The file doesn't carry a signature.
+    SignatureMissing,
+    /// The file was signed with a different key than the one given.
+    WrongSigner { signer: String },
+    /// The signature doesn't match the chunks, so the file was changed after
+    /// it was signed.
+    SignatureMismatch,
+    /// A signing or public key couldn't be read.
+    InvalidKey { reason: String },
+    /// A directory or glob pattern didn't turn up any files.
+    NoInputFiles { input: String },
+    /// A glob pattern couldn't be parsed.
+    InvalidPattern { pattern: String, reason: String },
+    /// An output file was given for more than one input file.
+    OutputFileForBatch,
+    /// Some of the files a command ran on failed.
+    BatchFailed { failed: usize, total: usize },
+    /// The chunk's data isn't valid UTF-8.
+    NonUtf8Data(FromUtf8Error),
+    /// Turning a report into JSON failed.
+    Json(serde_json::Error),
+    /// Reading or writing a file failed.
+    Io(io::Error),
+}
+
+impl PngError {
+    /// Places a chunk-level error at chunk `index`, which starts `base`
+    /// bytes into the file.
+    pub(crate) fn at_chunk(self, chunk_index: usize, base: usize) -> Self {
+        match self {
+            PngError::InvalidChunkType { offset, bytes, .. } => PngError::InvalidChunkType {
+                index: Some(chunk_index),
+                offset: base + offset,
+                bytes,
+            },
+            PngError::TruncatedChunk {
+                offset,
+                needed,
+                available,
+                ..
+            } => PngError::TruncatedChunk {
+                index: Some(chunk_index),
+                offset: base + offset,
+                needed,
+                available,
+            },
+            PngError::LengthMismatch {
+                offset,
+                declared,
+                actual,
+                ..
+            } => PngError::LengthMismatch {
+                index: Some(chunk_index),
+                offset: base + offset,
+                declared,
+                actual,
+            },
+            PngError::CrcMismatch {
+                offset,
+                expected,
+                actual,
+                ..
+            } => PngError::CrcMismatch {
+                index: Some(chunk_index),
+                offset: base + offset,
+                expected,
+                actual,
+            },
+            err => err,
+        }
+    }
+}
+
+/// Formats the " in chunk N" part of an error message, if there is one.
+struct InChunk(Option<usize>);
+
+impl fmt::Display for InChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(index) => write!(f, " in chunk {}", index),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::InvalidSignature { found } => {
+                write!(f, "Invalid png signature {:?}", found)
+            }
+            PngError::InvalidRiffHeader { found } => {
+                write!(f, "Invalid RIFF header {:?}", found)
+            }
+            PngError::InvalidChunkType {
+                index,
+                offset,
+                bytes,
+            } => write!(
+                f,
+                "Invalid chunk type {:?}{} at offset {}",
+                String::from_utf8_lossy(bytes),
+                InChunk(*index),
+                offset
+            ),
+            PngError::TruncatedChunk {
+                index,
+                offset,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Truncated chunk{} at offset {}: needed {} bytes but only {} are left",
+                InChunk(*index),
+                offset,
+                needed,
+                available
+            ),
+            PngError::LengthMismatch {
+                index,
+                offset,
+                declared,
+                actual,
+            } => write!(
+                f,
+                "Invalid chunk length{} at offset {}: declared {} bytes but found {}",
+                InChunk(*index),
+                offset,
+                declared,
+                actual
+            ),
+            PngError::CrcMismatch {
+                index,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Invalid chunk checksum{} at offset {}: expected {:#010x} but found {:#010x}",
+                InChunk(*index),
+                offset,
+                expected,
+                actual
+            ),
+            PngError::InvalidChunkData { chunk_type, reason } => {
+                write!(f, "Invalid {} chunk: {}", chunk_type, reason)
+            }
+            PngError::ChunkTooLarge { length } => write!(
+                f,
+                "{} bytes of data don't fit in a chunk, which holds at most {}",
+                length,
+                Chunk::MAX_LENGTH
+            ),
+            PngError::ChunkNotFound { chunk_type } => {
+                write!(f, "Chunk {} does not exist", chunk_type)
+            }
+            PngError::ChunkIndexOutOfRange { index, chunks } => write!(
+                f,
+                "Chunk index {} is out of range, the file has {} chunk(s)",
+                index, chunks
+            ),
+            PngError::MissingParts {
+                chunk_type,
+                missing,
+                count,
+            } => write!(
+                f,
+                "Message in {} chunks is missing part(s) {:?} of {}",
+                chunk_type, missing, count
+            ),
+            PngError::PartsOutOfOrder { chunk_type } => {
+                write!(
+                    f,
+                    "Message in {} chunks has its parts out of order",
+                    chunk_type
+                )
+            }
+            PngError::MessageChecksumMismatch { expected, actual } => write!(
+                f,
+                "Invalid message checksum: expected {:#010x} but found {:#010x}",
+                expected, actual
+            ),
+            PngError::InvalidAnimation { reason } => write!(f, "Invalid animation: {}", reason),
+            PngError::FrameNotFound { index, frames } => write!(
+                f,
+                "Frame {} does not exist, the animation has {} frame(s)",
+                index, frames
+            ),
+            PngError::TextNotFound { keyword } => {
+                write!(f, "No text found for keyword {:?}", keyword)
+            }
+            PngError::InvalidStructure { violations } => {
+                write!(f, "Found {} structural problem(s)", violations)
+            }
+            PngError::NothingRecovered => write!(f, "No readable chunks found"),
+            PngError::PassphraseRequired => {
+                write!(f, "Message is encrypted and needs a passphrase")
+            }
+            PngError::WrongPassphrase => {
+                write!(f, "Wrong passphrase, or the message has been tampered with")
+            }
+            PngError::InvalidPayload { reason } => {
+                write!(f, "Invalid encrypted message: {}", reason)
+            }
+            PngError::UnsupportedImage { reason } => write!(f, "Unsupported image: {}", reason),
+            PngError::InvalidImageSize {
+                width,
+                height,
+                length,
+            } => write!(
+                f,
+                "{} bytes of RGBA data don't make a {}x{} image",
+                length, width, height
+            ),
+            PngError::MessageTooLarge { size, capacity } => write!(
+                f,
+                "Message is {} bytes but the image can only hide {}",
+                size, capacity
+            ),
+            PngError::NoHiddenMessage => write!(f, "No message is hidden in the image's pixels"),
+            PngError::SignatureMissing => write!(f, "The image is not signed"),
+            PngError::WrongSigner { signer } => {
+                write!(f, "The image was signed by a different key: {}", signer)
+            }
+            PngError::SignatureMismatch => write!(
+                f,
+                "The signature doesn't match, the image was changed after it was signed"
+            ),
+            PngError::InvalidKey { reason } => write!(f, "Invalid key: {}", reason),
+            PngError::NoInputFiles { input } => write!(f, "No PNG files found for {:?}", input),
+            PngError::InvalidPattern { pattern, reason } => {
+                write!(f, "Invalid pattern {:?}: {}", pattern, reason)
+            }
+            PngError::OutputFileForBatch => write!(
+                f,
+                "An output file only works with a single input file, use --out-dir instead"
+            ),
+            PngError::BatchFailed { failed, total } => {
+                write!(f, "{} of {} file(s) failed", failed, total)
+            }
+            PngError::NonUtf8Data(err) => write!(f, "Chunk data is not valid UTF-8: {}", err),
+            PngError::Json(err) => write!(f, "Can't write JSON: {}", err),
+            PngError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for PngError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PngError::NonUtf8Data(err) => Some(err),
+            PngError::Json(err) => Some(err),
+            PngError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FromUtf8Error> for PngError {
+    fn from(err: FromUtf8Error) -> Self {
+        PngError::NonUtf8Data(err)
+    }
+}
+
+impl From<serde_json::Error> for PngError {
+    fn from(err: serde_json::Error) -> Self {
+        PngError::Json(err)
+    }
+}
+
+impl From<io::Error> for PngError {
+    fn from(err: io::Error) -> Self {
+        PngError::Io(err)
+    }
+}
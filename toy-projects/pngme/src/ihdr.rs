@@ -0,0 +1,317 @@
+//! The image header (`IHDR`) chunk.
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::{PngError, Result};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+
+/// How the samples of a pixel should be interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale = 0,
+    Rgb = 2,
+    Indexed = 3,
+    GrayscaleAlpha = 4,
+    Rgba = 6,
+}
+
+impl ColorType {
+    /// Bit depths the spec allows for this color type.
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+
+    /// Number of samples in each pixel.
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(value),
+        }
+    }
+}
+
+impl fmt::Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale + alpha",
+            ColorType::Rgba => "RGBA",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// The order the pixels are stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterlaceMethod {
+    None = 0,
+    Adam7 = 1,
+}
+
+/// The decoded contents of the `IHDR` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    /// Always 0 (deflate) in a valid PNG.
+    pub compression_method: u8,
+    /// Always 0 (adaptive filtering) in a valid PNG.
+    pub filter_method: u8,
+    pub interlace_method: InterlaceMethod,
+}
+
+impl ImageHeader {
+    /// The chunk type this is stored in.
+    pub const CHUNK_TYPE: &'static str = "IHDR";
+    const LENGTH: usize = 13;
+    /// Width and height are limited to 2^31 - 1 by the spec.
+    const MAX_DIMENSION: u32 = i32::MAX as u32;
+
+    /// Encodes this as an `IHDR` chunk.
+    pub fn to_chunk(self) -> Chunk {
+        let data: Vec<u8> = self
+            .width
+            .to_be_bytes()
+            .iter()
+            .chain(self.height.to_be_bytes().iter())
+            .chain(&[
+                self.bit_depth,
+                self.color_type as u8,
+                self.compression_method,
+                self.filter_method,
+                self.interlace_method as u8,
+            ])
+            .copied()
+            .collect();
+
+        Chunk::new(ChunkType::try_from(*b"IHDR").unwrap(), data)
+    }
+}
+
+fn invalid(reason: String) -> PngError {
+    PngError::InvalidChunkData {
+        chunk_type: ImageHeader::CHUNK_TYPE.to_string(),
+        reason,
+    }
+}
+
+impl TryFrom<&Chunk> for ImageHeader {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if chunk.chunk_type().bytes() != *b"IHDR" {
+            return Err(invalid(format!("found a {} chunk", chunk.chunk_type())));
+        }
+
+        let data = chunk.data();
+        if data.len() != ImageHeader::LENGTH {
+            return Err(invalid(format!(
+                "expected {} bytes of data but found {}",
+                ImageHeader::LENGTH,
+                data.len()
+            )));
+        }
+
+        let width = u32::from_be_bytes(data[0..4].try_into().unwrap());
+        let height = u32::from_be_bytes(data[4..8].try_into().unwrap());
+        for (name, value) in [("width", width), ("height", height)].iter() {
+            if *value == 0 || *value > ImageHeader::MAX_DIMENSION {
+                return Err(invalid(format!("{} {} is out of range", name, value)));
+            }
+        }
+
+        let bit_depth = data[8];
+        let color_type = ColorType::try_from(data[9])
+            .map_err(|value| invalid(format!("unknown color type {}", value)))?;
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(invalid(format!(
+                "bit depth {} is not allowed for {} images",
+                bit_depth, color_type
+            )));
+        }
+
+        let compression_method = data[10];
+        if compression_method != 0 {
+            return Err(invalid(format!(
+                "unknown compression method {}",
+                compression_method
+            )));
+        }
+
+        let filter_method = data[11];
+        if filter_method != 0 {
+            return Err(invalid(format!("unknown filter method {}", filter_method)));
+        }
+
+        let interlace_method = match data[12] {
+            0 => InterlaceMethod::None,
+            1 => InterlaceMethod::Adam7,
+            value => return Err(invalid(format!("unknown interlace method {}", value))),
+        };
+
+        Ok(Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method,
+            filter_method,
+            interlace_method,
+        })
+    }
+}
+
+impl fmt::Display for ImageHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let interlace = match self.interlace_method {
+            InterlaceMethod::None => "not interlaced",
+            InterlaceMethod::Adam7 => "Adam7 interlaced",
+        };
+
+        write!(
+            f,
+            "{}x{}, {}-bit {}, {}",
+            self.width, self.height, self.bit_depth, self.color_type, interlace
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::Png;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    fn ihdr_chunk(data: &[u8]) -> Chunk {
+        Chunk::of_type("IHDR", data.to_vec())
+    }
+
+    #[rustfmt::skip]
+    fn header_bytes(bit_depth: u8, color_type: u8) -> Vec<u8> {
+        vec![
+            0, 0, 0, 50,           // width
+            0, 0, 0, 40,           // height
+            bit_depth, color_type,
+            0, 0, 0,               // compression, filter, interlace
+        ]
+    }
+
+    #[test]
+    fn test_header_from_png() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        let header = png.header().unwrap();
+
+        assert_eq!(
+            header,
+            ImageHeader {
+                width: 50,
+                height: 50,
+                bit_depth: 8,
+                color_type: ColorType::Rgba,
+                compression_method: 0,
+                filter_method: 0,
+                interlace_method: InterlaceMethod::None,
+            }
+        );
+        assert_eq!(header.to_string(), "50x50, 8-bit RGBA, not interlaced");
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        let chunk = png.chunk_by_type("IHDR").unwrap();
+        let header = ImageHeader::try_from(chunk).unwrap();
+
+        assert_eq!(header.to_chunk().as_bytes(), chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_valid_color_type_bit_depths() {
+        let valid = [
+            (0, &[1, 2, 4, 8, 16][..]),
+            (2, &[8, 16]),
+            (3, &[1, 2, 4, 8]),
+            (4, &[8, 16]),
+            (6, &[8, 16]),
+        ];
+
+        for &(color_type, bit_depths) in valid.iter() {
+            for bit_depth in [1, 2, 3, 4, 8, 16, 32].iter() {
+                let chunk = ihdr_chunk(&header_bytes(*bit_depth, color_type));
+                let header = ImageHeader::try_from(&chunk);
+                assert_eq!(
+                    header.is_ok(),
+                    bit_depths.contains(bit_depth),
+                    "color type {} bit depth {}",
+                    color_type,
+                    bit_depth
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_color_type() {
+        let chunk = ihdr_chunk(&header_bytes(8, 1));
+        assert!(matches!(
+            ImageHeader::try_from(&chunk),
+            Err(PngError::InvalidChunkData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_zero_width() {
+        let mut data = header_bytes(8, 6);
+        data[3] = 0;
+        assert!(ImageHeader::try_from(&ihdr_chunk(&data)).is_err());
+    }
+
+    #[test]
+    fn test_unknown_methods() {
+        for &i in [10, 11, 12].iter() {
+            let mut data = header_bytes(8, 6);
+            data[i] = 2;
+            assert!(ImageHeader::try_from(&ihdr_chunk(&data)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_wrong_length() {
+        let chunk = ihdr_chunk(&header_bytes(8, 6)[..12]);
+        assert!(ImageHeader::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_wrong_chunk_type() {
+        let chunk = Chunk::of_type("ruSt", header_bytes(8, 6));
+        assert!(ImageHeader::try_from(&chunk).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! Read, edit and write PNG files one chunk at a time.
+//!
+//! The core types are [`Png`], an image held in memory as a list of
+//! [`Chunk`]s, and [`ChunkType`], the four letter name of a chunk. Every
+//! operation that can fail returns a [`PngError`].
+//!
+//! ```
+//! use pngme::{Chunk, ChunkType, Png};
+//! use std::convert::TryFrom;
+//! use std::str::FromStr;
+//!
+//! let iend = Chunk::new(ChunkType::from_str("IEND")?, Vec::new());
+//! let mut png = Png::from_chunks(vec![iend]);
+//!
+//! let message = Chunk::new(ChunkType::from_str("ruSt")?, b"hidden".to_vec());
+//! png.insert_chunk_at(0, message)?;
+//!
+//! let png = Png::try_from(png.as_bytes().as_slice())?;
+//! assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hidden");
+//! # Ok::<(), pngme::PngError>(())
+//! ```
+//!
+//! The other modules build on these: [`stream`] reads and writes files
+//! without loading them whole, [`message`], [`crypto`] and [`stego`] hide
+//! messages, [`signature`] signs files, [`ancillary`] and [`ihdr`] decode the
+//! chunks that describe the image, [`rgba`] turns the image data into pixels
+//! and back, and the rest check and rewrite whole files.
+//!
+//! [`riff`] reads and writes RIFF containers such as WebP files, and
+//! [`container`] lets messages be stored in either format the same way.
+
+pub mod ancillary;
+pub mod apng;
+pub mod chunk;
+pub mod chunk_type;
+mod codec;
+pub mod container;
+pub mod crypto;
+pub mod diff;
+pub mod error;
+pub mod ihdr;
+pub mod message;
+pub mod pixels;
+pub mod png;
+pub mod repair;
+pub mod report;
+pub mod rgba;
+pub mod riff;
+pub mod sanitize;
+pub mod signature;
+pub mod stego;
+pub mod stream;
+pub mod text;
+pub mod validate;
+
+pub use chunk::Chunk;
+pub use chunk_type::ChunkType;
+pub use error::PngError;
+pub use png::Png;
+
+/// The result of every fallible operation in this crate.
+pub type Result<T> = std::result::Result<T, PngError>;
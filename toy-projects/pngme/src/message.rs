@@ -0,0 +1,352 @@
+//! Splitting messages that are too big for one chunk over several.
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::codec::{be_u32, invalid};
+use crate::{PngError, Result};
+use crc::crc32;
+
+// A payload too big for one chunk is split over several chunks of the same
+// type, each starting with a part header:
+//
+//   | MAGIC (4) | index (4) | count (4) | checksum (4) | data |
+//
+// All fields are big endian. `index` counts from 0, `count` is the number of
+// parts, and `checksum` is the CRC-32 of the whole payload, so the pieces can
+// be checked once they are put back together. A payload that fits in one
+// chunk is stored as is, without a header, unless it looks like a part
+// itself. Then it is stored as part 0 of 1 so it can't be misread.
+
+const MAGIC: &[u8; 4] = b"pmPT";
+const HEADER_LEN: usize = MAGIC.len() + 12;
+
+/// Default size of the data in each part.
+pub const DEFAULT_PART_SIZE: usize = 1 << 20;
+
+/// Stores `payload` in chunks of type `chunk_type`, splitting it into parts
+/// of at most `part_size` bytes if it doesn't fit in one. Fails if a chunk
+/// would hold more than `Chunk::MAX_LENGTH` bytes.
+pub fn split(chunk_type: ChunkType, payload: &[u8], part_size: usize) -> Result<Vec<Chunk>> {
+    Ok(split_payload(payload, part_size)?
+        .into_iter()
+        .map(|data| Chunk::new(chunk_type, data))
+        .collect())
+}
+
+/// Like `split`, but returns only the data of each chunk, for formats other
+/// than PNG.
+pub fn split_payload(payload: &[u8], part_size: usize) -> Result<Vec<Vec<u8>>> {
+    let whole = payload.len() <= part_size && !is_part(payload);
+    let largest = if whole {
+        payload.len()
+    } else {
+        part_size.min(payload.len()) + HEADER_LEN
+    };
+    if largest > Chunk::MAX_LENGTH {
+        return Err(PngError::ChunkTooLarge { length: largest });
+    }
+
+    if whole {
+        return Ok(vec![payload.to_vec()]);
+    }
+
+    let checksum = crc32::checksum_ieee(payload);
+    let parts: Vec<&[u8]> = payload.chunks(part_size).collect();
+    let count = parts.len() as u32;
+
+    let parts = parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, data)| {
+            let mut part = Vec::with_capacity(HEADER_LEN + data.len());
+            part.extend_from_slice(MAGIC);
+            part.extend_from_slice(&(index as u32).to_be_bytes());
+            part.extend_from_slice(&count.to_be_bytes());
+            part.extend_from_slice(&checksum.to_be_bytes());
+            part.extend_from_slice(data);
+            part
+        })
+        .collect();
+
+    Ok(parts)
+}
+
+/// Returns true if `data` is one part of a split payload.
+pub fn is_part(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && data.starts_with(MAGIC)
+}
+
+/// Picks out the chunks of the first message of a type, for removing it:
+/// the first chunk, and if that is a part of a split payload, the parts
+/// after it. Shown the chunks of that type one at a time in file order, so
+/// a file can be streamed through it.
+#[derive(Debug, Default)]
+pub struct FirstMessage {
+    first_is_part: Option<bool>,
+}
+
+impl FirstMessage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the chunk holding `data` belongs to the first
+    /// message.
+    pub fn includes(&mut self, data: &[u8]) -> bool {
+        let is_part = is_part(data);
+        match self.first_is_part {
+            None => {
+                self.first_is_part = Some(is_part);
+                true
+            }
+            Some(first_is_part) => first_is_part && is_part,
+        }
+    }
+}
+
+/// Puts a payload back together from the chunks it was stored in, given in
+/// file order.
+///
+/// A single chunk without a part header is returned as is. Otherwise every
+/// chunk must be a part of the same payload, and all of them must be there
+/// in order.
+pub fn assemble(chunks: &[Chunk]) -> Result<Vec<u8>> {
+    let chunk_type = match chunks.first() {
+        Some(first) => first.chunk_type().to_string(),
+        None => return Ok(Vec::new()),
+    };
+    let parts: Vec<&[u8]> = chunks.iter().map(Chunk::data).collect();
+
+    assemble_payload(&chunk_type, &parts)
+}
+
+/// Like `assemble`, but takes only the data of each chunk, for formats other
+/// than PNG. `chunk_type` is used in errors.
+pub fn assemble_payload(chunk_type: &str, parts: &[&[u8]]) -> Result<Vec<u8>> {
+    let first = match parts.first() {
+        Some(first) => *first,
+        None => return Ok(Vec::new()),
+    };
+    if !is_part(first) {
+        return Ok(first.to_vec());
+    }
+
+    let chunk_type = chunk_type.to_string();
+    let (_, count, checksum) = header(first);
+
+    let mut indices = Vec::with_capacity(parts.len());
+    for part in parts {
+        if !is_part(part) {
+            return Err(invalid(&chunk_type, "a part header is missing".to_string()));
+        }
+
+        let (index, part_count, part_checksum) = header(part);
+        if part_count != count || part_checksum != checksum {
+            return Err(invalid(
+                &chunk_type,
+                "parts from different messages are mixed together".to_string(),
+            ));
+        }
+        if index >= count || indices.contains(&index) {
+            return Err(invalid(
+                &chunk_type,
+                format!("part {} of {} is not expected", index, count),
+            ));
+        }
+
+        indices.push(index);
+    }
+
+    let missing: Vec<u32> = (0..count).filter(|i| !indices.contains(i)).collect();
+    if !missing.is_empty() {
+        return Err(PngError::MissingParts {
+            chunk_type,
+            missing,
+            count,
+        });
+    }
+
+    if indices
+        .iter()
+        .enumerate()
+        .any(|(i, index)| i as u32 != *index)
+    {
+        return Err(PngError::PartsOutOfOrder { chunk_type });
+    }
+
+    let payload: Vec<u8> = parts
+        .iter()
+        .flat_map(|part| &part[HEADER_LEN..])
+        .copied()
+        .collect();
+
+    let actual = crc32::checksum_ieee(&payload);
+    if actual != checksum {
+        return Err(PngError::MessageChecksumMismatch {
+            expected: checksum,
+            actual,
+        });
+    }
+
+    Ok(payload)
+}
+
+/// Reads `(index, count, checksum)` from a part header.
+fn header(data: &[u8]) -> (u32, u32, u32) {
+    let field = |i: usize| {
+        let start = MAGIC.len() + 4 * i;
+        be_u32(&data[start..start + 4])
+    };
+
+    (field(0), field(1), field(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7) as u8).collect()
+    }
+
+    fn chunk_type() -> ChunkType {
+        ChunkType::from_str("ruSt").unwrap()
+    }
+
+    #[test]
+    fn test_small_payload_is_one_plain_chunk() {
+        let chunks = split(chunk_type(), b"short message", 100).unwrap();
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].data(), b"short message");
+        assert_eq!(assemble(&chunks).unwrap(), b"short message");
+    }
+
+    #[test]
+    fn test_small_payload_that_looks_like_a_part() {
+        let mut payload = MAGIC.to_vec();
+        payload.extend_from_slice(b"not a part header");
+        let chunks = split(chunk_type(), &payload, 100).unwrap();
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].data().len(), HEADER_LEN + payload.len());
+        assert_eq!(assemble(&chunks).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_first_message() {
+        let mut first = FirstMessage::new();
+        let parts = split_payload(&payload(1000), 300).unwrap();
+
+        assert!(parts.iter().all(|part| first.includes(part)));
+        assert!(!first.includes(b"plain"));
+
+        let mut first = FirstMessage::new();
+        assert!(first.includes(b"plain"));
+        assert!(!first.includes(&parts[0]));
+        assert!(!first.includes(b"plain"));
+    }
+
+    #[test]
+    fn test_chunk_too_large() {
+        // Zeroed memory is only touched where it's read, so this is cheap.
+        let payload = vec![0; Chunk::MAX_LENGTH + 1];
+
+        for &part_size in [usize::MAX, Chunk::MAX_LENGTH].iter() {
+            assert!(matches!(
+                split_payload(&payload, part_size),
+                Err(PngError::ChunkTooLarge { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_split_and_assemble() {
+        let payload = payload(1000);
+        let chunks = split(chunk_type(), &payload, 300).unwrap();
+
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|chunk| is_part(chunk.data())));
+        assert_eq!(chunks[3].data().len(), HEADER_LEN + 100);
+        assert_eq!(assemble(&chunks).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_exact_multiple_of_part_size() {
+        let payload = payload(900);
+        let chunks = split(chunk_type(), &payload, 300).unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(assemble(&chunks).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_missing_parts() {
+        let mut chunks = split(chunk_type(), &payload(1000), 300).unwrap();
+        chunks.remove(2);
+        chunks.remove(0);
+
+        match assemble(&chunks) {
+            Err(PngError::MissingParts { missing, count, .. }) => {
+                assert_eq!(missing, [0, 2]);
+                assert_eq!(count, 4);
+            }
+            other => panic!("expected MissingParts, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reordered_parts() {
+        let mut chunks = split(chunk_type(), &payload(1000), 300).unwrap();
+        chunks.swap(1, 2);
+
+        assert!(matches!(
+            assemble(&chunks),
+            Err(PngError::PartsOutOfOrder { .. })
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_part() {
+        let mut chunks = split(chunk_type(), &payload(1000), 300).unwrap();
+        chunks.insert(1, chunks[0].clone());
+
+        assert!(matches!(
+            assemble(&chunks),
+            Err(PngError::InvalidChunkData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_mixed_messages() {
+        let mut chunks = split(chunk_type(), &payload(1000), 300).unwrap();
+        chunks[1] = split(chunk_type(), &payload(999), 300).unwrap().remove(1);
+
+        assert!(matches!(
+            assemble(&chunks),
+            Err(PngError::InvalidChunkData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_corrupted_part() {
+        let mut chunks = split(chunk_type(), &payload(1000), 300).unwrap();
+        let mut data = chunks[1].data().to_vec();
+        data[HEADER_LEN] ^= 0xff;
+        chunks[1] = Chunk::new(chunk_type(), data);
+
+        assert!(matches!(
+            assemble(&chunks),
+            Err(PngError::MessageChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_plain_chunk_among_parts() {
+        let mut chunks = split(chunk_type(), &payload(1000), 300).unwrap();
+        chunks.push(Chunk::new(chunk_type(), b"plain".to_vec()));
+
+        assert!(assemble(&chunks).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+//! Decompressing and unfiltering the image data into raw pixels, and
+//! back.
+
+use crate::ihdr::{ImageHeader, InterlaceMethod};
+use crate::{PngError, Result};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+/// The per-scanline filters from the spec. Each one predicts a byte from its
+/// neighbours to the left (`a`), above (`b`) and above-left (`c`), and stores
+/// the difference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None = 0,
+    Sub = 1,
+    Up = 2,
+    Average = 3,
+    Paeth = 4,
+}
+
+impl FilterType {
+    /// Every filter type, in the order of their numbers.
+    pub const ALL: [FilterType; 5] = [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ];
+
+    fn predict(self, a: u8, b: u8, c: u8) -> u8 {
+        match self {
+            FilterType::None => 0,
+            FilterType::Sub => a,
+            FilterType::Up => b,
+            FilterType::Average => ((a as u16 + b as u16) / 2) as u8,
+            FilterType::Paeth => paeth(a, b, c),
+        }
+    }
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        FilterType::ALL
+            .iter()
+            .copied()
+            .find(|filter| *filter as u8 == value)
+            .ok_or(value)
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// The pixels of a non-interlaced image, inflated and unfiltered.
+///
+/// `data` holds the scanlines back to back without their filter type bytes,
+/// so every row is exactly `stride` bytes. The filter each row was stored
+/// with is kept so `encode` can write the rows back the same way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelData {
+    header: ImageHeader,
+    stride: usize,
+    filters: Vec<FilterType>,
+    data: Vec<u8>,
+}
+
+impl PixelData {
+    /// Inflates the concatenated `IDAT` data of an image and undoes the
+    /// scanline filters.
+    pub fn decode(header: ImageHeader, compressed: &[u8]) -> Result<Self> {
+        if header.interlace_method != InterlaceMethod::None {
+            return Err(PngError::UnsupportedImage {
+                reason: "interlaced images are not supported".to_string(),
+            });
+        }
+
+        let stride = stride_for(&header, header.width)?;
+        let expected = (stride + 1)
+            .checked_mul(header.height as usize)
+            .ok_or_else(image_too_large)?;
+        let filtered = inflate(compressed, expected)?;
+        let (filters, data) = unfilter_rows(&filtered, stride, bytes_per_pixel(&header))?;
+
+        Ok(Self {
+            header,
+            stride,
+            filters,
+            data,
+        })
+    }
+
+    /// Filters every row the way it was read and deflates the result, ready
+    /// to be stored in `IDAT` chunks.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let stride = self.stride;
+        let bpp = bytes_per_pixel(&self.header);
+        let mut filtered = Vec::with_capacity((stride + 1) * self.filters.len());
+
+        for (y, (row, filter)) in self.rows().zip(&self.filters).enumerate() {
+            let prior = match y {
+                0 => None,
+                _ => Some(&self.data[(y - 1) * stride..y * stride]),
+            };
+
+            filtered.push(*filter as u8);
+            filter_row(*filter, bpp, prior, row, &mut filtered);
+        }
+
+        deflate(&filtered)
+    }
+
+    /// The header the pixels were decoded with.
+    pub fn header(&self) -> &ImageHeader {
+        &self.header
+    }
+
+    /// The unfiltered scanlines, back to back.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The unfiltered scanlines, for editing in place.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Each unfiltered scanline in turn.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.data.chunks_exact(self.stride)
+    }
+}
+
+/// Number of bytes in one unfiltered scanline `width` pixels wide, which
+/// differs from the image width in the passes of an interlaced image.
+pub(crate) fn stride_for(header: &ImageHeader, width: u32) -> Result<usize> {
+    let bits = (width as usize)
+        .checked_mul(header.color_type.channels() * header.bit_depth as usize)
+        .ok_or_else(image_too_large)?;
+    Ok(bits.div_ceil(8))
+}
+
+/// The distance filters look back for the byte "to the left", which is one
+/// whole pixel, rounded up to at least one byte.
+pub(crate) fn bytes_per_pixel(header: &ImageHeader) -> usize {
+    let bits = header.color_type.channels() * header.bit_depth as usize;
+    bits.div_ceil(8)
+}
+
+/// Inflates image data that should come to exactly `expected` bytes.
+pub(crate) fn inflate(compressed: &[u8], expected: usize) -> Result<Vec<u8>> {
+    // Never inflate more than the header says is there, so a tiny
+    // zlib bomb can't make us allocate gigabytes.
+    let mut filtered = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take(expected as u64 + 1)
+        .read_to_end(&mut filtered)
+        .map_err(|err| invalid(format!("can't inflate image data: {}", err)))?;
+    if filtered.len() != expected {
+        return Err(invalid(format!(
+            "expected {} bytes of image data but found {}",
+            expected,
+            filtered.len()
+        )));
+    }
+
+    Ok(filtered)
+}
+
+pub(crate) fn deflate(filtered: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(filtered)?;
+    Ok(encoder.finish()?)
+}
+
+/// Unfilters scanlines of `stride` bytes that each start with their filter
+/// type byte, returning the filters and the rows without them.
+pub(crate) fn unfilter_rows(
+    filtered: &[u8],
+    stride: usize,
+    bpp: usize,
+) -> Result<(Vec<FilterType>, Vec<u8>)> {
+    let height = filtered.len() / (stride + 1);
+    let mut filters = Vec::with_capacity(height);
+    let mut data = vec![0; stride * height];
+
+    for (y, line) in filtered.chunks_exact(stride + 1).enumerate() {
+        let filter = FilterType::try_from(line[0])
+            .map_err(|value| invalid(format!("unknown filter type {} in row {}", value, y)))?;
+        filters.push(filter);
+
+        let (above, rest) = data.split_at_mut(y * stride);
+        let prior = match y {
+            0 => None,
+            _ => Some(&above[(y - 1) * stride..]),
+        };
+        let row = &mut rest[..stride];
+        row.copy_from_slice(&line[1..]);
+        unfilter(filter, bpp, prior, row);
+    }
+
+    Ok((filters, data))
+}
+
+/// Reverses `filter` on `row` in place. `prior` is the unfiltered row above,
+/// or `None` for the first row.
+fn unfilter(filter: FilterType, bpp: usize, prior: Option<&[u8]>, row: &mut [u8]) {
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = prior.map_or(0, |prior| prior[i]);
+        let c = match prior {
+            Some(prior) if i >= bpp => prior[i - bpp],
+            _ => 0,
+        };
+
+        row[i] = row[i].wrapping_add(filter.predict(a, b, c));
+    }
+}
+
+/// Applies `filter` to `row` and appends the result to `out`.
+pub(crate) fn filter_row(
+    filter: FilterType,
+    bpp: usize,
+    prior: Option<&[u8]>,
+    row: &[u8],
+    out: &mut Vec<u8>,
+) {
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = prior.map_or(0, |prior| prior[i]);
+        let c = match prior {
+            Some(prior) if i >= bpp => prior[i - bpp],
+            _ => 0,
+        };
+
+        out.push(row[i].wrapping_sub(filter.predict(a, b, c)));
+    }
+}
+
+fn invalid(reason: String) -> PngError {
+    PngError::InvalidChunkData {
+        chunk_type: "IDAT".to_string(),
+        reason,
+    }
+}
+
+/// For a header whose image has more bytes than fit in memory at all.
+pub(crate) fn image_too_large() -> PngError {
+    PngError::InvalidChunkData {
+        chunk_type: ImageHeader::CHUNK_TYPE.to_string(),
+        reason: "image too large".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::ColorType;
+    use crate::png::Png;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    fn dice() -> PixelData {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        PixelData::decode(png.header().unwrap(), &png.image_data()).unwrap()
+    }
+
+    fn header(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> ImageHeader {
+        ImageHeader {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    /// Filters and deflates `data` with the same filter on every row.
+    fn compress(header: &ImageHeader, filter: FilterType, data: &[u8]) -> Vec<u8> {
+        let pixels = PixelData {
+            header: *header,
+            stride: stride_for(header, header.width).unwrap(),
+            filters: vec![filter; header.height as usize],
+            data: data.to_vec(),
+        };
+        pixels.encode().unwrap()
+    }
+
+    #[test]
+    fn test_decode_dice() {
+        let pixels = dice();
+
+        assert_eq!(pixels.data().len(), 50 * 50 * 4);
+        assert_eq!(pixels.rows().count(), 50);
+    }
+
+    #[test]
+    fn test_dice_round_trip() {
+        let pixels = dice();
+        let decoded = PixelData::decode(*pixels.header(), &pixels.encode().unwrap()).unwrap();
+
+        assert_eq!(decoded, pixels);
+    }
+
+    #[test]
+    fn test_every_filter_round_trips() {
+        let header = header(7, 5, 8, ColorType::Rgb);
+        let data: Vec<u8> = (0..7 * 5 * 3).map(|i| (i * 37 % 251) as u8).collect();
+
+        for &filter in FilterType::ALL.iter() {
+            let compressed = compress(&header, filter, &data);
+            let pixels = PixelData::decode(header, &compressed).unwrap();
+
+            assert_eq!(pixels.data(), &data[..], "{:?}", filter);
+            assert!(pixels.filters.iter().all(|f| *f == filter));
+        }
+    }
+
+    #[test]
+    fn test_sub_byte_pixels_round_trip() {
+        let header = header(13, 3, 2, ColorType::Grayscale);
+        let data: Vec<u8> = (0..4 * 3).map(|i| (i * 91) as u8).collect();
+
+        for &filter in FilterType::ALL.iter() {
+            let compressed = compress(&header, filter, &data);
+            assert_eq!(
+                PixelData::decode(header, &compressed).unwrap().data(),
+                &data[..]
+            );
+        }
+    }
+
+    #[test]
+    fn test_paeth_predictor() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+        assert_eq!(paeth(1, 200, 100), 100);
+    }
+
+    #[test]
+    fn test_unknown_filter_type() {
+        let header = header(2, 1, 8, ColorType::Grayscale);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[5, 1, 2]).unwrap();
+
+        assert!(matches!(
+            PixelData::decode(header, &encoder.finish().unwrap()),
+            Err(PngError::InvalidChunkData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_wrong_data_length() {
+        let header = header(4, 4, 8, ColorType::Grayscale);
+        let compressed = compress(&header, FilterType::None, &[0; 16]);
+        let taller = ImageHeader {
+            height: 5,
+            ..header
+        };
+        let shorter = ImageHeader {
+            height: 3,
+            ..header
+        };
+
+        assert!(PixelData::decode(taller, &compressed).is_err());
+        assert!(PixelData::decode(shorter, &compressed).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_stream() {
+        let header = header(4, 4, 8, ColorType::Grayscale);
+        assert!(PixelData::decode(header, b"not zlib data").is_err());
+    }
+
+    #[test]
+    fn test_image_too_large() {
+        let png = Png::try_from(&include_bytes!("../tests/fixtures/huge.png")[..]).unwrap();
+        let err = PixelData::decode(png.header().unwrap(), &png.image_data()).unwrap_err();
+
+        assert_eq!(err.to_string(), "Invalid IHDR chunk: image too large");
+    }
+
+    #[test]
+    fn test_interlaced_unsupported() {
+        let header = ImageHeader {
+            interlace_method: InterlaceMethod::Adam7,
+            ..header(4, 4, 8, ColorType::Grayscale)
+        };
+
+        assert!(matches!(
+            PixelData::decode(header, &[]),
+            Err(PngError::UnsupportedImage { .. })
+        ));
+    }
+}
@@ -1,101 +1,271 @@
-use crate::chunk::Chunk;
-use crate::StrError;
+//! A whole PNG held in memory.
+
+use crate::chunk::{Chunk, ChunkRef};
+use crate::chunk_type::ChunkType;
+use crate::diff::PngDiff;
+use crate::ihdr::ImageHeader;
+use crate::validate::{Validator, Violation};
+use crate::{PngError, Result};
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
 
+/// A PNG as the list of chunks that follow its signature.
+///
+/// Parse one from bytes with `TryFrom<&[u8]>`, which checks the signature
+/// and every chunk's length and CRC, or build one with `from_chunks`. The
+/// chunks aren't checked against the spec's ordering rules until `validate`
+/// is called, so any list of chunks can be edited freely.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Png {
     chunks: Vec<Chunk>,
 }
 
 impl Png {
-    const STANDARD_HEADER: &'static [u8] = &[137, 80, 78, 71, 13, 10, 26, 10];
+    /// The 8 byte signature every PNG file starts with.
+    pub const STANDARD_HEADER: &'static [u8] = &[137, 80, 78, 71, 13, 10, 26, 10];
+    /// Largest `IDAT` chunk `replace_image_data` writes.
+    const IDAT_CHUNK_SIZE: usize = 1 << 16;
 
-    fn from_chunks(chunks: Vec<Chunk>) -> Png {
+    /// Creates a PNG from a list of chunks, in file order.
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
         Png { chunks }
     }
 
-    fn append_chunk(&mut self, chunk: Chunk) {
+    /// Adds a chunk at the end. Note that this puts it after `IEND` if there
+    /// is one.
+    pub fn append_chunk(&mut self, chunk: Chunk) {
         self.chunks.push(chunk);
     }
 
+    /// Inserts a chunk so that it ends up at `index`, shifting the chunks
+    /// after it. `index` can be at most the number of chunks.
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        if index > self.chunks.len() {
+            return Err(PngError::ChunkIndexOutOfRange {
+                index,
+                chunks: self.chunks.len(),
+            });
+        }
+
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    /// Puts `chunk` in place of the first chunk with the given type and
+    /// returns the chunk it replaced. `chunk` doesn't need to have the same
+    /// type.
+    pub fn replace_chunk(&mut self, chunk_type: &str, chunk: Chunk) -> Result<Chunk> {
+        match self.chunk_position_by_type(chunk_type) {
+            Some(chunk_idx) => Ok(std::mem::replace(&mut self.chunks[chunk_idx], chunk)),
+            None => Err(PngError::ChunkNotFound {
+                chunk_type: chunk_type.to_string(),
+            }),
+        }
+    }
+
     fn chunk_position_by_type(&self, chunk_type: &str) -> Option<usize> {
-        self.chunks().into_iter().position(|chunk| {
-            std::str::from_utf8(&chunk.chunk_type().bytes().to_owned()).unwrap() == chunk_type
-        })
+        self.chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes())
     }
 
-    fn remove_chunk(&mut self, chunk_type: &str) -> crate::Result<()> {
+    /// Removes the first chunk with the given type and returns it.
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
         if let Some(chunk_idx) = self.chunk_position_by_type(chunk_type) {
-            self.chunks.remove(chunk_idx);
-            Ok(())
+            Ok(self.chunks.remove(chunk_idx))
         } else {
-            Err(Box::new(StrError("Chunk does not exist")))
+            Err(PngError::ChunkNotFound {
+                chunk_type: chunk_type.to_string(),
+            })
         }
     }
 
-    fn as_bytes(&self) -> Vec<u8> {
+    /// The whole file: the signature followed by every chunk.
+    pub fn as_bytes(&self) -> Vec<u8> {
         Png::STANDARD_HEADER
             .iter()
             .copied()
-            .chain(self.chunks().iter().map(|chunk| chunk.as_bytes()).flatten())
+            .chain(self.chunks.iter().flat_map(|chunk| chunk.as_bytes()))
             .collect()
     }
 
-    fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        if let Some(chunk_idx) = self.chunk_position_by_type(chunk_type) {
-            Some(&self.chunks[chunk_idx])
-        } else {
-            None
-        }
+    /// The first chunk with the given type, if there is one.
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+        self.chunk_position_by_type(chunk_type)
+            .map(|chunk_idx| &self.chunks[chunk_idx])
     }
 
-    fn chunks(&self) -> Vec<Chunk> {
-        self.chunks.to_owned()
+    /// Iterates over every chunk with the given type, in file order.
+    pub fn chunks_by_type<'a>(&'a self, chunk_type: &'a str) -> impl Iterator<Item = &'a Chunk> {
+        self.chunks
+            .iter()
+            .filter(move |chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes())
+    }
+
+    /// Every chunk, in file order.
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// Decodes the `IHDR` chunk.
+    pub fn header(&self) -> Result<ImageHeader> {
+        let chunk =
+            self.chunk_by_type(ImageHeader::CHUNK_TYPE)
+                .ok_or_else(|| PngError::ChunkNotFound {
+                    chunk_type: ImageHeader::CHUNK_TYPE.to_string(),
+                })?;
+
+        ImageHeader::try_from(chunk)
+    }
+
+    /// The compressed image data, which the spec lets encoders split over
+    /// any number of consecutive `IDAT` chunks.
+    pub fn image_data(&self) -> Vec<u8> {
+        self.chunks_by_type("IDAT")
+            .flat_map(|chunk| chunk.data())
+            .copied()
+            .collect()
+    }
+
+    /// Replaces every `IDAT` chunk with new ones holding `data`. They go
+    /// where the first `IDAT` was, or before `IEND` if there was none.
+    pub fn replace_image_data(&mut self, data: &[u8]) {
+        let position = self
+            .chunk_position_by_type("IDAT")
+            .or_else(|| self.chunk_position_by_type("IEND"))
+            .unwrap_or(self.chunks.len());
+        self.chunks
+            .retain(|chunk| chunk.chunk_type().bytes() != *b"IDAT");
+
+        let idat = ChunkType::try_from(*b"IDAT").unwrap();
+        let new_chunks = data
+            .chunks(Png::IDAT_CHUNK_SIZE)
+            .map(|data| Chunk::new(idat, data.to_vec()));
+        self.chunks.splice(position..position, new_chunks);
+    }
+
+    /// Keeps only the chunks `keep` returns true for, in order, and returns
+    /// the ones that were removed.
+    pub fn retain<F>(&mut self, mut keep: F) -> Vec<Chunk>
+    where
+        F: FnMut(&Chunk) -> bool,
+    {
+        let (kept, removed) = self.chunks.drain(..).partition(|chunk| keep(chunk));
+        self.chunks = kept;
+
+        removed
+    }
+
+    /// Lists the chunks that were added, removed, moved or changed to get
+    /// from `self` to `other`.
+    pub fn diff(&self, other: &Png) -> PngDiff {
+        PngDiff::between(&self.chunks, &other.chunks)
+    }
+
+    /// Checks the file against the spec's rules on chunk ordering and
+    /// returns every violation found. An empty list means the file is valid.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut validator = Validator::new();
+        for chunk in &self.chunks {
+            validator.check(chunk);
+        }
+
+        validator.finish()
     }
 }
 
 impl TryFrom<&[u8]> for Png {
-    type Error = crate::Error;
+    type Error = PngError;
 
-    fn try_from(bytes: &[u8]) -> crate::Result<Self> {
-        let (header_bytes, chunks_bytes) = bytes.split_at(8);
-        let header: [u8; 8] = header_bytes.try_into().unwrap();
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let chunks = ChunkRefs::new(bytes)?
+            .map(|chunk| chunk.map(Chunk::from))
+            .collect::<Result<_>>()?;
 
-        if header != Png::STANDARD_HEADER {
-            return Err(Box::new(StrError("Invalid png header")));
-        }
+        Ok(Self { chunks })
+    }
+}
 
-        let mut chunks = Vec::new();
-        let mut ptr = 0;
-        loop {
-            let length_bytes = chunks_bytes.get(ptr..(ptr + 4)).unwrap();
-            ptr += 4;
+/// Iterates over the chunks of a PNG that is already in memory, borrowing
+/// each one instead of copying it out. Iteration stops after the first error.
+pub struct ChunkRefs<'a> {
+    bytes: &'a [u8],
+    index: usize,
+    offset: usize,
+    done: bool,
+}
 
-            let length_bytes: [u8; 4] = length_bytes.try_into().unwrap();
-            let length = u32::from_be_bytes(length_bytes);
+impl<'a> ChunkRefs<'a> {
+    /// Checks the signature at the start of `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        let header_len = Png::STANDARD_HEADER.len();
+        if bytes.get(..header_len) != Some(Png::STANDARD_HEADER) {
+            return Err(PngError::InvalidSignature {
+                found: bytes.iter().take(header_len).copied().collect(),
+            });
+        }
 
-            let data_size: usize = 4 + length as usize + 4;
-            let data_bytes = chunks_bytes.get(ptr..(ptr + data_size)).unwrap();
-            assert_eq!(length, data_bytes.len() as u32 - 8);
-            ptr += data_size;
+        Ok(Self {
+            bytes,
+            index: 0,
+            offset: header_len,
+            done: false,
+        })
+    }
 
-            let chunk: Chunk =
-                Chunk::try_from([&length_bytes as &[u8], data_bytes].concat().as_ref()).unwrap();
+    fn next_chunk(&mut self) -> Result<ChunkRef<'a>> {
+        let rest = &self.bytes[self.offset..];
+        let truncated = |needed| PngError::TruncatedChunk {
+            index: Some(self.index),
+            offset: self.offset,
+            needed,
+            available: rest.len(),
+        };
+
+        let length_bytes: [u8; 4] = match rest.get(..4) {
+            Some(length_bytes) => length_bytes.try_into().unwrap(),
+            None => return Err(truncated(4)),
+        };
+        // Saturate rather than overflow where usize is 32 bits; a chunk that
+        // big can't be in the slice anyway.
+        let chunk_size =
+            (u32::from_be_bytes(length_bytes) as usize).saturating_add(Chunk::OVERHEAD);
+        let chunk_bytes = rest
+            .get(..chunk_size)
+            .ok_or_else(|| truncated(chunk_size))?;
+
+        let chunk =
+            ChunkRef::try_from(chunk_bytes).map_err(|err| err.at_chunk(self.index, self.offset))?;
+
+        self.index += 1;
+        self.offset += chunk_size;
+
+        Ok(chunk)
+    }
+}
 
-            chunks.push(chunk);
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = Result<ChunkRef<'a>>;
 
-            if chunks_bytes.len() == ptr {
-                break;
-            }
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.offset == self.bytes.len() {
+            return None;
         }
 
-        Ok(Self { chunks })
+        let chunk = self.next_chunk();
+        self.done = chunk.is_err();
+
+        Some(chunk)
     }
 }
 
 impl fmt::Display for Png {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.chunks())
+        for chunk in self.chunks() {
+            writeln!(f, "{}", chunk)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -105,17 +275,17 @@ mod tests {
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
     use crate::Result;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
     use std::convert::TryFrom;
     use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -196,6 +366,64 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_invalid_signature_error() {
+        let png = Png::try_from([137, 80, 78].as_ref());
+
+        assert!(matches!(
+            png,
+            Err(PngError::InvalidSignature { ref found }) if found == &[137, 80, 78]
+        ));
+    }
+
+    #[test]
+    fn test_truncated_chunk_error() {
+        let bytes = &PNG_FILE[..PNG_FILE.len() - 6];
+        let png = Png::try_from(bytes);
+
+        // IEND is the 7th chunk and starts 12 bytes before the end of the file.
+        assert!(matches!(
+            png,
+            Err(PngError::TruncatedChunk {
+                index: Some(6),
+                offset: 4791,
+                needed: 12,
+                available: 6,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_crc_mismatch_error() {
+        let mut bytes = PNG_FILE.to_vec();
+        // Flip a bit in the IHDR data.
+        bytes[16] ^= 1;
+
+        let png = Png::try_from(bytes.as_ref());
+
+        assert!(matches!(
+            png,
+            Err(PngError::CrcMismatch {
+                index: Some(0),
+                offset: 29,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_remove_missing_chunk() {
+        let mut png = testing_png();
+        let removed = png.remove_chunk("TeSt");
+        assert!(matches!(removed, Err(PngError::ChunkNotFound { .. })));
+    }
+
+    #[test]
+    fn test_missing_header() {
+        let png = testing_png();
+        assert!(matches!(png.header(), Err(PngError::ChunkNotFound { .. })));
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -203,6 +431,40 @@ mod tests {
         assert_eq!(chunks.len(), 3);
     }
 
+    #[test]
+    fn test_chunk_refs() {
+        let chunks: Vec<ChunkRef> = ChunkRefs::new(&PNG_FILE)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        assert_eq!(chunks.len(), png.chunks().len());
+        for (chunk_ref, chunk) in chunks.iter().zip(png.chunks()) {
+            assert_eq!(*chunk_ref, chunk.as_chunk_ref());
+        }
+    }
+
+    #[test]
+    fn test_chunk_refs_stop_after_error() {
+        let mut chunks = ChunkRefs::new(&PNG_FILE[..PNG_FILE.len() - 1]).unwrap();
+
+        assert!(chunks.by_ref().any(|chunk| chunk.is_err()));
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am the second middle chunk").unwrap());
+
+        let data: Vec<String> = png
+            .chunks_by_type("miDl")
+            .map(|chunk| chunk.data_as_string().unwrap())
+            .collect();
+        assert_eq!(data, ["I am another chunk", "I am the second middle chunk"]);
+    }
+
     #[test]
     fn test_chunk_by_type() {
         let png = testing_png();
@@ -229,6 +491,52 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_insert_chunk_at() {
+        let mut png = testing_png();
+        png.insert_chunk_at(1, chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+        assert_eq!(png.chunks().len(), 4);
+
+        let end = png.chunks().len();
+        png.insert_chunk_at(end, chunk_from_strings("LaSt", "Message").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[end].chunk_type().to_string(), "LaSt");
+    }
+
+    #[test]
+    fn test_insert_chunk_out_of_range() {
+        let mut png = testing_png();
+        let result = png.insert_chunk_at(4, chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(matches!(
+            result,
+            Err(PngError::ChunkIndexOutOfRange {
+                index: 4,
+                chunks: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        let old = png
+            .replace_chunk("FrSt", chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        assert_eq!(&old.chunk_type().to_string(), "FrSt");
+        assert_eq!(&png.chunks()[0].chunk_type().to_string(), "TeSt");
+        assert!(png.chunk_by_type("FrSt").is_none());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_replace_missing_chunk() {
+        let mut png = testing_png();
+        let result = png.replace_chunk("NoPe", chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -239,10 +547,57 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_retain() {
+        let mut png = testing_png();
+        let removed = png.retain(|chunk| chunk.chunk_type().to_string() != "miDl");
+
+        assert_eq!(png.chunks().len(), 2);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].chunk_type().to_string(), "miDl");
+        assert!(png.chunk_by_type("miDl").is_none());
+    }
+
+    #[test]
+    fn test_image_data() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.image_data(), png.chunk_by_type("IDAT").unwrap().data());
+    }
+
+    #[test]
+    fn test_replace_image_data() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let data: Vec<u8> = (0..Png::IDAT_CHUNK_SIZE * 2 + 10)
+            .map(|i| i as u8)
+            .collect();
+        png.replace_image_data(&data);
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(
+            types,
+            ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "IDAT", "IDAT", "RuSt", "IEND"]
+        );
+        assert_eq!(png.image_data(), data);
+    }
+
+    #[test]
+    fn test_replace_missing_image_data() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        png.replace_image_data(b"data");
+
+        assert_eq!(png.chunks()[3].chunk_type().to_string(), "IDAT");
+        assert_eq!(png.chunks()[4].chunk_type().to_string(), "IEND");
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -259,6 +614,48 @@ mod tests {
         let png: Png = TryFrom::try_from(bytes.as_ref()).unwrap();
 
         let _png_string = format!("{}", png);
+        assert_eq!(
+            png.to_string(),
+            "FrSt (20 bytes)\nmiDl (18 bytes)\nLASt (19 bytes)\n"
+        );
+    }
+
+    /// A chunk with a random letter type and up to 64 bytes of data.
+    fn arbitrary_chunk() -> impl Strategy<Value = Chunk> {
+        ("[a-zA-Z]{4}", vec(any::<u8>(), 0..64))
+            .prop_map(|(chunk_type, data)| Chunk::of_type(&chunk_type, data))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(chunks in vec(arbitrary_chunk(), 0..8)) {
+            let png = Png::from_chunks(chunks);
+            prop_assert_eq!(Png::try_from(png.as_bytes().as_slice())?, png);
+        }
+
+        #[test]
+        fn prop_arbitrary_bytes_dont_panic(bytes in vec(any::<u8>(), 0..256)) {
+            let _ = Png::try_from(bytes.as_slice());
+
+            let signed: Vec<u8> = Png::STANDARD_HEADER.iter().chain(&bytes).copied().collect();
+            let _ = Png::try_from(signed.as_slice());
+        }
+
+        #[test]
+        fn prop_damaged_file_doesnt_panic(
+            chunks in vec(arbitrary_chunk(), 1..8),
+            damage in vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+            cut in any::<prop::sample::Index>(),
+        ) {
+            let mut bytes = Png::from_chunks(chunks).as_bytes();
+            let len = bytes.len();
+            for (index, byte) in damage {
+                bytes[index.index(len)] = byte;
+            }
+            bytes.truncate(cut.index(bytes.len() + 1));
+
+            let _ = Png::try_from(bytes.as_slice());
+        }
     }
 
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
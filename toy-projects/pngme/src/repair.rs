@@ -0,0 +1,424 @@
+//! Best-effort recovery of damaged PNG files.
+
+use crate::chunk::{Chunk, ChunkRef};
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::{PngError, Result};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::str::FromStr;
+
+/// Choices `recover` can't make on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RepairOptions {
+    /// Keep chunks whose CRC doesn't match their data, with a recomputed
+    /// CRC. Otherwise they are dropped, since the data may be damaged.
+    pub fix_crcs: bool,
+}
+
+/// One thing `recover` had to fix. Offsets are into the damaged file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    /// The file didn't start with the PNG signature.
+    Signature,
+    /// Bytes that aren't part of any chunk were skipped.
+    Skipped { offset: usize, length: usize },
+    /// A chunk's stored CRC doesn't match its data. `recomputed` says if the
+    /// chunk was kept with a new CRC or dropped.
+    BadCrc {
+        offset: usize,
+        chunk_type: ChunkType,
+        expected: u32,
+        actual: u32,
+        recomputed: bool,
+    },
+    /// The file ends partway through a chunk, which was dropped.
+    Truncated {
+        offset: usize,
+        chunk_type: ChunkType,
+        needed: usize,
+        available: usize,
+    },
+    /// Bytes after IEND were dropped.
+    TrailingData { offset: usize, length: usize },
+    /// The file has no IEND, so one was added.
+    MissingIend,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fix::Signature => write!(f, "Replaced the missing or damaged PNG signature"),
+            Fix::Skipped { offset, length } => write!(
+                f,
+                "Skipped {} unreadable byte(s) at offset {}",
+                length, offset
+            ),
+            Fix::BadCrc {
+                offset,
+                chunk_type,
+                expected,
+                actual,
+                recomputed,
+            } => write!(
+                f,
+                "{} {} chunk at offset {} with a bad checksum: expected {:#010x} but found {:#010x}",
+                if *recomputed { "Fixed" } else { "Dropped" },
+                chunk_type,
+                offset,
+                expected,
+                actual
+            ),
+            Fix::Truncated {
+                offset,
+                chunk_type,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Dropped truncated {} chunk at offset {}: needed {} bytes but only {} are left",
+                chunk_type, offset, needed, available
+            ),
+            Fix::TrailingData { offset, length } => write!(
+                f,
+                "Dropped {} byte(s) after IEND at offset {}",
+                length, offset
+            ),
+            Fix::MissingIend => write!(f, "Added the missing IEND chunk"),
+        }
+    }
+}
+
+/// What `recover` salvaged from a damaged file, and how.
+pub struct Repair {
+    pub png: Png,
+    pub fixes: Vec<Fix>,
+}
+
+/// Salvages every chunk it can from a damaged PNG.
+///
+/// Unlike `Png::try_from`, this doesn't stop at the first problem. After
+/// bytes that can't be read as a chunk, it looks for the next offset where a
+/// chunk with a valid type and a matching CRC starts and carries on from
+/// there. Anything after IEND is dropped, and an IEND is added if there
+/// wasn't one.
+///
+/// A chunk with a bad CRC is only trusted to be a chunk, rather than garbage
+/// that happens to look like one, if another chunk or the end of the file
+/// follows it. Fails if not a single chunk could be found.
+pub fn recover(bytes: &[u8], options: RepairOptions) -> Result<Repair> {
+    let mut chunks = Vec::new();
+    let mut fixes = Vec::new();
+
+    let signature_len = Png::STANDARD_HEADER.len();
+    let mut offset = signature_len;
+    if !bytes.starts_with(Png::STANDARD_HEADER) {
+        fixes.push(Fix::Signature);
+        if header_at(bytes, signature_len).is_none() {
+            offset = 0;
+        }
+    }
+
+    while offset < bytes.len() {
+        let (chunk_type, end) = match header_at(bytes, offset) {
+            Some((chunk_type, end)) if end <= bytes.len() => (chunk_type, end),
+            header => {
+                let next = resync(bytes, offset + 1);
+                match (next, header) {
+                    (Some(next), _) => fixes.push(Fix::Skipped {
+                        offset,
+                        length: next - offset,
+                    }),
+                    (None, Some((chunk_type, end))) => fixes.push(Fix::Truncated {
+                        offset,
+                        chunk_type,
+                        needed: end - offset,
+                        available: bytes.len() - offset,
+                    }),
+                    (None, None) => fixes.push(Fix::Skipped {
+                        offset,
+                        length: bytes.len() - offset,
+                    }),
+                }
+
+                offset = next.unwrap_or(bytes.len());
+                continue;
+            }
+        };
+
+        match ChunkRef::try_from(&bytes[offset..end]) {
+            Ok(chunk) => chunks.push(chunk.to_chunk()),
+            Err(PngError::CrcMismatch {
+                expected, actual, ..
+            }) if end == bytes.len() || header_at(bytes, end).is_some() => {
+                if options.fix_crcs {
+                    let data = &bytes[offset + 8..end - 4];
+                    chunks.push(Chunk::new(chunk_type, data.to_vec()));
+                }
+                fixes.push(Fix::BadCrc {
+                    offset,
+                    chunk_type,
+                    expected,
+                    actual,
+                    recomputed: options.fix_crcs,
+                });
+            }
+            Err(_) => {
+                let next = resync(bytes, offset + 1).unwrap_or(bytes.len());
+                fixes.push(Fix::Skipped {
+                    offset,
+                    length: next - offset,
+                });
+                offset = next;
+                continue;
+            }
+        }
+
+        offset = end;
+        if chunk_type.bytes() == *b"IEND" {
+            break;
+        }
+    }
+
+    if offset < bytes.len() {
+        fixes.push(Fix::TrailingData {
+            offset,
+            length: bytes.len() - offset,
+        });
+    }
+
+    if chunks.is_empty() {
+        return Err(PngError::NothingRecovered);
+    }
+
+    let has_iend = chunks
+        .last()
+        .is_some_and(|chunk| chunk.chunk_type().bytes() == *b"IEND");
+    if !has_iend {
+        chunks.push(Chunk::new(ChunkType::from_str("IEND")?, Vec::new()));
+        fixes.push(Fix::MissingIend);
+    }
+
+    Ok(Repair {
+        png: Png::from_chunks(chunks),
+        fixes,
+    })
+}
+
+/// Reads the chunk header at `offset`, returning the chunk type and where
+/// the chunk would end. `None` if there's no room for a header or the type
+/// isn't made of letters.
+fn header_at(bytes: &[u8], offset: usize) -> Option<(ChunkType, usize)> {
+    let header = bytes.get(offset..offset.checked_add(8)?)?;
+    let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+    let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(&header[4..]).unwrap()).ok()?;
+
+    Some((
+        chunk_type,
+        offset
+            .saturating_add(length)
+            .saturating_add(Chunk::OVERHEAD),
+    ))
+}
+
+/// The first offset from `start` on where a whole chunk with a matching CRC
+/// starts.
+fn resync(bytes: &[u8], start: usize) -> Option<usize> {
+    (start..bytes.len()).find(|&offset| match header_at(bytes, offset) {
+        Some((_, end)) if end <= bytes.len() => ChunkRef::try_from(&bytes[offset..end]).is_ok(),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    /// Offset of the `index`th chunk in the dice image.
+    fn chunk_offset(index: usize) -> usize {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        Png::STANDARD_HEADER.len()
+            + png.chunks()[..index]
+                .iter()
+                .map(|chunk| chunk.length() as usize + Chunk::OVERHEAD)
+                .sum::<usize>()
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    fn recover_default(bytes: &[u8]) -> Repair {
+        recover(bytes, RepairOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_intact_file() {
+        let repair = recover_default(PNG_FILE);
+
+        assert!(repair.fixes.is_empty());
+        assert_eq!(repair.png.as_bytes(), PNG_FILE);
+    }
+
+    #[test]
+    fn test_bad_crc_dropped() {
+        let mut bytes = PNG_FILE.to_vec();
+        let offset = chunk_offset(5);
+        bytes[offset + 8] ^= 0xff;
+
+        let repair = recover_default(&bytes);
+        assert_eq!(
+            chunk_types(&repair.png),
+            ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "IEND"]
+        );
+        assert!(matches!(
+            repair.fixes[..],
+            [Fix::BadCrc {
+                recomputed: false,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_bad_crc_recomputed() {
+        let mut bytes = PNG_FILE.to_vec();
+        let offset = chunk_offset(5);
+        bytes[offset + 8] ^= 0xff;
+
+        let repair = recover(&bytes, RepairOptions { fix_crcs: true }).unwrap();
+        assert_eq!(repair.png.chunks().len(), 7);
+        assert_eq!(
+            repair.png.chunks()[5].data()[0],
+            PNG_FILE[offset + 8] ^ 0xff
+        );
+        assert!(Png::try_from(repair.png.as_bytes().as_slice()).is_ok());
+        assert!(matches!(
+            repair.fixes[..],
+            [Fix::BadCrc {
+                offset: o,
+                recomputed: true,
+                ..
+            }] if o == offset
+        ));
+    }
+
+    #[test]
+    fn test_resync_after_garbage() {
+        let offset = chunk_offset(2);
+        let mut bytes = PNG_FILE[..offset].to_vec();
+        bytes.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef, b'x']);
+        bytes.extend_from_slice(&PNG_FILE[offset..]);
+
+        let repair = recover_default(&bytes);
+        assert_eq!(repair.png.as_bytes(), PNG_FILE);
+        assert_eq!(repair.fixes, [Fix::Skipped { offset, length: 5 }]);
+    }
+
+    #[test]
+    fn test_corrupted_length() {
+        // A bad length sends the reader into the middle of the next chunk,
+        // so the chunk can't be trusted and is skipped as garbage.
+        let mut bytes = PNG_FILE.to_vec();
+        let offset = chunk_offset(1);
+        bytes[offset + 3] = 2;
+
+        let repair = recover_default(&bytes);
+        assert_eq!(
+            chunk_types(&repair.png),
+            ["IHDR", "gAMA", "pHYs", "IDAT", "RuSt", "IEND"]
+        );
+        assert_eq!(
+            repair.fixes,
+            [Fix::Skipped {
+                offset,
+                length: chunk_offset(2) - offset
+            }]
+        );
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"trailing garbage");
+
+        let repair = recover_default(&bytes);
+        assert_eq!(repair.png.as_bytes(), PNG_FILE);
+        assert_eq!(
+            repair.fixes,
+            [Fix::TrailingData {
+                offset: PNG_FILE.len(),
+                length: 16
+            }]
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = &PNG_FILE[..chunk_offset(4) + 100];
+
+        let repair = recover_default(bytes);
+        assert_eq!(
+            chunk_types(&repair.png),
+            ["IHDR", "sRGB", "gAMA", "pHYs", "IEND"]
+        );
+        assert!(matches!(
+            repair.fixes[..],
+            [Fix::Truncated { available: 100, .. }, Fix::MissingIend]
+        ));
+    }
+
+    #[test]
+    fn test_bad_signature() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[1] = b'J';
+
+        let repair = recover_default(&bytes);
+        assert_eq!(repair.png.as_bytes(), PNG_FILE);
+        assert_eq!(repair.fixes, [Fix::Signature]);
+    }
+
+    #[test]
+    fn test_missing_signature() {
+        let repair = recover_default(&PNG_FILE[8..]);
+
+        assert_eq!(repair.png.as_bytes(), PNG_FILE);
+        assert_eq!(repair.fixes, [Fix::Signature]);
+    }
+
+    #[test]
+    fn test_nothing_to_recover() {
+        assert!(matches!(
+            recover(b"not a png at all", RepairOptions::default()),
+            Err(PngError::NothingRecovered)
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_damaged_file_doesnt_panic(
+            damage in vec((any::<prop::sample::Index>(), any::<u8>()), 0..8),
+            cut in any::<prop::sample::Index>(),
+            fix_crcs: bool,
+        ) {
+            let mut bytes = PNG_FILE.to_vec();
+            let len = bytes.len();
+            for (index, byte) in damage {
+                bytes[index.index(len)] = byte;
+            }
+            bytes.truncate(cut.index(bytes.len() + 1));
+
+            if let Ok(repair) = recover(&bytes, RepairOptions { fix_crcs }) {
+                // Whatever was salvaged is a readable file.
+                prop_assert!(Png::try_from(repair.png.as_bytes().as_slice()).is_ok());
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Machine-readable descriptions of chunks.
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::riff::{FourCc, RiffChunk};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// How chunk data is written out in machine-readable output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataEncoding {
+    Hex,
+    Base64,
+}
+
+impl DataEncoding {
+    /// Encodes `data` as text.
+    pub fn encode(self, data: &[u8]) -> String {
+        match self {
+            DataEncoding::Hex => {
+                data.iter()
+                    .fold(String::with_capacity(data.len() * 2), |mut hex, byte| {
+                        // Writing to a `String` can't fail.
+                        let _ = write!(hex, "{:02x}", byte);
+                        hex
+                    })
+            }
+            DataEncoding::Base64 => BASE64.encode(data),
+        }
+    }
+}
+
+/// Everything about a chunk that scripts might want, as printed by
+/// `print --format json`.
+///
+/// `offset` is where the chunk's length field starts in the file, and `data`
+/// is only filled in if an encoding was asked for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChunkReport {
+    pub index: usize,
+    pub offset: usize,
+    pub chunk_type: ChunkType,
+    pub critical: bool,
+    pub public: bool,
+    pub safe_to_copy: bool,
+    pub length: u32,
+    pub crc: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+impl ChunkReport {
+    /// Describes `chunk`, found at position `index` and byte `offset` of a
+    /// file, including its data if `data` is given.
+    pub fn new(index: usize, offset: usize, chunk: &Chunk, data: Option<DataEncoding>) -> Self {
+        let chunk_type = *chunk.chunk_type();
+
+        Self {
+            index,
+            offset,
+            chunk_type,
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+            length: chunk.length(),
+            crc: chunk.crc(),
+            data: data.map(|encoding| encoding.encode(chunk.data())),
+        }
+    }
+}
+
+/// The RIFF counterpart of [`ChunkReport`]. RIFF chunk codes carry no
+/// flags and the chunks have no CRC, so there is less to say.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RiffChunkReport {
+    pub index: usize,
+    pub offset: usize,
+    pub chunk_type: FourCc,
+    pub length: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+impl RiffChunkReport {
+    /// Describes `chunk`, found at position `index` and byte `offset` of a
+    /// file, including its data if `data` is given.
+    pub fn new(index: usize, offset: usize, chunk: &RiffChunk, data: Option<DataEncoding>) -> Self {
+        Self {
+            index,
+            offset,
+            chunk_type: *chunk.id(),
+            length: chunk.length(),
+            data: data.map(|encoding| encoding.encode(chunk.data())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk() -> Chunk {
+        Chunk::of_type("ruSt", b"Hi!\xff".to_vec())
+    }
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(DataEncoding::Hex.encode(b"Hi!\xff"), "486921ff");
+        assert_eq!(DataEncoding::Base64.encode(b"Hi!\xff"), "SGkh/w==");
+        assert_eq!(DataEncoding::Hex.encode(b""), "");
+    }
+
+    #[test]
+    fn test_report() {
+        let chunk = chunk();
+        let report = ChunkReport::new(2, 33, &chunk, None);
+
+        assert_eq!(report.chunk_type.to_string(), "ruSt");
+        assert!(!report.critical);
+        assert!(!report.public);
+        assert!(report.safe_to_copy);
+        assert_eq!(report.length, 4);
+        assert_eq!(report.crc, chunk.crc());
+        assert_eq!(report.data, None);
+    }
+
+    #[test]
+    fn test_json() {
+        let chunk = chunk();
+        let json =
+            serde_json::to_value(ChunkReport::new(1, 8, &chunk, Some(DataEncoding::Hex))).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "index": 1,
+                "offset": 8,
+                "chunk_type": "ruSt",
+                "critical": false,
+                "public": false,
+                "safe_to_copy": true,
+                "length": 4,
+                "crc": chunk.crc(),
+                "data": "486921ff",
+            })
+        );
+    }
+
+    #[test]
+    fn test_riff_json() {
+        let chunk = RiffChunk::new(FourCc::from(*b"XMP "), b"Hi!".to_vec());
+        let json = serde_json::to_value(RiffChunkReport::new(
+            1,
+            12,
+            &chunk,
+            Some(DataEncoding::Base64),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "index": 1,
+                "offset": 12,
+                "chunk_type": "XMP ",
+                "length": 3,
+                "data": "SGkh",
+            })
+        );
+    }
+
+    #[test]
+    fn test_json_without_data() {
+        let json = serde_json::to_value(ChunkReport::new(0, 8, &chunk(), None)).unwrap();
+
+        assert!(json.get("data").is_none());
+    }
+}
@@ -0,0 +1,614 @@
+//! Decoding a whole PNG to 8-bit RGBA pixels, and encoding RGBA pixels
+//! into a new PNG.
+//!
+//! Decoding handles every color type and bit depth the spec allows, palettes
+//! with `tRNS` transparency, and Adam7 interlacing. Encoding always writes a
+//! non-interlaced 8-bit RGBA image.
+
+use crate::ancillary::Transparency;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::{ColorType, ImageHeader, InterlaceMethod};
+use crate::pixels::{
+    bytes_per_pixel, deflate, filter_row, image_too_large, inflate, stride_for, unfilter_rows,
+    FilterType,
+};
+use crate::png::Png;
+use crate::{PngError, Result};
+use std::convert::TryFrom;
+
+/// The Adam7 passes as `(x, y, dx, dy)`: each pass holds the pixels from
+/// column `x` and row `y` on, every `dx` columns and `dy` rows.
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// The single "pass" of a non-interlaced image.
+const NO_INTERLACE: [(u32, u32, u32, u32); 1] = [(0, 0, 1, 1)];
+
+/// How `RgbaImage::encode` picks the filter for each scanline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FilterStrategy {
+    /// Use the same filter for every row.
+    Fixed(FilterType),
+    /// Try every filter on each row and keep the one whose output has the
+    /// smallest sum of absolute values, read as signed bytes. This is the
+    /// heuristic the spec recommends, and usually compresses best.
+    #[default]
+    Adaptive,
+}
+
+/// An image as 8-bit RGBA pixels, row by row from the top left, without
+/// any padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Wraps `pixels`, which must hold exactly 4 bytes for every pixel of a
+    /// `width` by `height` image.
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self> {
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|count| count.checked_mul(4));
+        if width == 0 || height == 0 || expected != Some(pixels.len()) {
+            return Err(PngError::InvalidImageSize {
+                width,
+                height,
+                length: pixels.len(),
+            });
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Decodes the pixels of `png`: inflates the `IDAT` data, undoes the
+    /// filters and interlacing, and converts every pixel to 8-bit RGBA.
+    ///
+    /// 16-bit samples are cut down to their high byte, and lower bit depths
+    /// are scaled up so their brightest value becomes 255.
+    pub fn decode(png: &Png) -> Result<Self> {
+        let header = png.header()?;
+        let palette = match header.color_type {
+            ColorType::Indexed => Some(palette(png)?),
+            _ => None,
+        };
+        let transparency = png
+            .chunk_by_type(Transparency::CHUNK_TYPE)
+            .map(|chunk| Transparency::from_chunk(chunk, header.color_type))
+            .transpose()?;
+
+        let passes = match header.interlace_method {
+            InterlaceMethod::None => &NO_INTERLACE[..],
+            InterlaceMethod::Adam7 => &ADAM7[..],
+        };
+        let sizes: Vec<(u32, u32)> = passes
+            .iter()
+            .map(|&pass| pass_size(&header, pass))
+            .collect();
+        let expected = sizes
+            .iter()
+            .filter(|(width, height)| *width > 0 && *height > 0)
+            .try_fold(0usize, |total, &(width, height)| {
+                let stride = stride_for(&header, width)? + 1;
+                stride
+                    .checked_mul(height as usize)
+                    .and_then(|size| total.checked_add(size))
+                    .ok_or_else(image_too_large)
+            })?;
+        let filtered = inflate(&png.image_data(), expected)?;
+
+        let converter = Converter {
+            header: &header,
+            palette: palette.as_deref(),
+            transparency: transparency.as_ref(),
+        };
+        let bpp = bytes_per_pixel(&header);
+        let width = header.width as usize;
+        let size = width
+            .checked_mul(header.height as usize)
+            .and_then(|size| size.checked_mul(4))
+            .ok_or_else(image_too_large)?;
+        let mut pixels = vec![0; size];
+        let mut start = 0;
+
+        for (&(x0, y0, dx, dy), &(pass_width, pass_height)) in passes.iter().zip(&sizes) {
+            // Empty passes aren't stored at all, not even as filter bytes.
+            if pass_width == 0 || pass_height == 0 {
+                continue;
+            }
+
+            let stride = stride_for(&header, pass_width)?;
+            let end = start + (stride + 1) * pass_height as usize;
+            let (_, rows) = unfilter_rows(&filtered[start..end], stride, bpp)?;
+            start = end;
+
+            for (y, row) in rows.chunks_exact(stride).enumerate() {
+                let target_y = (y0 + y as u32 * dy) as usize;
+                for x in 0..pass_width as usize {
+                    let target_x = (x0 + x as u32 * dx) as usize;
+                    let offset = (target_y * width + target_x) * 4;
+                    pixels[offset..offset + 4].copy_from_slice(&converter.pixel(row, x)?);
+                }
+            }
+        }
+
+        Self::new(header.width, header.height, pixels)
+    }
+
+    /// Encodes the pixels as a non-interlaced 8-bit RGBA PNG holding just
+    /// `IHDR`, `IDAT` and `IEND`.
+    pub fn encode(&self, strategy: FilterStrategy) -> Result<Png> {
+        let header = ImageHeader {
+            width: self.width,
+            height: self.height,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        };
+
+        let stride = self.width as usize * 4;
+        let mut filtered = Vec::with_capacity((stride + 1) * self.height as usize);
+        let mut candidate = Vec::with_capacity(stride);
+        let mut prior = None;
+
+        for row in self.pixels.chunks_exact(stride) {
+            let filter = match strategy {
+                FilterStrategy::Fixed(filter) => filter,
+                FilterStrategy::Adaptive => FilterType::ALL
+                    .iter()
+                    .copied()
+                    .min_by_key(|&filter| {
+                        candidate.clear();
+                        filter_row(filter, 4, prior, row, &mut candidate);
+                        candidate
+                            .iter()
+                            .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                            .sum::<u64>()
+                    })
+                    .unwrap(),
+            };
+
+            filtered.push(filter as u8);
+            filter_row(filter, 4, prior, row, &mut filtered);
+            prior = Some(row);
+        }
+
+        let iend = Chunk::new(ChunkType::try_from(*b"IEND").unwrap(), Vec::new());
+        let mut png = Png::from_chunks(vec![header.to_chunk(), iend]);
+        png.replace_image_data(&deflate(&filtered)?);
+
+        Ok(png)
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The RGBA bytes of every pixel.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Takes the RGBA bytes out of the image.
+    pub fn into_pixels(self) -> Vec<u8> {
+        self.pixels
+    }
+
+    /// The pixel at column `x` and row `y`, or `None` if that's outside the
+    /// image.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let offset = (y as usize * self.width as usize + x as usize) * 4;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[offset..offset + 4]);
+        Some(pixel)
+    }
+}
+
+/// Turns the samples of one unfiltered scanline into RGBA pixels.
+struct Converter<'a> {
+    header: &'a ImageHeader,
+    palette: Option<&'a [[u8; 3]]>,
+    transparency: Option<&'a Transparency>,
+}
+
+impl Converter<'_> {
+    fn pixel(&self, row: &[u8], x: usize) -> Result<[u8; 4]> {
+        let depth = self.header.bit_depth;
+        let channels = self.header.color_type.channels();
+        let sample = |channel: usize| sample(row, x * channels + channel, depth);
+        let scale = |value: u16| scale(value, depth);
+
+        let pixel = match self.header.color_type {
+            ColorType::Grayscale => {
+                let gray = sample(0);
+                let alpha = match self.transparency {
+                    Some(Transparency::Gray(key)) if *key == gray => 0,
+                    _ => 255,
+                };
+                let gray = scale(gray);
+                [gray, gray, gray, alpha]
+            }
+            ColorType::Rgb => {
+                let (red, green, blue) = (sample(0), sample(1), sample(2));
+                let alpha = match self.transparency {
+                    Some(Transparency::Rgb(r, g, b)) if (*r, *g, *b) == (red, green, blue) => 0,
+                    _ => 255,
+                };
+                [scale(red), scale(green), scale(blue), alpha]
+            }
+            ColorType::Indexed => {
+                let index = sample(0) as usize;
+                let palette = self.palette.unwrap_or(&[]);
+                let [red, green, blue] = *palette.get(index).ok_or_else(|| {
+                    invalid(format!(
+                        "palette index {} is past the {} palette entries",
+                        index,
+                        palette.len()
+                    ))
+                })?;
+                let alpha = match self.transparency {
+                    Some(Transparency::Indexed(alpha)) => alpha.get(index).copied().unwrap_or(255),
+                    _ => 255,
+                };
+                [red, green, blue, alpha]
+            }
+            ColorType::GrayscaleAlpha => {
+                let gray = scale(sample(0));
+                [gray, gray, gray, scale(sample(1))]
+            }
+            ColorType::Rgba => [
+                scale(sample(0)),
+                scale(sample(1)),
+                scale(sample(2)),
+                scale(sample(3)),
+            ],
+        };
+
+        Ok(pixel)
+    }
+}
+
+/// Size of an interlacing pass, which is 0 in either direction if the
+/// image is too small for the pass to hold any pixels.
+fn pass_size(header: &ImageHeader, (x0, y0, dx, dy): (u32, u32, u32, u32)) -> (u32, u32) {
+    let size = |length: u32, start: u32, step: u32| {
+        if length > start {
+            (length - start).div_ceil(step)
+        } else {
+            0
+        }
+    };
+
+    (size(header.width, x0, dx), size(header.height, y0, dy))
+}
+
+/// Reads sample number `index` of a scanline. Samples below 8 bits are
+/// packed from the most significant bit down, and 16-bit samples are big
+/// endian.
+fn sample(row: &[u8], index: usize, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+        8 => row[index] as u16,
+        _ => {
+            let bit = index * bit_depth as usize;
+            let shift = 8 - bit_depth as usize - bit % 8;
+            let mask = (1 << bit_depth) - 1;
+            ((row[bit / 8] >> shift) & mask) as u16
+        }
+    }
+}
+
+/// Scales a sample of any bit depth to 8 bits.
+fn scale(value: u16, bit_depth: u8) -> u8 {
+    match bit_depth {
+        16 => (value >> 8) as u8,
+        8 => value as u8,
+        _ => (value as u32 * 255 / ((1 << bit_depth) - 1)) as u8,
+    }
+}
+
+/// The colors in the `PLTE` chunk.
+fn palette(png: &Png) -> Result<Vec<[u8; 3]>> {
+    let chunk = png
+        .chunk_by_type("PLTE")
+        .ok_or_else(|| PngError::ChunkNotFound {
+            chunk_type: "PLTE".to_string(),
+        })?;
+
+    let data = chunk.data();
+    if data.is_empty() || data.len() % 3 != 0 || data.len() > 256 * 3 {
+        return Err(PngError::InvalidChunkData {
+            chunk_type: "PLTE".to_string(),
+            reason: format!("{} bytes is not a whole number of 1-256 colors", data.len()),
+        });
+    }
+
+    Ok(data
+        .chunks_exact(3)
+        .map(|color| [color[0], color[1], color[2]])
+        .collect())
+}
+
+fn invalid(reason: String) -> PngError {
+    PngError::InvalidChunkData {
+        chunk_type: "IDAT".to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixels::PixelData;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    fn header(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> ImageHeader {
+        ImageHeader {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    /// Builds a PNG from raw scanlines, each with filter type None, plus
+    /// any extra chunks to put before `IDAT`.
+    fn png_from_rows(header: ImageHeader, rows: &[Vec<u8>], extra: Vec<Chunk>) -> Png {
+        let filtered: Vec<u8> = rows
+            .iter()
+            .flat_map(|row| std::iter::once(0).chain(row.iter().copied()))
+            .collect();
+
+        let mut chunks = vec![header.to_chunk()];
+        chunks.extend(extra);
+        chunks.push(Chunk::of_type("IEND", Vec::new()));
+
+        let mut png = Png::from_chunks(chunks);
+        png.replace_image_data(&deflate(&filtered).unwrap());
+        png
+    }
+
+    /// A gradient that exercises every filter.
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        let pixels = (0..height)
+            .flat_map(|y| {
+                (0..width).flat_map(move |x| {
+                    [
+                        (x * 7) as u8,
+                        (y * 13) as u8,
+                        ((x + y) * 3) as u8,
+                        255 - x as u8,
+                    ]
+                })
+            })
+            .collect();
+        RgbaImage::new(width, height, pixels).unwrap()
+    }
+
+    /// Interlaces an image by hand, for checking the decoder against.
+    fn interlace(image: &RgbaImage) -> Vec<u8> {
+        let header = ImageHeader {
+            interlace_method: InterlaceMethod::Adam7,
+            ..header(image.width, image.height, 8, ColorType::Rgba)
+        };
+
+        let mut filtered = Vec::new();
+        for &pass in ADAM7.iter() {
+            let (width, height) = pass_size(&header, pass);
+            if width == 0 || height == 0 {
+                continue;
+            }
+
+            let (x0, y0, dx, dy) = pass;
+            for y in 0..height {
+                filtered.push(0);
+                for x in 0..width {
+                    filtered.extend(image.pixel(x0 + x * dx, y0 + y * dy).unwrap());
+                }
+            }
+        }
+
+        deflate(&filtered).unwrap()
+    }
+
+    #[test]
+    fn test_decode_dice() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        let image = RgbaImage::decode(&png).unwrap();
+        let raw = PixelData::decode(png.header().unwrap(), &png.image_data()).unwrap();
+
+        assert_eq!((image.width(), image.height()), (50, 50));
+        assert_eq!(image.pixels(), raw.data());
+    }
+
+    #[test]
+    fn test_round_trip_every_strategy() {
+        let image = gradient(23, 17);
+        let strategies = FilterType::ALL
+            .iter()
+            .map(|&filter| FilterStrategy::Fixed(filter))
+            .chain(std::iter::once(FilterStrategy::Adaptive));
+
+        for strategy in strategies {
+            let png = image.encode(strategy).unwrap();
+            let png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+
+            assert!(png.validate().is_empty(), "{:?}", strategy);
+            assert_eq!(RgbaImage::decode(&png).unwrap(), image, "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn test_adaptive_picks_per_row() {
+        let image = gradient(40, 10);
+        let png = image.encode(FilterStrategy::Adaptive).unwrap();
+        let raw = PixelData::decode(png.header().unwrap(), &png.image_data()).unwrap();
+        let fixed = image
+            .encode(FilterStrategy::Fixed(FilterType::None))
+            .unwrap();
+
+        assert_eq!(raw.data(), image.pixels());
+        assert!(png.image_data().len() < fixed.image_data().len());
+    }
+
+    #[test]
+    fn test_decode_adam7() {
+        // Sizes that leave some passes empty and some partly filled.
+        for &(width, height) in [(1, 1), (3, 2), (9, 9), (17, 5)].iter() {
+            let image = gradient(width, height);
+            let header = ImageHeader {
+                interlace_method: InterlaceMethod::Adam7,
+                ..header(width, height, 8, ColorType::Rgba)
+            };
+            let mut png = png_from_rows(header, &[], Vec::new());
+            png.replace_image_data(&interlace(&image));
+
+            assert_eq!(
+                RgbaImage::decode(&png).unwrap(),
+                image,
+                "{}x{}",
+                width,
+                height
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_low_bit_depth_gray() {
+        let png = png_from_rows(
+            header(4, 1, 2, ColorType::Grayscale),
+            &[vec![0b00_01_10_11]],
+            Vec::new(),
+        );
+        let image = RgbaImage::decode(&png).unwrap();
+
+        let grays: Vec<u8> = image.pixels().chunks(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(grays, [0, 85, 170, 255]);
+    }
+
+    #[test]
+    fn test_decode_gray_transparency() {
+        let png = png_from_rows(
+            header(2, 1, 16, ColorType::Grayscale),
+            &[vec![0x12, 0x34, 0xff, 0xff]],
+            vec![Chunk::of_type("tRNS", [0x12, 0x34])],
+        );
+        let image = RgbaImage::decode(&png).unwrap();
+
+        assert_eq!(image.pixel(0, 0), Some([0x12, 0x12, 0x12, 0]));
+        assert_eq!(image.pixel(1, 0), Some([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_decode_rgb_16() {
+        let png = png_from_rows(
+            header(1, 1, 16, ColorType::Rgb),
+            &[vec![0xab, 0xcd, 0x12, 0x34, 0x00, 0xff]],
+            Vec::new(),
+        );
+
+        assert_eq!(
+            RgbaImage::decode(&png).unwrap().pixel(0, 0),
+            Some([0xab, 0x12, 0x00, 255])
+        );
+    }
+
+    #[test]
+    fn test_decode_palette() {
+        let png = png_from_rows(
+            header(3, 1, 4, ColorType::Indexed),
+            &[vec![0x01, 0x20]],
+            vec![
+                Chunk::of_type("PLTE", [10, 20, 30, 40, 50, 60, 70, 80, 90]),
+                Chunk::of_type("tRNS", [128]),
+            ],
+        );
+        let image = RgbaImage::decode(&png).unwrap();
+
+        assert_eq!(image.pixel(0, 0), Some([10, 20, 30, 128]));
+        assert_eq!(image.pixel(1, 0), Some([40, 50, 60, 255]));
+        assert_eq!(image.pixel(2, 0), Some([70, 80, 90, 255]));
+    }
+
+    #[test]
+    fn test_decode_palette_index_out_of_range() {
+        let png = png_from_rows(
+            header(1, 1, 8, ColorType::Indexed),
+            &[vec![1]],
+            vec![Chunk::of_type("PLTE", [1, 2, 3])],
+        );
+
+        assert!(RgbaImage::decode(&png).is_err());
+    }
+
+    #[test]
+    fn test_decode_missing_palette() {
+        let png = png_from_rows(header(1, 1, 8, ColorType::Indexed), &[vec![0]], Vec::new());
+
+        assert!(matches!(
+            RgbaImage::decode(&png),
+            Err(PngError::ChunkNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_image_too_large() {
+        let png = Png::try_from(&include_bytes!("../tests/fixtures/huge.png")[..]).unwrap();
+
+        assert!(matches!(
+            RgbaImage::decode(&png),
+            Err(PngError::InvalidChunkData { chunk_type, .. }) if chunk_type == "IHDR"
+        ));
+    }
+
+    #[test]
+    fn test_new_checks_size() {
+        assert!(RgbaImage::new(2, 2, vec![0; 16]).is_ok());
+        assert!(matches!(
+            RgbaImage::new(2, 2, vec![0; 15]),
+            Err(PngError::InvalidImageSize { .. })
+        ));
+        assert!(RgbaImage::new(0, 2, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_pass_sizes() {
+        let header = header(3, 2, 8, ColorType::Rgba);
+        let sizes: Vec<_> = ADAM7.iter().map(|&pass| pass_size(&header, pass)).collect();
+
+        assert_eq!(
+            sizes,
+            [(1, 1), (0, 1), (1, 0), (1, 1), (2, 0), (1, 1), (3, 1)]
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! RIFF containers, the chunk format WebP (and WAV and AVI) files use.
+//!
+//! A RIFF file is the 4 bytes `RIFF`, the size of the rest of the file, a
+//! form type like `WEBP`, and then the chunks. Each chunk is a four
+//! character code, a little-endian length and the data, padded with a zero
+//! byte to an even length. There is no checksum.
+
+use crate::{PngError, Result};
+use serde::{Serialize, Serializer};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::str::FromStr;
+
+/// The four character code that names a RIFF chunk or form, like `VP8 ` or
+/// `EXIF`. Codes shorter than four characters are padded with spaces.
+///
+/// Parsing from a string only accepts printable ASCII, but codes read from a
+/// file can be any bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FourCc {
+    bytes: [u8; 4],
+}
+
+impl FourCc {
+    /// The code as bytes.
+    pub fn bytes(&self) -> [u8; 4] {
+        self.bytes
+    }
+}
+
+impl From<[u8; 4]> for FourCc {
+    fn from(bytes: [u8; 4]) -> Self {
+        Self { bytes }
+    }
+}
+
+impl FromStr for FourCc {
+    type Err = PngError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || PngError::InvalidChunkType {
+            index: None,
+            offset: 0,
+            bytes: s.as_bytes().to_vec(),
+        };
+
+        let bytes: [u8; 4] = s.as_bytes().try_into().map_err(|_| invalid())?;
+        if !bytes.iter().all(|b| matches!(b, b' '..=b'~')) {
+            return Err(invalid());
+        }
+
+        Ok(Self { bytes })
+    }
+}
+
+impl fmt::Display for FourCc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &byte in self.bytes.iter() {
+            match byte {
+                b' '..=b'~' => write!(f, "{}", byte as char)?,
+                _ => write!(f, "\\x{:02x}", byte)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for FourCc {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A chunk read from, or to be written to, a RIFF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RiffChunk {
+    id: FourCc,
+    data: Vec<u8>,
+}
+
+impl RiffChunk {
+    /// Size of the code and length fields in front of the data.
+    pub const HEADER_LEN: usize = 8;
+
+    /// Creates a chunk.
+    pub fn new(id: FourCc, data: Vec<u8>) -> Self {
+        Self { id, data }
+    }
+
+    /// The four character code of the chunk.
+    pub fn id(&self) -> &FourCc {
+        &self.id
+    }
+
+    /// The chunk's data, without the header or padding.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Number of bytes of data, not counting the padding.
+    pub fn length(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    /// The chunk as it is stored in a file: code, length, data and a padding
+    /// byte if the length is odd.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::HEADER_LEN + self.data.len() + 1);
+        bytes.extend_from_slice(&self.id.bytes());
+        bytes.extend_from_slice(&self.length().to_le_bytes());
+        bytes.extend_from_slice(&self.data);
+        if self.data.len() % 2 == 1 {
+            bytes.push(0);
+        }
+
+        bytes
+    }
+}
+
+impl fmt::Display for RiffChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} bytes)", self.id, self.length())
+    }
+}
+
+/// A whole RIFF file, held in memory as its form type and chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Riff {
+    form_type: FourCc,
+    chunks: Vec<RiffChunk>,
+}
+
+impl Riff {
+    /// The 4 bytes every RIFF file starts with.
+    pub const SIGNATURE: &'static [u8; 4] = b"RIFF";
+    /// Size of the signature, size and form type fields.
+    pub const HEADER_LEN: usize = 12;
+    /// The form type of WebP files.
+    pub const WEBP: FourCc = FourCc { bytes: *b"WEBP" };
+
+    /// Creates a RIFF file of the given form type from a list of chunks, in
+    /// file order.
+    pub fn from_chunks(form_type: FourCc, chunks: Vec<RiffChunk>) -> Self {
+        Self { form_type, chunks }
+    }
+
+    /// What kind of file this is, like `WEBP` or `WAVE`.
+    pub fn form_type(&self) -> &FourCc {
+        &self.form_type
+    }
+
+    /// Every chunk, in file order.
+    pub fn chunks(&self) -> &[RiffChunk] {
+        &self.chunks
+    }
+
+    /// Adds a chunk at the end.
+    pub fn append_chunk(&mut self, chunk: RiffChunk) {
+        self.chunks.push(chunk);
+    }
+
+    /// Inserts a chunk so that it ends up at `index`, shifting the chunks
+    /// after it. `index` can be at most the number of chunks.
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: RiffChunk) -> Result<()> {
+        if index > self.chunks.len() {
+            return Err(PngError::ChunkIndexOutOfRange {
+                index,
+                chunks: self.chunks.len(),
+            });
+        }
+
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    /// The first chunk with the given code, if there is one.
+    pub fn chunk_by_id(&self, id: &str) -> Option<&RiffChunk> {
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.id().bytes() == id.as_bytes())
+    }
+
+    /// Keeps only the chunks `keep` returns true for, in order, and returns
+    /// the ones that were removed.
+    pub fn retain<F>(&mut self, mut keep: F) -> Vec<RiffChunk>
+    where
+        F: FnMut(&RiffChunk) -> bool,
+    {
+        let (kept, removed) = self.chunks.drain(..).partition(|chunk| keep(chunk));
+        self.chunks = kept;
+
+        removed
+    }
+
+    /// The whole file: the header followed by every chunk.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let body: Vec<u8> = self.chunks.iter().flat_map(RiffChunk::as_bytes).collect();
+        // The size counts the form type and the chunks.
+        let size = (body.len() + 4) as u32;
+
+        let mut bytes = Vec::with_capacity(Self::HEADER_LEN + body.len());
+        bytes.extend_from_slice(Self::SIGNATURE);
+        bytes.extend_from_slice(&size.to_le_bytes());
+        bytes.extend_from_slice(&self.form_type.bytes());
+        bytes.extend(body);
+
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Riff {
+    type Error = PngError;
+
+    /// Parses a RIFF file. Anything after the size given in the header is
+    /// ignored, and the padding byte after the last chunk may be missing,
+    /// since plenty of writers get that wrong.
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < Self::HEADER_LEN || !bytes.starts_with(Self::SIGNATURE) {
+            return Err(PngError::InvalidRiffHeader {
+                found: bytes[..bytes.len().min(Self::HEADER_LEN)].to_vec(),
+            });
+        }
+
+        let size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let end = size.saturating_add(8);
+        if end > bytes.len() {
+            return Err(PngError::TruncatedChunk {
+                index: None,
+                offset: 0,
+                needed: end,
+                available: bytes.len(),
+            });
+        }
+
+        let form_type = FourCc::from(<[u8; 4]>::try_from(&bytes[8..12]).unwrap());
+        let mut chunks = Vec::new();
+        let mut offset = Self::HEADER_LEN;
+
+        while offset < end {
+            let truncated = |needed: usize| PngError::TruncatedChunk {
+                index: Some(chunks.len()),
+                offset,
+                needed,
+                available: end - offset,
+            };
+
+            if end - offset < RiffChunk::HEADER_LEN {
+                return Err(truncated(RiffChunk::HEADER_LEN));
+            }
+            let id = FourCc::from(<[u8; 4]>::try_from(&bytes[offset..offset + 4]).unwrap());
+            let length =
+                u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()) as usize;
+
+            let needed = RiffChunk::HEADER_LEN.saturating_add(length);
+            if end - offset < needed {
+                return Err(truncated(needed));
+            }
+            let data = &bytes[offset + RiffChunk::HEADER_LEN..offset + needed];
+            chunks.push(RiffChunk::new(id, data.to_vec()));
+
+            offset = (offset + needed + length % 2).min(end);
+        }
+
+        Ok(Self { form_type, chunks })
+    }
+}
+
+impl fmt::Display for Riff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in &self.chunks {
+            writeln!(f, "{}", chunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if `bytes` start like a RIFF file.
+pub fn is_riff(bytes: &[u8]) -> bool {
+    bytes.starts_with(Riff::SIGNATURE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEBP_FILE: &[u8] = include_bytes!("../tests/fixtures/tiny.webp");
+
+    fn fourcc(s: &str) -> FourCc {
+        FourCc::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_webp() {
+        let riff = Riff::try_from(WEBP_FILE).unwrap();
+
+        assert_eq!(riff.form_type(), &Riff::WEBP);
+        assert_eq!(riff.chunks()[0].id(), &fourcc("VP8L"));
+        assert_eq!(riff.as_bytes(), WEBP_FILE);
+    }
+
+    #[test]
+    fn test_odd_chunks_are_padded() {
+        let riff = Riff::from_chunks(
+            Riff::WEBP,
+            vec![
+                RiffChunk::new(fourcc("ruSt"), b"odd".to_vec()),
+                RiffChunk::new(fourcc("EXIF"), b"even".to_vec()),
+            ],
+        );
+        let bytes = riff.as_bytes();
+
+        assert_eq!(&bytes[4..8], &(bytes.len() as u32 - 8).to_le_bytes());
+        assert_eq!(&bytes[12..24], b"ruSt\x03\0\0\0odd\0");
+        assert_eq!(Riff::try_from(bytes.as_slice()).unwrap(), riff);
+    }
+
+    #[test]
+    fn test_missing_final_padding_is_tolerated() {
+        let mut bytes = Riff::from_chunks(
+            Riff::WEBP,
+            vec![RiffChunk::new(fourcc("ruSt"), b"odd".to_vec())],
+        )
+        .as_bytes();
+        bytes.pop();
+        let size = bytes.len() as u32 - 8;
+        bytes[4..8].copy_from_slice(&size.to_le_bytes());
+
+        let riff = Riff::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(riff.chunks()[0].data(), b"odd");
+    }
+
+    #[test]
+    fn test_trailing_bytes_are_ignored() {
+        let mut bytes = Riff::try_from(WEBP_FILE).unwrap().as_bytes();
+        bytes.extend_from_slice(b"junk");
+
+        assert_eq!(
+            Riff::try_from(bytes.as_slice()).unwrap().as_bytes(),
+            WEBP_FILE
+        );
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let mut bytes = WEBP_FILE.to_vec();
+        bytes.truncate(bytes.len() - 2);
+        let size = bytes.len() as u32 - 8;
+        bytes[4..8].copy_from_slice(&size.to_le_bytes());
+
+        assert!(matches!(
+            Riff::try_from(bytes.as_slice()),
+            Err(PngError::TruncatedChunk { index: Some(0), .. })
+        ));
+    }
+
+    #[test]
+    fn test_size_past_end_of_file() {
+        let bytes = &WEBP_FILE[..WEBP_FILE.len() - 1];
+
+        assert!(matches!(
+            Riff::try_from(bytes),
+            Err(PngError::TruncatedChunk { index: None, .. })
+        ));
+    }
+
+    #[test]
+    fn test_not_riff() {
+        assert!(matches!(
+            Riff::try_from(&b"\x89PNG\r\n\x1a\n\0\0\0\0"[..]),
+            Err(PngError::InvalidRiffHeader { .. })
+        ));
+        assert!(!is_riff(b"\x89PNG"));
+        assert!(is_riff(WEBP_FILE));
+    }
+
+    #[test]
+    fn test_fourcc() {
+        assert_eq!(fourcc("XMP ").to_string(), "XMP ");
+        assert_eq!(FourCc::from(*b"a\0b\x7f").to_string(), "a\\x00b\\x7f");
+        assert!(FourCc::from_str("abc").is_err());
+        assert!(FourCc::from_str("ab\tc").is_err());
+    }
+
+    #[test]
+    fn test_retain_and_insert() {
+        let mut riff = Riff::try_from(WEBP_FILE).unwrap();
+        riff.insert_chunk_at(0, RiffChunk::new(fourcc("ruSt"), b"hi".to_vec()))
+            .unwrap();
+        assert!(riff
+            .insert_chunk_at(5, RiffChunk::new(fourcc("ruSt"), Vec::new()))
+            .is_err());
+        assert_eq!(riff.chunk_by_id("ruSt").unwrap().data(), b"hi");
+
+        let removed = riff.retain(|chunk| chunk.id().bytes() != *b"ruSt");
+        assert_eq!(removed.len(), 1);
+        assert_eq!(riff.as_bytes(), WEBP_FILE);
+    }
+}
@@ -0,0 +1,177 @@
+//! Deciding which chunks to strip before publishing an image.
+
+use crate::chunk_type::ChunkType;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Public ancillary chunks that only carry information about the file, not
+/// about how to show the image.
+const METADATA_CHUNKS: [&[u8; 4]; 5] = [b"tEXt", b"zTXt", b"iTXt", b"eXIf", b"tIME"];
+
+/// Ancillary chunks that change how the image is shown, or animate it.
+const RENDERING_CHUNKS: [&[u8; 4]; 16] = [
+    b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"mDCV", b"cLLI", b"bKGD", b"hIST",
+    b"tRNS", b"pHYs", b"sPLT", b"acTL", b"fcTL", b"fdAT",
+];
+
+/// Why a chunk gets stripped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StripReason {
+    /// The chunk type isn't registered, so it could hold anything.
+    Private,
+    /// A text, Exif or timestamp chunk.
+    Metadata,
+    /// An ancillary chunk we don't know that is safe to copy, meaning it
+    /// doesn't depend on the pixels and so can't be needed to show them.
+    UnknownSafeToCopy,
+    /// The chunk type was asked for explicitly.
+    Requested,
+}
+
+impl fmt::Display for StripReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            StripReason::Private => "private",
+            StripReason::Metadata => "metadata",
+            StripReason::UnknownSafeToCopy => "unknown, safe to copy",
+            StripReason::Requested => "requested",
+        };
+
+        f.write_str(reason)
+    }
+}
+
+/// Decides which chunks to strip from an image before publishing it.
+///
+/// By default the policy keeps every public critical chunk (the image can't
+/// be shown without them) and the ancillary chunks that affect rendering,
+/// and strips private chunks, text, Exif and timestamps. Unknown public
+/// ancillary chunks are kept only if they are unsafe to copy, which the spec
+/// uses for chunks that describe the pixels.
+///
+/// `keep` and `strip` override the defaults for ancillary and private chunk
+/// types. Public critical chunks are always kept.
+#[derive(Debug, Clone, Default)]
+pub struct SanitizePolicy {
+    pub keep: HashSet<ChunkType>,
+    pub strip: HashSet<ChunkType>,
+}
+
+impl SanitizePolicy {
+    /// A policy with no overrides.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns why a chunk of this type should be stripped, or `None` to
+    /// keep it.
+    pub fn strip_reason(&self, chunk_type: &ChunkType) -> Option<StripReason> {
+        if chunk_type.is_critical() && chunk_type.is_public() {
+            return None;
+        }
+        if self.strip.contains(chunk_type) {
+            return Some(StripReason::Requested);
+        }
+        if self.keep.contains(chunk_type) {
+            return None;
+        }
+
+        // The APNG chunks were registered after the spec, so their
+        // names still have the private bit set. Check the known lists first.
+        let bytes = chunk_type.bytes();
+        if RENDERING_CHUNKS.contains(&&bytes) {
+            None
+        } else if METADATA_CHUNKS.contains(&&bytes) {
+            Some(StripReason::Metadata)
+        } else if !chunk_type.is_public() {
+            Some(StripReason::Private)
+        } else if !chunk_type.is_safe_to_copy() {
+            None
+        } else {
+            Some(StripReason::UnknownSafeToCopy)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::Png;
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+    const APNG_FILE: &[u8] = include_bytes!("../tests/fixtures/animated.png");
+
+    fn reason(chunk_type: &str) -> Option<StripReason> {
+        SanitizePolicy::new().strip_reason(&ChunkType::from_str(chunk_type).unwrap())
+    }
+
+    #[test]
+    fn test_keeps_public_critical_chunks() {
+        for chunk_type in ["IHDR", "PLTE", "IDAT", "IEND"].iter() {
+            assert_eq!(reason(chunk_type), None, "{}", chunk_type);
+        }
+    }
+
+    #[test]
+    fn test_keeps_rendering_chunks() {
+        for chunk_type in ["gAMA", "sRGB", "pHYs", "tRNS", "iCCP", "fcTL"].iter() {
+            assert_eq!(reason(chunk_type), None, "{}", chunk_type);
+        }
+    }
+
+    #[test]
+    fn test_strips_metadata() {
+        for chunk_type in ["tEXt", "zTXt", "iTXt", "eXIf", "tIME"].iter() {
+            assert_eq!(reason(chunk_type), Some(StripReason::Metadata));
+        }
+    }
+
+    #[test]
+    fn test_strips_private_chunks() {
+        // Private chunks go even when they claim to be critical.
+        for chunk_type in ["ruSt", "RuSt", "prVt"].iter() {
+            assert_eq!(reason(chunk_type), Some(StripReason::Private));
+        }
+    }
+
+    #[test]
+    fn test_unknown_public_chunks() {
+        assert_eq!(reason("aBCD"), None);
+        assert_eq!(reason("aBCd"), Some(StripReason::UnknownSafeToCopy));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut policy = SanitizePolicy::new();
+        policy.keep.insert(ChunkType::from_str("ruSt").unwrap());
+        policy.strip.insert(ChunkType::from_str("gAMA").unwrap());
+        policy.strip.insert(ChunkType::from_str("IDAT").unwrap());
+
+        let reason = |chunk_type| policy.strip_reason(&ChunkType::from_str(chunk_type).unwrap());
+        assert_eq!(reason("ruSt"), None);
+        assert_eq!(reason("gAMA"), Some(StripReason::Requested));
+        assert_eq!(reason("IDAT"), None);
+    }
+
+    #[test]
+    fn test_sanitize_dice() {
+        let mut png = Png::try_from(PNG_FILE).unwrap();
+        let policy = SanitizePolicy::new();
+        let removed = png.retain(|chunk| policy.strip_reason(chunk.chunk_type()).is_none());
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].chunk_type().to_string(), "RuSt");
+        assert_eq!(png.validate(), []);
+    }
+
+    #[test]
+    fn test_sanitize_keeps_animation() {
+        let mut png = Png::try_from(APNG_FILE).unwrap();
+        let policy = SanitizePolicy::new();
+        let removed = png.retain(|chunk| policy.strip_reason(chunk.chunk_type()).is_none());
+
+        assert!(removed.is_empty());
+    }
+}
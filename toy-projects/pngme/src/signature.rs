@@ -0,0 +1,355 @@
+//! Ed25519 signatures over a whole PNG, stored in a chunk of the file they
+//! sign.
+//!
+//! The signature covers the bytes of every other chunk, in file order, as
+//! they are written out: length, type, data and CRC. Adding, removing,
+//! reordering or changing any of them breaks it. The signature chunk itself
+//! can move around without breaking anything.
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::report::DataEncoding;
+use crate::{PngError, Result};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use ed25519_dalek::{Signature, Signer, Verifier, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+// Layout of the signature chunk's data:
+//
+//   | VERSION (1) | public key (32) | signature (64) |
+//
+// The public key is only there so `verify` can tell a file signed by someone
+// else apart from one that was changed. It is never trusted on its own.
+
+/// The chunk type signatures are stored in: ancillary, private and safe to
+/// copy.
+pub const CHUNK_TYPE: &str = "sgNt";
+const VERSION: u8 = 1;
+const LENGTH: usize = 1 + PUBLIC_KEY_LENGTH + SIGNATURE_LENGTH;
+
+/// Signed before the chunk bytes, so a signature over a PNG can't be passed
+/// off as one over anything else.
+const CONTEXT: &[u8] = b"pngme signature v1\0";
+
+/// Generates a new random signing key.
+pub fn generate_key() -> SigningKey {
+    let mut secret = [0; 32];
+    OsRng.fill_bytes(&mut secret);
+    SigningKey::from_bytes(&secret)
+}
+
+/// Signs every chunk of `png` with `key` and stores the signature in a new
+/// chunk just before `IEND`, replacing any earlier signatures.
+pub fn sign(png: &mut Png, key: &SigningKey) -> Result<()> {
+    png.retain(|chunk| !is_signature_chunk(chunk));
+    let signature = key.sign(&signed_bytes(png));
+
+    let mut data = Vec::with_capacity(LENGTH);
+    data.push(VERSION);
+    data.extend_from_slice(key.verifying_key().as_bytes());
+    data.extend_from_slice(&signature.to_bytes());
+    let chunk = Chunk::new(ChunkType::from_str(CHUNK_TYPE)?, data);
+
+    let position = png
+        .chunks()
+        .iter()
+        .position(|chunk| chunk.chunk_type().bytes() == *b"IEND")
+        .unwrap_or_else(|| png.chunks().len());
+    png.insert_chunk_at(position, chunk)
+}
+
+/// Checks that `png` carries a signature made by `key` over exactly the
+/// chunks it holds now.
+pub fn verify(png: &Png, key: &VerifyingKey) -> Result<()> {
+    let mut signatures = png
+        .chunks()
+        .iter()
+        .filter(|chunk| is_signature_chunk(chunk));
+    let chunk = signatures.next().ok_or(PngError::SignatureMissing)?;
+    if signatures.next().is_some() {
+        return Err(invalid("the file has more than one signature".to_string()));
+    }
+
+    let (signer, signature) = parse(chunk.data())?;
+    if signer != *key {
+        return Err(PngError::WrongSigner {
+            signer: to_hex(signer.as_bytes()),
+        });
+    }
+
+    key.verify(&signed_bytes(png), &signature)
+        .map_err(|_| PngError::SignatureMismatch)
+}
+
+/// Returns true for the chunk that holds a signature.
+pub fn is_signature_chunk(chunk: &Chunk) -> bool {
+    chunk.chunk_type().bytes() == *CHUNK_TYPE.as_bytes()
+}
+
+/// Encodes a signing key as hex, the way key files store it.
+pub fn signing_key_to_hex(key: &SigningKey) -> String {
+    to_hex(&key.to_bytes())
+}
+
+/// Encodes a verifying key as hex, the way key files store it.
+pub fn verifying_key_to_hex(key: &VerifyingKey) -> String {
+    to_hex(key.as_bytes())
+}
+
+/// Reads a signing key written by `signing_key_to_hex`. Surrounding
+/// whitespace is ignored.
+pub fn signing_key_from_hex(hex: &str) -> Result<SigningKey> {
+    Ok(SigningKey::from_bytes(&key_bytes(hex)?))
+}
+
+/// Reads a verifying key written by `verifying_key_to_hex`. Surrounding
+/// whitespace is ignored.
+pub fn verifying_key_from_hex(hex: &str) -> Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&key_bytes(hex)?).map_err(|_| PngError::InvalidKey {
+        reason: "not a valid Ed25519 public key".to_string(),
+    })
+}
+
+/// The bytes a signature covers: every chunk but the signature, in order.
+fn signed_bytes(png: &Png) -> Vec<u8> {
+    let mut bytes = CONTEXT.to_vec();
+    for chunk in png.chunks() {
+        if !is_signature_chunk(chunk) {
+            bytes.extend(chunk.as_bytes());
+        }
+    }
+
+    bytes
+}
+
+fn parse(data: &[u8]) -> Result<(VerifyingKey, Signature)> {
+    if data.len() != LENGTH {
+        return Err(invalid(format!(
+            "expected {} bytes of data but found {}",
+            LENGTH,
+            data.len()
+        )));
+    }
+    if data[0] != VERSION {
+        return Err(invalid(format!("unknown signature version {}", data[0])));
+    }
+
+    let (key, signature) = data[1..].split_at(PUBLIC_KEY_LENGTH);
+    let key = <[u8; PUBLIC_KEY_LENGTH]>::try_from(key).unwrap();
+    let key = VerifyingKey::from_bytes(&key)
+        .map_err(|_| invalid("the public key is not valid".to_string()))?;
+    let signature = Signature::from_slice(signature).unwrap();
+
+    Ok((key, signature))
+}
+
+fn key_bytes(hex: &str) -> Result<[u8; 32]> {
+    let invalid = |reason: &str| PngError::InvalidKey {
+        reason: reason.to_string(),
+    };
+
+    let hex = hex.trim();
+    if hex.len() != 64 {
+        return Err(invalid("expected 64 hex digits"));
+    }
+
+    let mut bytes = [0; 32];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid("not hex"))?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid("not hex"))?;
+    }
+
+    Ok(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    DataEncoding::Hex.encode(bytes)
+}
+
+fn invalid(reason: String) -> PngError {
+    PngError::InvalidChunkData {
+        chunk_type: CHUNK_TYPE.to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    fn key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn signed() -> Png {
+        let mut png = Png::try_from(PNG_FILE).unwrap();
+        sign(&mut png, &key()).unwrap();
+        png
+    }
+
+    #[test]
+    fn test_chunk_type_is_private_and_safe_to_copy() {
+        let chunk_type = ChunkType::from_str(CHUNK_TYPE).unwrap();
+
+        assert!(!chunk_type.is_critical());
+        assert!(!chunk_type.is_public());
+        assert!(chunk_type.is_safe_to_copy());
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let png = signed();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+
+        assert_eq!(types[types.len() - 2..], ["sgNt", "IEND"]);
+        assert!(verify(&png, &key().verifying_key()).is_ok());
+    }
+
+    #[test]
+    fn test_survives_round_trip() {
+        let png = Png::try_from(signed().as_bytes().as_slice()).unwrap();
+        assert!(verify(&png, &key().verifying_key()).is_ok());
+    }
+
+    #[test]
+    fn test_resigning_replaces_signature() {
+        let mut png = signed();
+        sign(&mut png, &key()).unwrap();
+
+        assert_eq!(
+            png.chunks()
+                .iter()
+                .filter(|c| is_signature_chunk(c))
+                .count(),
+            1
+        );
+        assert!(verify(&png, &key().verifying_key()).is_ok());
+    }
+
+    #[test]
+    fn test_added_chunk_fails() {
+        let mut png = signed();
+        png.insert_chunk_at(1, Chunk::of_type("ruSt", b"hi"))
+            .unwrap();
+
+        assert!(matches!(
+            verify(&png, &key().verifying_key()),
+            Err(PngError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_removed_chunk_fails() {
+        let mut png = signed();
+        png.remove_chunk("RuSt").unwrap();
+
+        assert!(matches!(
+            verify(&png, &key().verifying_key()),
+            Err(PngError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_changed_chunk_fails() {
+        let mut png = signed();
+        png.replace_chunk("RuSt", Chunk::of_type("RuSt", b"bye"))
+            .unwrap();
+
+        assert!(matches!(
+            verify(&png, &key().verifying_key()),
+            Err(PngError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_moving_signature_is_fine() {
+        let mut png = signed();
+        let position = png.chunks().iter().position(is_signature_chunk).unwrap();
+        let signature = png.retain(|chunk| !is_signature_chunk(chunk)).remove(0);
+        assert!(position > 1);
+        png.insert_chunk_at(1, signature).unwrap();
+
+        assert!(verify(&png, &key().verifying_key()).is_ok());
+    }
+
+    #[test]
+    fn test_other_key_fails() {
+        let other = SigningKey::from_bytes(&[8; 32]);
+
+        assert!(matches!(
+            verify(&signed(), &other.verifying_key()),
+            Err(PngError::WrongSigner { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unsigned_fails() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+
+        assert!(matches!(
+            verify(&png, &key().verifying_key()),
+            Err(PngError::SignatureMissing)
+        ));
+    }
+
+    #[test]
+    fn test_two_signatures_fail() {
+        let mut png = signed();
+        let signature = png.chunk_by_type(CHUNK_TYPE).unwrap().clone();
+        png.insert_chunk_at(1, signature).unwrap();
+
+        assert!(verify(&png, &key().verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_malformed_signature_chunk() {
+        let mut png = signed();
+        png.replace_chunk(CHUNK_TYPE, Chunk::of_type(CHUNK_TYPE, [1; 10]))
+            .unwrap();
+
+        assert!(matches!(
+            verify(&png, &key().verifying_key()),
+            Err(PngError::InvalidChunkData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_key_hex_round_trip() {
+        let key = generate_key();
+        let secret = signing_key_to_hex(&key);
+        let public = verifying_key_to_hex(&key.verifying_key());
+
+        assert_eq!(secret.len(), 64);
+        assert_eq!(
+            signing_key_from_hex(&format!("{}\n", secret))
+                .unwrap()
+                .to_bytes(),
+            key.to_bytes()
+        );
+        assert_eq!(
+            verifying_key_from_hex(&public).unwrap(),
+            key.verifying_key()
+        );
+    }
+
+    #[test]
+    fn test_bad_key_hex() {
+        assert!(signing_key_from_hex("abcd").is_err());
+        assert!(signing_key_from_hex(&"zz".repeat(32)).is_err());
+        assert!(matches!(
+            verifying_key_from_hex(&"é".repeat(32)),
+            Err(PngError::InvalidKey { .. })
+        ));
+    }
+}
@@ -0,0 +1,242 @@
+//! Hiding messages in the least significant bits of the pixels.
+
+use crate::ihdr::{ColorType, ImageHeader};
+use crate::pixels::{image_too_large, PixelData};
+use crate::{PngError, Result};
+use std::convert::TryInto;
+
+// A hidden message is spread one bit per sample over the least significant
+// bits of the pixel samples, most significant bit of each byte first:
+//
+//   | MAGIC (4) | length (4, big endian) | message |
+//
+// For 16-bit images only the low byte of each sample is touched. Indexed
+// images are refused, because flipping the low bit of a palette index can
+// pick a completely different colour.
+
+const MAGIC: &[u8; 4] = b"pmLS";
+const HEADER_LEN: usize = MAGIC.len() + 4;
+
+/// Number of message bytes that can be hidden in an image with this header.
+pub fn capacity(header: &ImageHeader) -> Result<usize> {
+    Ok((sample_count(header)? / 8).saturating_sub(HEADER_LEN))
+}
+
+/// Hides `message` in the low bits of `pixels`.
+pub fn embed(pixels: &mut PixelData, message: &[u8]) -> Result<()> {
+    let capacity = capacity(pixels.header())?;
+    if message.len() > capacity {
+        return Err(PngError::MessageTooLarge {
+            size: message.len(),
+            capacity,
+        });
+    }
+
+    let length = (message.len() as u32).to_be_bytes();
+    let payload = MAGIC.iter().chain(length.iter()).chain(message);
+    let bits = payload.flat_map(|byte| (0..8).rev().map(move |bit| (byte >> bit) & 1));
+
+    let step = sample_step(pixels.header());
+    for (sample, bit) in samples_mut(pixels.data_mut(), step).zip(bits) {
+        *sample = (*sample & !1) | bit;
+    }
+
+    Ok(())
+}
+
+/// Reads back a message hidden by `embed`.
+pub fn extract(pixels: &PixelData) -> Result<Vec<u8>> {
+    let capacity = capacity(pixels.header())?;
+    let step = sample_step(pixels.header());
+    let mut bytes = samples(pixels.data(), step)
+        .collect::<Vec<_>>()
+        .chunks_exact(8)
+        .map(|bits| {
+            bits.iter()
+                .fold(0, |byte, sample| (byte << 1) | (sample & 1))
+        })
+        .take(HEADER_LEN + capacity)
+        .collect::<Vec<u8>>();
+
+    if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(PngError::NoHiddenMessage);
+    }
+
+    let length = u32::from_be_bytes(bytes[MAGIC.len()..HEADER_LEN].try_into().unwrap()) as usize;
+    if length > capacity {
+        return Err(PngError::NoHiddenMessage);
+    }
+
+    bytes.truncate(HEADER_LEN + length);
+    Ok(bytes.split_off(HEADER_LEN))
+}
+
+fn sample_count(header: &ImageHeader) -> Result<usize> {
+    if header.color_type == ColorType::Indexed || header.bit_depth < 8 {
+        return Err(PngError::UnsupportedImage {
+            reason: format!(
+                "can't hide a message in {}-bit {} pixels",
+                header.bit_depth, header.color_type
+            ),
+        });
+    }
+
+    (header.width as usize)
+        .checked_mul(header.height as usize)
+        .and_then(|count| count.checked_mul(header.color_type.channels()))
+        .ok_or_else(image_too_large)
+}
+
+/// Bytes between the low bytes of two samples.
+fn sample_step(header: &ImageHeader) -> usize {
+    header.bit_depth as usize / 8
+}
+
+/// The low byte of every sample. Samples are big endian, so for 16-bit
+/// images that is the second byte of each pair.
+fn samples(data: &[u8], step: usize) -> impl Iterator<Item = u8> + '_ {
+    data.iter().skip(step - 1).step_by(step).copied()
+}
+
+fn samples_mut(data: &mut [u8], step: usize) -> impl Iterator<Item = &mut u8> {
+    data.iter_mut().skip(step - 1).step_by(step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::InterlaceMethod;
+    use crate::png::Png;
+    use std::convert::TryFrom;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    fn dice() -> PixelData {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        PixelData::decode(png.header().unwrap(), &png.image_data()).unwrap()
+    }
+
+    fn header(bit_depth: u8, color_type: ColorType) -> ImageHeader {
+        ImageHeader {
+            width: 10,
+            height: 10,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    #[test]
+    fn test_capacity() {
+        // 50x50 RGBA: 10000 samples, 1250 bytes, minus the 8 byte header.
+        assert_eq!(capacity(dice().header()).unwrap(), 1242);
+        assert_eq!(capacity(&header(8, ColorType::Grayscale)).unwrap(), 4);
+        assert_eq!(capacity(&header(16, ColorType::Rgb)).unwrap(), 29);
+    }
+
+    #[test]
+    fn test_capacity_of_tiny_image() {
+        let header = ImageHeader {
+            width: 1,
+            height: 1,
+            ..header(8, ColorType::Rgba)
+        };
+        assert_eq!(capacity(&header).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_unsupported_images() {
+        for &(bit_depth, color_type) in [(8, ColorType::Indexed), (4, ColorType::Grayscale)].iter()
+        {
+            assert!(matches!(
+                capacity(&header(bit_depth, color_type)),
+                Err(PngError::UnsupportedImage { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut pixels = dice();
+        embed(&mut pixels, b"This is a secret message!").unwrap();
+
+        assert_eq!(extract(&pixels).unwrap(), b"This is a secret message!");
+    }
+
+    #[test]
+    fn test_round_trip_through_idat() {
+        let mut pixels = dice();
+        embed(&mut pixels, b"This is a secret message!").unwrap();
+        let pixels = PixelData::decode(*pixels.header(), &pixels.encode().unwrap()).unwrap();
+
+        assert_eq!(extract(&pixels).unwrap(), b"This is a secret message!");
+    }
+
+    #[test]
+    fn test_full_capacity_round_trip() {
+        let mut pixels = dice();
+        let message: Vec<u8> = (0..1242).map(|i| i as u8).collect();
+        embed(&mut pixels, &message).unwrap();
+
+        assert_eq!(extract(&pixels).unwrap(), message);
+    }
+
+    #[test]
+    fn test_only_low_bits_change() {
+        let original = dice();
+        let mut pixels = original.clone();
+        embed(&mut pixels, &[0xff; 100]).unwrap();
+
+        assert!(original
+            .data()
+            .iter()
+            .zip(pixels.data())
+            .all(|(before, after)| before | 1 == after | 1));
+        assert_ne!(original.data(), pixels.data());
+    }
+
+    #[test]
+    fn test_16_bit_only_touches_low_bytes() {
+        let header = header(16, ColorType::Rgb);
+        let data = vec![0xaa; 10 * 10 * 3 * 2];
+        let mut pixels = PixelData::decode(header, &encode_raw(&data, 10 * 3 * 2)).unwrap();
+        embed(&mut pixels, b"16 bits").unwrap();
+
+        assert_eq!(extract(&pixels).unwrap(), b"16 bits");
+        assert!(pixels.data().iter().step_by(2).all(|byte| *byte == 0xaa));
+    }
+
+    #[test]
+    fn test_message_too_large() {
+        let mut pixels = dice();
+
+        assert!(matches!(
+            embed(&mut pixels, &[0; 1243]),
+            Err(PngError::MessageTooLarge {
+                size: 1243,
+                capacity: 1242
+            })
+        ));
+    }
+
+    #[test]
+    fn test_no_hidden_message() {
+        assert!(matches!(extract(&dice()), Err(PngError::NoHiddenMessage)));
+    }
+
+    /// Deflates `data` as rows of `stride` bytes with no filtering.
+    fn encode_raw(data: &[u8], stride: usize) -> Vec<u8> {
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in data.chunks(stride) {
+            encoder.write_all(&[0]).unwrap();
+            encoder.write_all(row).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+}
@@ -0,0 +1,284 @@
+//! Reading and writing PNG files one chunk at a time.
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+use crate::{PngError, Result};
+use std::convert::TryFrom;
+use std::io::{ErrorKind, Read, Write};
+
+/// Reads a PNG one chunk at a time, so only a single chunk has to be held in
+/// memory.
+///
+/// The signature is checked by `PngReader::new`, and the chunks are yielded
+/// by iterating over the reader. Iteration stops after the first error.
+pub struct PngReader<R: Read> {
+    reader: R,
+    index: usize,
+    offset: usize,
+    done: bool,
+}
+
+impl<R: Read> PngReader<R> {
+    /// Reads and checks the signature, leaving `reader` at the first chunk.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = Vec::with_capacity(Png::STANDARD_HEADER.len());
+        reader
+            .by_ref()
+            .take(Png::STANDARD_HEADER.len() as u64)
+            .read_to_end(&mut header)?;
+
+        if header != Png::STANDARD_HEADER {
+            return Err(PngError::InvalidSignature { found: header });
+        }
+
+        Ok(Self {
+            reader,
+            index: 0,
+            offset: Png::STANDARD_HEADER.len(),
+            done: false,
+        })
+    }
+
+    /// Byte offset in the file of the chunk the next call to `next` reads.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Reads the next chunk, or returns `None` if the input ends cleanly
+    /// between two chunks.
+    fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        let mut length_bytes = [0; 4];
+        let read = read_full(&mut self.reader, &mut length_bytes)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < length_bytes.len() {
+            return Err(self.truncated(length_bytes.len(), read));
+        }
+
+        let chunk_size =
+            (u32::from_be_bytes(length_bytes) as usize).saturating_add(Chunk::OVERHEAD);
+
+        // Don't trust the length field with the allocation: `take` makes
+        // `read_to_end` grow the buffer only as bytes actually arrive.
+        let mut chunk_bytes = length_bytes.to_vec();
+        self.reader
+            .by_ref()
+            .take((chunk_size - length_bytes.len()) as u64)
+            .read_to_end(&mut chunk_bytes)?;
+        if chunk_bytes.len() < chunk_size {
+            return Err(self.truncated(chunk_size, chunk_bytes.len()));
+        }
+
+        let chunk = Chunk::try_from(chunk_bytes.as_ref())
+            .map_err(|err| err.at_chunk(self.index, self.offset))?;
+
+        self.index += 1;
+        self.offset += chunk_size;
+
+        Ok(Some(chunk))
+    }
+
+    fn truncated(&self, needed: usize, available: usize) -> PngError {
+        PngError::TruncatedChunk {
+            index: Some(self.index),
+            offset: self.offset,
+            needed,
+            available,
+        }
+    }
+}
+
+impl<R: Read> Iterator for PngReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let chunk = self.read_chunk().transpose();
+        if !matches!(chunk, Some(Ok(_))) {
+            self.done = true;
+        }
+
+        chunk
+    }
+}
+
+/// Like `Read::read_exact`, but returns how many bytes were read instead of
+/// failing when the input ends early.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(read)
+}
+
+/// Writes a PNG one chunk at a time. The signature is written by
+/// `PngWriter::new`.
+pub struct PngWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PngWriter<W> {
+    /// Writes the signature, ready for the first chunk.
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(Png::STANDARD_HEADER)?;
+        Ok(Self { writer })
+    }
+
+    /// Writes one chunk in full.
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        self.writer.write_all(&chunk.as_bytes())?;
+        Ok(())
+    }
+
+    /// Flushes the writer and hands it back.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use std::str::FromStr;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    #[test]
+    fn test_read_chunks() {
+        let reader = PngReader::new(PNG_FILE).unwrap();
+        let chunk_types: Vec<String> = reader
+            .map(|chunk| chunk.unwrap().chunk_type().to_string())
+            .collect();
+
+        assert_eq!(
+            chunk_types,
+            ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "RuSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_read_matches_png() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        let chunks: Vec<Chunk> = PngReader::new(PNG_FILE)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(png.as_bytes(), Png::from_chunks(chunks).as_bytes());
+    }
+
+    #[test]
+    fn test_read_offsets() {
+        let mut reader = PngReader::new(PNG_FILE).unwrap();
+        assert_eq!(reader.offset(), Png::STANDARD_HEADER.len());
+
+        let header = reader.next().unwrap().unwrap();
+        assert_eq!(
+            reader.offset(),
+            Png::STANDARD_HEADER.len() + Chunk::OVERHEAD + header.length() as usize
+        );
+    }
+
+    #[test]
+    fn test_read_invalid_signature() {
+        let reader = PngReader::new(&PNG_FILE[1..]);
+        assert!(matches!(reader, Err(PngError::InvalidSignature { .. })));
+    }
+
+    #[test]
+    fn test_read_truncated_chunk() {
+        let mut reader = PngReader::new(&PNG_FILE[..PNG_FILE.len() - 6]).unwrap();
+
+        let error = reader.find_map(|chunk| chunk.err());
+        assert!(matches!(
+            error,
+            Some(PngError::TruncatedChunk {
+                index: Some(6),
+                offset: 4791,
+                needed: 12,
+                available: 6,
+            })
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_read_truncated_length() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(&[0, 0]);
+
+        let error = PngReader::new(bytes.as_slice())
+            .unwrap()
+            .find_map(|chunk| chunk.err());
+        assert!(matches!(
+            error,
+            Some(PngError::TruncatedChunk {
+                index: Some(7),
+                needed: 4,
+                available: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        for chunk in PngReader::new(PNG_FILE).unwrap() {
+            writer.write_chunk(&chunk.unwrap()).unwrap();
+        }
+
+        assert_eq!(writer.finish().unwrap(), PNG_FILE);
+    }
+
+    #[test]
+    fn test_write_chunk() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunk = Chunk::new(chunk_type, b"Message".to_vec());
+
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        writer.write_chunk(&chunk).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(png.chunks().len(), 1);
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(),
+            "Message"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_reader_agrees_with_png(bytes in vec(any::<u8>(), 0..256), signed: bool) {
+            let bytes: Vec<u8> = if signed {
+                Png::STANDARD_HEADER.iter().chain(&bytes).copied().collect()
+            } else {
+                bytes
+            };
+
+            let read = PngReader::new(bytes.as_slice())
+                .and_then(|reader| reader.collect::<Result<Vec<Chunk>>>());
+            let parsed = Png::try_from(bytes.as_slice());
+            match (read, parsed) {
+                (Ok(chunks), Ok(png)) => prop_assert_eq!(chunks.as_slice(), png.chunks()),
+                (read, parsed) => prop_assert_eq!(read.is_ok(), parsed.is_ok()),
+            }
+        }
+    }
+}
@@ -0,0 +1,415 @@
+//! The textual metadata chunks: `tEXt`, `zTXt` and `iTXt`.
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::{PngError, Result};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Read, Write};
+
+/// The most bytes compressed text, or an `iCCP` profile, may inflate to.
+pub(crate) const MAX_TEXT_LEN: usize = 16 * 1024 * 1024;
+
+/// Which of the three textual chunk types an entry is stored in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextEncoding {
+    /// `tEXt`: uncompressed Latin-1.
+    Plain,
+    /// `zTXt`: deflate-compressed Latin-1.
+    Compressed,
+    /// `iTXt`: UTF-8, optionally compressed, with an optional language tag
+    /// and a translation of the keyword into that language.
+    International {
+        compressed: bool,
+        language_tag: String,
+        translated_keyword: String,
+    },
+}
+
+/// A keyword/value pair stored in a `tEXt`, `zTXt` or `iTXt` chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEntry {
+    pub keyword: String,
+    pub text: String,
+    pub encoding: TextEncoding,
+}
+
+impl TextEntry {
+    /// Creates an entry stored as `tEXt` if the text fits in Latin-1, and as
+    /// `iTXt` otherwise.
+    pub fn new(keyword: &str, text: &str) -> Self {
+        let encoding = if is_latin1(text) {
+            TextEncoding::Plain
+        } else {
+            TextEncoding::International {
+                compressed: false,
+                language_tag: String::new(),
+                translated_keyword: String::new(),
+            }
+        };
+
+        Self {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            encoding,
+        }
+    }
+
+    /// Returns true for the chunk types that hold textual data.
+    pub fn is_text_chunk(chunk_type: &ChunkType) -> bool {
+        matches!(&chunk_type.bytes(), b"tEXt" | b"zTXt" | b"iTXt")
+    }
+
+    /// The chunk type this is stored in, which depends on the encoding.
+    pub fn chunk_type(&self) -> ChunkType {
+        let bytes = match self.encoding {
+            TextEncoding::Plain => *b"tEXt",
+            TextEncoding::Compressed => *b"zTXt",
+            TextEncoding::International { .. } => *b"iTXt",
+        };
+
+        ChunkType::try_from(bytes).unwrap()
+    }
+
+    /// Encodes this as a chunk, checking the keyword and compressing the text
+    /// if needed.
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let chunk_type = self.chunk_type();
+        let invalid = |reason: String| PngError::InvalidChunkData {
+            chunk_type: chunk_type.to_string(),
+            reason,
+        };
+
+        check_keyword(&self.keyword).map_err(invalid)?;
+        let mut data = to_latin1(&self.keyword).unwrap();
+        data.push(0);
+
+        match &self.encoding {
+            TextEncoding::Plain | TextEncoding::Compressed => {
+                let text = to_latin1(&self.text)
+                    .ok_or_else(|| invalid("text is not Latin-1".to_string()))?;
+
+                if self.encoding == TextEncoding::Compressed {
+                    data.push(0);
+                    data.extend(compress(&text)?);
+                } else {
+                    data.extend(text);
+                }
+            }
+            TextEncoding::International {
+                compressed,
+                language_tag,
+                translated_keyword,
+            } => {
+                if !language_tag.is_ascii() {
+                    return Err(invalid("language tag is not ASCII".to_string()));
+                }
+
+                data.extend(&[*compressed as u8, 0]);
+                data.extend(language_tag.as_bytes());
+                data.push(0);
+                data.extend(translated_keyword.as_bytes());
+                data.push(0);
+
+                if *compressed {
+                    data.extend(compress(self.text.as_bytes())?);
+                } else {
+                    data.extend(self.text.as_bytes());
+                }
+            }
+        }
+
+        Ok(Chunk::new(chunk_type, data))
+    }
+}
+
+impl TryFrom<&Chunk> for TextEntry {
+    type Error = PngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let invalid = |reason: &str| PngError::InvalidChunkData {
+            chunk_type: chunk.chunk_type().to_string(),
+            reason: reason.to_string(),
+        };
+
+        let (keyword, rest) =
+            split_at_nul(chunk.data()).ok_or_else(|| invalid("keyword is not terminated"))?;
+        let keyword = from_latin1(keyword);
+        check_keyword(&keyword).map_err(|reason| invalid(&reason))?;
+
+        let (text, encoding) = match &chunk.chunk_type().bytes() {
+            b"tEXt" => (from_latin1(rest), TextEncoding::Plain),
+            b"zTXt" => {
+                let (method, compressed) = rest
+                    .split_first()
+                    .ok_or_else(|| invalid("missing compression method"))?;
+                if *method != 0 {
+                    return Err(invalid("unknown compression method"));
+                }
+
+                let text = decompress(compressed).map_err(invalid)?;
+                (from_latin1(&text), TextEncoding::Compressed)
+            }
+            b"iTXt" => {
+                let (flag, method, rest) = match rest {
+                    [flag, method, rest @ ..] => (*flag, *method, rest),
+                    _ => return Err(invalid("missing compression flag")),
+                };
+                let (language_tag, rest) =
+                    split_at_nul(rest).ok_or_else(|| invalid("language tag is not terminated"))?;
+                let (translated_keyword, text) = split_at_nul(rest)
+                    .ok_or_else(|| invalid("translated keyword is not terminated"))?;
+
+                let compressed = match (flag, method) {
+                    (0, _) => false,
+                    (1, 0) => true,
+                    _ => return Err(invalid("unknown compression flag or method")),
+                };
+                let text = if compressed {
+                    decompress(text).map_err(invalid)?
+                } else {
+                    text.to_vec()
+                };
+
+                let utf8 = |bytes: Vec<u8>| {
+                    String::from_utf8(bytes).map_err(|_| invalid("text is not UTF-8"))
+                };
+                let encoding = TextEncoding::International {
+                    compressed,
+                    language_tag: utf8(language_tag.to_vec())?,
+                    translated_keyword: utf8(translated_keyword.to_vec())?,
+                };
+
+                (utf8(text)?, encoding)
+            }
+            _ => return Err(invalid("not a textual chunk")),
+        };
+
+        Ok(Self {
+            keyword,
+            text,
+            encoding,
+        })
+    }
+}
+
+impl fmt::Display for TextEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.keyword, self.chunk_type(), self.text)
+    }
+}
+
+/// Keywords are 1-79 printable Latin-1 characters with no leading, trailing
+/// or consecutive spaces.
+pub(crate) fn check_keyword(keyword: &str) -> std::result::Result<(), String> {
+    let len = keyword.chars().count();
+    if len == 0 || len > 79 {
+        return Err(format!("keyword must be 1-79 characters, not {}", len));
+    }
+
+    let printable = |c: char| matches!(c as u32, 32..=126 | 161..=255);
+    if !keyword.chars().all(printable) {
+        return Err(format!("keyword {:?} is not printable Latin-1", keyword));
+    }
+
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return Err(format!("keyword {:?} has extra spaces", keyword));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn split_at_nul(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let nul = bytes.iter().position(|&b| b == 0)?;
+    Some((&bytes[..nul], &bytes[nul + 1..]))
+}
+
+fn is_latin1(s: &str) -> bool {
+    s.chars().all(|c| (c as u32) <= 0xFF)
+}
+
+/// Latin-1 maps each byte straight to the code point with the same value.
+pub(crate) fn from_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+pub(crate) fn to_latin1(s: &str) -> Option<Vec<u8>> {
+    if is_latin1(s) {
+        Some(s.chars().map(|c| c as u8).collect())
+    } else {
+        None
+    }
+}
+
+pub(crate) fn compress(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+/// Inflates compressed chunk data, or says why it can't. Anything that
+/// inflates to more than `MAX_TEXT_LEN` bytes is refused, so a tiny zlib
+/// bomb can't make us allocate gigabytes.
+pub(crate) fn decompress(bytes: &[u8]) -> std::result::Result<Vec<u8>, &'static str> {
+    let mut text = Vec::new();
+    ZlibDecoder::new(bytes)
+        .take(MAX_TEXT_LEN as u64 + 1)
+        .read_to_end(&mut text)
+        .map_err(|_| "corrupt compressed data")?;
+    if text.len() > MAX_TEXT_LEN {
+        return Err("decompressed text too large");
+    }
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn international(compressed: bool) -> TextEntry {
+        TextEntry {
+            keyword: "Title".to_string(),
+            text: "Dadu yang cantik 🎲".to_string(),
+            encoding: TextEncoding::International {
+                compressed,
+                language_tag: "ms".to_string(),
+                translated_keyword: "Tajuk".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let entry = TextEntry::try_from(&Chunk::of_type("tEXt", b"Author\0Caf\xe9")).unwrap();
+
+        assert_eq!(entry.keyword, "Author");
+        assert_eq!(entry.text, "Café");
+        assert_eq!(entry.encoding, TextEncoding::Plain);
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let entry = TextEntry::new("Comment", "Café au lait");
+        let chunk = entry.to_chunk().unwrap();
+
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(chunk.data(), b"Comment\0Caf\xe9 au lait");
+        assert_eq!(TextEntry::try_from(&chunk).unwrap(), entry);
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let entry = TextEntry {
+            keyword: "Description".to_string(),
+            text: "la ".repeat(100),
+            encoding: TextEncoding::Compressed,
+        };
+        let chunk = entry.to_chunk().unwrap();
+
+        assert_eq!(chunk.chunk_type().to_string(), "zTXt");
+        assert!(chunk.data().len() < 100);
+        assert_eq!(TextEntry::try_from(&chunk).unwrap(), entry);
+    }
+
+    #[test]
+    fn test_international_round_trip() {
+        for &compressed in [false, true].iter() {
+            let entry = international(compressed);
+            let chunk = entry.to_chunk().unwrap();
+
+            assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+            assert_eq!(TextEntry::try_from(&chunk).unwrap(), entry);
+        }
+    }
+
+    #[test]
+    fn test_parse_international() {
+        let data = b"Title\0\0\0ms\0Tajuk\0Dadu";
+        let entry = TextEntry::try_from(&Chunk::of_type("iTXt", data)).unwrap();
+
+        assert_eq!(entry.keyword, "Title");
+        assert_eq!(entry.text, "Dadu");
+        assert_eq!(
+            entry.encoding,
+            TextEncoding::International {
+                compressed: false,
+                language_tag: "ms".to_string(),
+                translated_keyword: "Tajuk".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_new_picks_international_for_non_latin1() {
+        let entry = TextEntry::new("Title", "🎲");
+        assert_eq!(entry.chunk_type().to_string(), "iTXt");
+    }
+
+    #[test]
+    fn test_latin1_required_for_plain_text() {
+        let entry = TextEntry {
+            keyword: "Title".to_string(),
+            text: "🎲".to_string(),
+            encoding: TextEncoding::Plain,
+        };
+        assert!(entry.to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_invalid_keywords() {
+        let long = "k".repeat(80);
+        for keyword in ["", " Title", "Title ", "Two  spaces", "Tab\t", &long].iter() {
+            let entry = TextEntry::new(keyword, "text");
+            assert!(entry.to_chunk().is_err(), "{:?}", keyword);
+        }
+    }
+
+    #[test]
+    fn test_invalid_chunks() {
+        let invalid: [(&str, &[u8]); 6] = [
+            ("tEXt", b"no terminator"),
+            ("zTXt", b"Title\0"),
+            ("zTXt", b"Title\0\x01data"),
+            ("zTXt", b"Title\0\0not deflate"),
+            ("iTXt", b"Title\0\0\0ms"),
+            ("iTXt", b"Title\0\0\0\0\0\xff"),
+        ];
+
+        for (chunk_type, data) in invalid.iter() {
+            let entry = TextEntry::try_from(&Chunk::of_type(chunk_type, *data));
+            assert!(
+                matches!(entry, Err(PngError::InvalidChunkData { .. })),
+                "{} {:?}",
+                chunk_type,
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompressed_text_too_large() {
+        let bomb = compress(&vec![b'a'; MAX_TEXT_LEN + 1]).unwrap();
+        let mut data = b"Title\0\0".to_vec();
+        data.extend(&bomb);
+
+        assert_eq!(
+            TextEntry::try_from(&Chunk::of_type("zTXt", data.as_slice()))
+                .unwrap_err()
+                .to_string(),
+            "Invalid zTXt chunk: decompressed text too large"
+        );
+
+        let exactly = compress(&vec![b'a'; MAX_TEXT_LEN]).unwrap();
+        assert_eq!(decompress(&exactly).unwrap().len(), MAX_TEXT_LEN);
+    }
+
+    #[test]
+    fn test_not_a_text_chunk() {
+        let entry = TextEntry::try_from(&Chunk::of_type("ruSt", b"Title\0text"));
+        assert!(entry.is_err());
+    }
+}
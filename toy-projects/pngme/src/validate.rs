@@ -0,0 +1,304 @@
+//! Checking a PNG against the spec's rules on chunk ordering.
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::{ColorType, ImageHeader};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Chunks that may appear at most once in a PNG.
+const SINGLETON_CHUNKS: [&[u8; 4]; 15] = [
+    b"IHDR", b"PLTE", b"IEND", b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"bKGD", b"hIST",
+    b"tRNS", b"pHYs", b"tIME", b"eXIf", b"acTL",
+];
+
+/// A way in which a PNG breaks the spec's rules on which chunks must appear
+/// and in what order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// A chunk the file must contain is missing.
+    MissingChunk { chunk_type: &'static str },
+    /// The first chunk isn't `IHDR`.
+    FirstChunkNotIhdr { chunk_type: ChunkType },
+    /// The `IHDR` chunk can't be decoded.
+    InvalidHeader { reason: String },
+    /// A chunk comes after `IEND`.
+    ChunkAfterIend { index: usize, chunk_type: ChunkType },
+    /// An `IDAT` chunk is separated from the previous `IDAT` by other chunks.
+    IdatNotConsecutive { index: usize },
+    /// A `PLTE` chunk comes after the image data.
+    PlteAfterIdat { index: usize },
+    /// A chunk that may only appear once appears again.
+    DuplicateChunk { index: usize, chunk_type: ChunkType },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::MissingChunk { chunk_type } => write!(f, "Missing {} chunk", chunk_type),
+            Violation::FirstChunkNotIhdr { chunk_type } => {
+                write!(f, "First chunk is {} instead of IHDR", chunk_type)
+            }
+            Violation::InvalidHeader { reason } => write!(f, "Invalid IHDR chunk: {}", reason),
+            Violation::ChunkAfterIend { index, chunk_type } => {
+                write!(f, "Chunk {} ({}) comes after IEND", index, chunk_type)
+            }
+            Violation::IdatNotConsecutive { index } => {
+                write!(f, "Chunk {} (IDAT) is not next to the previous IDAT", index)
+            }
+            Violation::PlteAfterIdat { index } => {
+                write!(f, "Chunk {} (PLTE) comes after IDAT", index)
+            }
+            Violation::DuplicateChunk { index, chunk_type } => {
+                write!(f, "Chunk {} ({}) appears more than once", index, chunk_type)
+            }
+        }
+    }
+}
+
+/// Checks chunk ordering as the chunks go by, so a file can be validated
+/// while it is streamed.
+///
+/// Feed it every chunk in file order with `check`, then call `finish` to get
+/// every violation that was found.
+#[derive(Default)]
+pub struct Validator {
+    index: usize,
+    violations: Vec<Violation>,
+    seen: HashSet<ChunkType>,
+    header: Option<ImageHeader>,
+    previous_was_idat: bool,
+    seen_iend: bool,
+}
+
+impl Validator {
+    /// A validator that hasn't seen any chunks yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks the next chunk in the file.
+    pub fn check(&mut self, chunk: &Chunk) {
+        let index = self.index;
+        let chunk_type = *chunk.chunk_type();
+        let bytes = chunk_type.bytes();
+        self.index += 1;
+
+        if index == 0 {
+            if &bytes == b"IHDR" {
+                match ImageHeader::try_from(chunk) {
+                    Ok(header) => self.header = Some(header),
+                    Err(err) => self.violations.push(Violation::InvalidHeader {
+                        reason: err.to_string(),
+                    }),
+                }
+            } else {
+                self.violations
+                    .push(Violation::FirstChunkNotIhdr { chunk_type });
+            }
+        }
+
+        if self.seen_iend {
+            self.violations
+                .push(Violation::ChunkAfterIend { index, chunk_type });
+        }
+
+        match &bytes {
+            b"IDAT" if self.seen.contains(&chunk_type) && !self.previous_was_idat => {
+                self.violations
+                    .push(Violation::IdatNotConsecutive { index });
+            }
+            b"PLTE" if self.seen_type(b"IDAT") => {
+                self.violations.push(Violation::PlteAfterIdat { index });
+            }
+            b"IEND" => self.seen_iend = true,
+            _ => {}
+        }
+
+        if SINGLETON_CHUNKS.contains(&&bytes) && self.seen.contains(&chunk_type) {
+            self.violations
+                .push(Violation::DuplicateChunk { index, chunk_type });
+        }
+
+        self.previous_was_idat = &bytes == b"IDAT";
+        self.seen.insert(chunk_type);
+    }
+
+    /// Runs the checks that need the whole file and returns every violation.
+    pub fn finish(mut self) -> Vec<Violation> {
+        // An empty file has no first chunk to complain about.
+        if self.index == 0 {
+            self.violations
+                .push(Violation::MissingChunk { chunk_type: "IHDR" });
+        }
+
+        let indexed =
+            matches!(self.header, Some(header) if header.color_type == ColorType::Indexed);
+        if indexed && !self.seen_type(b"PLTE") {
+            self.violations
+                .push(Violation::MissingChunk { chunk_type: "PLTE" });
+        }
+
+        if !self.seen_type(b"IDAT") {
+            self.violations
+                .push(Violation::MissingChunk { chunk_type: "IDAT" });
+        }
+
+        if !self.seen_iend {
+            self.violations
+                .push(Violation::MissingChunk { chunk_type: "IEND" });
+        }
+
+        self.violations
+    }
+
+    fn seen_type(&self, bytes: &[u8; 4]) -> bool {
+        self.seen
+            .iter()
+            .any(|chunk_type| &chunk_type.bytes() == bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::Png;
+    use std::str::FromStr;
+
+    const PNG_FILE: &[u8] = include_bytes!("../tests/fixtures/dice.png");
+
+    /// The dice image with its chunks rearranged by `edit`.
+    fn edited_png<F: FnOnce(&mut Vec<Chunk>)>(edit: F) -> Png {
+        let mut chunks = Png::try_from(PNG_FILE).unwrap().chunks().to_vec();
+        edit(&mut chunks);
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_valid_png() {
+        let png = Png::try_from(PNG_FILE).unwrap();
+        assert_eq!(png.validate(), []);
+    }
+
+    #[test]
+    fn test_empty_png() {
+        let png = Png::from_chunks(Vec::new());
+        assert_eq!(
+            png.validate(),
+            [
+                Violation::MissingChunk { chunk_type: "IHDR" },
+                Violation::MissingChunk { chunk_type: "IDAT" },
+                Violation::MissingChunk { chunk_type: "IEND" },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ihdr_not_first() {
+        let png = edited_png(|chunks| chunks.swap(0, 1));
+        assert_eq!(
+            png.validate(),
+            [Violation::FirstChunkNotIhdr {
+                chunk_type: ChunkType::from_str("sRGB").unwrap()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let png = edited_png(|chunks| chunks[0] = Chunk::of_type("IHDR", Vec::new()));
+        assert!(matches!(
+            png.validate().as_slice(),
+            [Violation::InvalidHeader { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_chunk_after_iend() {
+        let png = edited_png(|chunks| chunks.push(Chunk::of_type("ruSt", Vec::new())));
+        assert_eq!(
+            png.validate(),
+            [Violation::ChunkAfterIend {
+                index: 7,
+                chunk_type: ChunkType::from_str("ruSt").unwrap()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_missing_iend() {
+        let png = edited_png(|chunks| {
+            chunks.pop();
+        });
+        assert_eq!(
+            png.validate(),
+            [Violation::MissingChunk { chunk_type: "IEND" }]
+        );
+    }
+
+    #[test]
+    fn test_idat_not_consecutive() {
+        let png = edited_png(|chunks| {
+            chunks.insert(5, Chunk::of_type("ruSt", Vec::new()));
+            chunks.insert(6, Chunk::of_type("IDAT", Vec::new()));
+        });
+        assert_eq!(png.validate(), [Violation::IdatNotConsecutive { index: 6 }]);
+    }
+
+    #[test]
+    fn test_consecutive_idats() {
+        let png = edited_png(|chunks| chunks.insert(5, Chunk::of_type("IDAT", Vec::new())));
+        assert_eq!(png.validate(), []);
+    }
+
+    #[test]
+    fn test_plte_after_idat() {
+        let png = edited_png(|chunks| chunks.insert(5, Chunk::of_type("PLTE", Vec::new())));
+        assert_eq!(png.validate(), [Violation::PlteAfterIdat { index: 5 }]);
+    }
+
+    #[test]
+    fn test_missing_plte_for_indexed_image() {
+        let png = edited_png(|chunks| {
+            let mut data = chunks[0].data().to_vec();
+            data[8] = 8;
+            data[9] = ColorType::Indexed as u8;
+            chunks[0] = Chunk::of_type("IHDR", data);
+        });
+        assert_eq!(
+            png.validate(),
+            [Violation::MissingChunk { chunk_type: "PLTE" }]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_singletons() {
+        let png = edited_png(|chunks| {
+            chunks.insert(3, Chunk::of_type("gAMA", Vec::new()));
+            chunks.insert(1, Chunk::of_type("IHDR", Vec::new()));
+        });
+        assert_eq!(
+            png.validate(),
+            [
+                Violation::DuplicateChunk {
+                    index: 1,
+                    chunk_type: ChunkType::from_str("IHDR").unwrap()
+                },
+                Violation::DuplicateChunk {
+                    index: 4,
+                    chunk_type: ChunkType::from_str("gAMA").unwrap()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_every_violation() {
+        let png = edited_png(|chunks| {
+            chunks.swap(0, 1);
+            chunks.push(Chunk::of_type("IDAT", Vec::new()));
+        });
+        assert_eq!(png.validate().len(), 3);
+    }
+}
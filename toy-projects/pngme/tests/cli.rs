@@ -0,0 +1,881 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const DICE_PNG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/dice.png");
+
+fn pngme(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(args)
+        .output()
+        .expect("failed to run pngme")
+}
+
+/// Copies the dice fixture into a fresh file under the test tmp dir so tests
+/// can modify it without stepping on each other.
+fn fixture_copy(name: &str) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::copy(DICE_PNG, &path).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+/// The chunk types `print` lists for `file`, in order.
+fn chunk_types(file: &str) -> Vec<String> {
+    stdout(&pngme(&["print", file]))
+        .lines()
+        .filter(|line| !line.starts_with(' '))
+        .map(|line| line.split_whitespace().next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_encode_then_decode() {
+    let file = fixture_copy("encode_then_decode.png");
+    let file = file.to_str().unwrap();
+
+    let output = pngme(&["encode", file, "ruSt", "This is a secret message!"]);
+    assert!(output.status.success());
+
+    let output = pngme(&["decode", file, "ruSt"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "This is a secret message!\n");
+}
+
+#[test]
+fn test_encode_keeps_iend_last() {
+    let file = fixture_copy("encode_keeps_iend_last.png");
+    let file = file.to_str().unwrap();
+
+    assert!(pngme(&["encode", file, "ruSt", "message"]).status.success());
+
+    let output = pngme(&["print", file]);
+    let stdout = stdout(&output);
+    let last_two: Vec<&str> = stdout.lines().rev().take(2).collect();
+    assert!(last_two[0].starts_with("IEND"));
+    assert!(last_two[1].starts_with("ruSt"));
+}
+
+#[test]
+fn test_encode_decode_encrypted() {
+    let file = fixture_copy("encode_decode_encrypted.png");
+    let file = file.to_str().unwrap();
+
+    let output = pngme(&[
+        "encode",
+        file,
+        "ruSt",
+        "This is a secret message!",
+        "--passphrase",
+        "hunter2",
+    ]);
+    assert!(output.status.success());
+
+    // The message isn't stored in the clear.
+    let bytes = fs::read(file).unwrap();
+    assert!(!bytes.windows(6).any(|window| window == b"secret"));
+
+    let output = pngme(&["decode", file, "ruSt", "--passphrase", "hunter2"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "This is a secret message!\n");
+
+    let output = pngme(&["decode", file, "ruSt", "--passphrase", "hunter3"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Wrong passphrase"));
+
+    let output = pngme(&["decode", file, "ruSt"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs a passphrase"));
+}
+
+#[test]
+fn test_encode_to_output_file() {
+    let input = fixture_copy("encode_output_input.png");
+    let output_file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("encode_output_output.png");
+    let _ = fs::remove_file(&output_file);
+
+    let output = pngme(&[
+        "encode",
+        input.to_str().unwrap(),
+        "ruSt",
+        "Hidden",
+        output_file.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+
+    // The input file is left untouched.
+    assert_eq!(fs::read(&input).unwrap(), fs::read(DICE_PNG).unwrap());
+
+    let output = pngme(&["decode", output_file.to_str().unwrap(), "ruSt"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Hidden\n");
+}
+
+#[test]
+fn test_encode_invalid_chunk_type() {
+    let file = fixture_copy("encode_invalid_chunk_type.png");
+
+    let output = pngme(&["encode", file.to_str().unwrap(), "Rust", "message"]);
+    assert!(!output.status.success());
+    assert_eq!(fs::read(&file).unwrap(), fs::read(DICE_PNG).unwrap());
+}
+
+#[test]
+fn test_decode_missing_chunk() {
+    let output = pngme(&["decode", DICE_PNG, "ruSt"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_remove() {
+    let file = fixture_copy("remove.png");
+    let file = file.to_str().unwrap();
+
+    assert!(pngme(&["encode", file, "ruSt", "Remove me"])
+        .status
+        .success());
+
+    let output = pngme(&["remove", file, "ruSt"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Removed chunk ruSt\n");
+
+    assert!(!pngme(&["decode", file, "ruSt"]).status.success());
+    assert_eq!(fs::read(file).unwrap(), fs::read(DICE_PNG).unwrap());
+}
+
+#[test]
+fn test_remove_missing_chunk() {
+    let file = fixture_copy("remove_missing_chunk.png");
+
+    let output = pngme(&["remove", file.to_str().unwrap(), "ruSt"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_print() {
+    assert!(pngme(&["print", DICE_PNG]).status.success());
+    assert_eq!(
+        chunk_types(DICE_PNG),
+        ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "RuSt", "IEND"]
+    );
+}
+
+#[test]
+fn test_print_header() {
+    let output = pngme(&["print", DICE_PNG]);
+    let stdout = stdout(&output);
+    let mut lines = stdout.lines();
+
+    assert_eq!(lines.next(), Some("IHDR (13 bytes)"));
+    assert_eq!(lines.next(), Some("    50x50, 8-bit RGBA, not interlaced"));
+}
+
+#[test]
+fn test_print_ancillary_chunks() {
+    let output = pngme(&["print", DICE_PNG]);
+    let stdout = stdout(&output);
+
+    assert!(stdout.contains("sRGB (1 bytes)\n    sRGB, perceptual rendering intent\n"));
+    assert!(stdout.contains("gAMA (4 bytes)\n    gamma 0.45455\n"));
+    assert!(stdout.contains("pHYs (9 bytes)\n    3778x3778 pixels per meter (96x96 DPI)\n"));
+}
+
+#[test]
+fn test_validate() {
+    let output = pngme(&["validate", DICE_PNG]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "OK\n");
+}
+
+#[test]
+fn test_validate_reports_every_violation() {
+    // Copy the IHDR chunk to the end of the file, after IEND.
+    let mut bytes = fs::read(DICE_PNG).unwrap();
+    let ihdr = bytes[8..33].to_vec();
+    bytes.extend(ihdr);
+
+    let file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("validate_violations.png");
+    fs::write(&file, bytes).unwrap();
+
+    let output = pngme(&["validate", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Chunk 7 (IHDR) comes after IEND\nChunk 7 (IHDR) appears more than once\n"
+    );
+}
+
+#[test]
+fn test_text_set_get_list_delete() {
+    let file = fixture_copy("text.png");
+    let file = file.to_str().unwrap();
+
+    assert!(pngme(&["text", "set", file, "Author", "Dolpheyn"])
+        .status
+        .success());
+    assert!(
+        pngme(&["text", "set", file, "Comment", "dice", "--compress"])
+            .status
+            .success()
+    );
+    assert!(
+        pngme(&["text", "set", file, "Title", "Dadu", "--lang", "ms"])
+            .status
+            .success()
+    );
+
+    let output = pngme(&["text", "get", file, "Comment"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "dice\n");
+
+    let output = pngme(&["text", "list", file]);
+    assert_eq!(
+        stdout(&output),
+        "Author (tEXt): Dolpheyn\nComment (zTXt): dice\nTitle (iTXt): Dadu\n"
+    );
+
+    // Setting an existing keyword replaces it.
+    assert!(pngme(&["text", "set", file, "Author", "Someone else"])
+        .status
+        .success());
+    let output = pngme(&["text", "list", file]);
+    assert_eq!(
+        stdout(&output),
+        "Comment (zTXt): dice\nTitle (iTXt): Dadu\nAuthor (tEXt): Someone else\n"
+    );
+
+    let output = pngme(&["text", "delete", file, "Author"]);
+    assert!(output.status.success());
+    assert!(!pngme(&["text", "get", file, "Author"]).status.success());
+    assert!(!pngme(&["text", "delete", file, "Author"]).status.success());
+
+    assert!(pngme(&["validate", file]).status.success());
+}
+
+#[test]
+fn test_text_set_invalid_keyword() {
+    let file = fixture_copy("text_invalid_keyword.png");
+
+    let output = pngme(&["text", "set", file.to_str().unwrap(), " Author", "x"]);
+    assert!(!output.status.success());
+    assert_eq!(fs::read(&file).unwrap(), fs::read(DICE_PNG).unwrap());
+}
+
+#[test]
+fn test_invalid_png() {
+    let file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("not_a_png.png");
+    fs::write(&file, b"definitely not a png file").unwrap();
+
+    let output = pngme(&["print", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_stego_capacity() {
+    let output = pngme(&["stego", "capacity", DICE_PNG]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "1242 bytes\n");
+}
+
+#[test]
+fn test_stego_hide_then_reveal() {
+    let file = fixture_copy("stego_hide_then_reveal.png");
+    let file = file.to_str().unwrap();
+
+    let output = pngme(&["stego", "hide", file, "This is a secret message!"]);
+    assert!(output.status.success());
+
+    // No new chunks, and the file is still a valid PNG.
+    assert_eq!(chunk_types(file), chunk_types(DICE_PNG));
+    assert_eq!(stdout(&pngme(&["validate", file])), "OK\n");
+
+    let output = pngme(&["stego", "reveal", file]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "This is a secret message!\n");
+}
+
+#[test]
+fn test_stego_encrypted() {
+    let file = fixture_copy("stego_encrypted.png");
+    let file = file.to_str().unwrap();
+
+    let output = pngme(&["stego", "hide", file, "secret", "--passphrase", "hunter2"]);
+    assert!(output.status.success());
+
+    let output = pngme(&["stego", "reveal", file, "--passphrase", "hunter2"]);
+    assert_eq!(stdout(&output), "secret\n");
+
+    assert!(!pngme(&["stego", "reveal", file]).status.success());
+}
+
+#[test]
+fn test_stego_message_too_large() {
+    let file = fixture_copy("stego_message_too_large.png");
+    let file = file.to_str().unwrap();
+    let message = "x".repeat(1243);
+
+    let output = pngme(&["stego", "hide", file, &message]);
+    assert!(!output.status.success());
+    assert_eq!(fs::read(file).unwrap(), fs::read(DICE_PNG).unwrap());
+}
+
+#[test]
+fn test_stego_reveal_without_message() {
+    let output = pngme(&["stego", "reveal", DICE_PNG]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_stego_reveal_huge_image() {
+    let huge = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/huge.png");
+    let output = pngme(&["stego", "reveal", huge]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("image too large"));
+}
+
+#[test]
+fn test_encode_decode_file_in_parts() {
+    let file = fixture_copy("encode_decode_file_in_parts.png");
+    let file = file.to_str().unwrap();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let payload: Vec<u8> = (0..10_000u32).map(|i| (i * 31 % 256) as u8).collect();
+    let payload_path = dir.join("encode_decode_file_in_parts.bin");
+    let decoded_path = dir.join("encode_decode_file_in_parts.out");
+    fs::write(&payload_path, &payload).unwrap();
+
+    let output = pngme(&[
+        "encode",
+        file,
+        "ruSt",
+        payload_path.to_str().unwrap(),
+        "--file",
+        "--part-size",
+        "4096",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(chunk_types(file).iter().filter(|t| *t == "ruSt").count(), 3);
+    assert_eq!(stdout(&pngme(&["validate", file])), "OK\n");
+
+    let output = pngme(&["decode", file, "ruSt", "-o", decoded_path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(&decoded_path).unwrap(), payload);
+
+    let output = pngme(&["remove", file, "ruSt"]);
+    assert_eq!(stdout(&output), "Removed 3 ruSt chunks\n");
+    assert_eq!(chunk_types(file), chunk_types(DICE_PNG));
+}
+
+#[test]
+fn test_decode_detects_missing_part() {
+    let file = fixture_copy("decode_detects_missing_part.png");
+    let file = file.to_str().unwrap();
+    let message = "x".repeat(100);
+
+    let output = pngme(&["encode", file, "ruSt", &message, "--part-size", "40"]);
+    assert!(output.status.success());
+
+    // Drop the middle part by hand.
+    let bytes = fs::read(file).unwrap();
+    let part = b"ruStpmPT\0\0\0\x01";
+    let start = bytes.windows(part.len()).position(|w| w == part).unwrap() - 4;
+    let end = start + 12 + 16 + 40;
+    let bytes: Vec<u8> = [&bytes[..start], &bytes[end..]].concat();
+    fs::write(file, bytes).unwrap();
+
+    let output = pngme(&["decode", file, "ruSt"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing part(s) [1] of 3"));
+}
+
+const ANIMATED_PNG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/animated.png");
+
+#[test]
+fn test_frames_list() {
+    let output = pngme(&["frames", "list", ANIMATED_PNG]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "3 frame(s), loops forever\n\
+         Frame 0: 16x16 at (0, 0), 100 ms, dispose none, blend source (default image)\n\
+         Frame 1: 16x16 at (0, 0), 100 ms, dispose background, blend source\n\
+         Frame 2: 8x8 at (4, 4), 250 ms, dispose none, blend over\n"
+    );
+}
+
+#[test]
+fn test_frames_list_still_image() {
+    let output = pngme(&["frames", "list", DICE_PNG]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_frames_extract() {
+    let output_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("frames_extract.png");
+    let output_path = output_path.to_str().unwrap();
+
+    let output = pngme(&["frames", "extract", ANIMATED_PNG, "2", output_path]);
+    assert!(output.status.success());
+    assert_eq!(chunk_types(output_path), ["IHDR", "IDAT", "IEND"]);
+    assert_eq!(stdout(&pngme(&["validate", output_path])), "OK\n");
+
+    let output = pngme(&["frames", "extract", ANIMATED_PNG, "3", output_path]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_frames_drop() {
+    let file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("frames_drop.png");
+    fs::copy(ANIMATED_PNG, &file).unwrap();
+    let file = file.to_str().unwrap();
+
+    let output = pngme(&["frames", "drop", file, "1"]);
+    assert!(output.status.success());
+
+    let output = pngme(&["frames", "list", file]);
+    let stdout = stdout(&output);
+    assert!(stdout.starts_with("2 frame(s)"));
+    assert!(stdout.contains("Frame 1: 8x8"));
+}
+
+#[test]
+fn test_print_animation_chunks() {
+    let output = pngme(&["print", ANIMATED_PNG]);
+    assert!(stdout(&output).contains("acTL (8 bytes)\n    3 frame(s), loops forever\n"));
+}
+
+#[test]
+fn test_diff() {
+    let file = fixture_copy("diff.png");
+    let file = file.to_str().unwrap();
+    assert!(pngme(&["encode", file, "ruSt", "message"]).status.success());
+
+    let output = pngme(&["diff", DICE_PNG, file]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "+ ruSt added at 6\n");
+
+    let output = pngme(&["diff", DICE_PNG, DICE_PNG]);
+    assert_eq!(stdout(&output), "No differences\n");
+}
+
+#[test]
+fn test_diff_json() {
+    let file = fixture_copy("diff_json.png");
+    let file = file.to_str().unwrap();
+    assert!(pngme(&["remove", file, "gAMA"]).status.success());
+
+    let output = pngme(&["diff", DICE_PNG, file, "--format", "json"]);
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "changes": [{"change": "removed", "chunk_type": "gAMA", "index": 2}]
+        })
+    );
+}
+
+#[test]
+fn test_sanitize() {
+    let file = fixture_copy("sanitize.png");
+    let file = file.to_str().unwrap();
+    assert!(pngme(&["text", "set", file, "Author", "Someone"])
+        .status
+        .success());
+    assert!(pngme(&["encode", file, "ruSt", "secret"]).status.success());
+
+    let output = pngme(&["sanitize", file]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Removed RuSt (3 bytes, private)\n\
+         Removed tEXt (14 bytes, metadata)\n\
+         Removed ruSt (6 bytes, private)\n\
+         Removed 3 chunk(s)\n"
+    );
+    assert_eq!(
+        chunk_types(file),
+        ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "IEND"]
+    );
+    assert_eq!(stdout(&pngme(&["validate", file])), "OK\n");
+}
+
+#[test]
+fn test_sanitize_keep_and_strip() {
+    let file = fixture_copy("sanitize_keep_and_strip.png");
+    let file = file.to_str().unwrap();
+
+    let output = pngme(&[
+        "sanitize", file, "--keep", "RuSt", "--strip", "gAMA", "--strip", "IDAT",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        chunk_types(file),
+        ["IHDR", "sRGB", "pHYs", "IDAT", "RuSt", "IEND"]
+    );
+}
+
+/// A fresh directory under the test tmp dir holding `dice.png`,
+/// `nested/dice.png` and `nested/animated.png`.
+fn batch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::copy(DICE_PNG, dir.join("dice.png")).unwrap();
+    fs::copy(DICE_PNG, dir.join("nested/dice.png")).unwrap();
+    fs::copy(ANIMATED_PNG, dir.join("nested/animated.png")).unwrap();
+    dir
+}
+
+#[test]
+fn test_batch_encode_in_place() {
+    let dir = batch_dir("batch_encode_in_place");
+    let dir_str = dir.to_str().unwrap();
+
+    let output = pngme(&["encode", dir_str, "ruSt", "batch", "--jobs", "2"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("3 succeeded, 0 failed\n"));
+
+    for file in &["dice.png", "nested/dice.png", "nested/animated.png"] {
+        let file = dir.join(file);
+        let output = pngme(&["decode", file.to_str().unwrap(), "ruSt"]);
+        assert_eq!(stdout(&output), "batch\n");
+    }
+}
+
+#[test]
+fn test_batch_sanitize_to_out_dir() {
+    let dir = batch_dir("batch_sanitize");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("batch_sanitize_out");
+    let _ = fs::remove_dir_all(&out_dir);
+    let pattern = format!("{}/*/*.png", dir.to_str().unwrap());
+
+    let output = pngme(&["sanitize", &pattern, "--out-dir", out_dir.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    // The inputs are left alone and the outputs mirror the directories below
+    // the pattern's fixed part.
+    assert_eq!(
+        chunk_types(dir.join("nested/dice.png").to_str().unwrap()).len(),
+        7
+    );
+    assert_eq!(
+        chunk_types(out_dir.join("nested/dice.png").to_str().unwrap()),
+        ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "IEND"]
+    );
+    assert!(out_dir.join("nested/animated.png").exists());
+    assert!(!out_dir.join("dice.png").exists());
+}
+
+#[test]
+fn test_batch_reports_failures() {
+    let dir = batch_dir("batch_reports_failures");
+    let dice = dir.join("dice.png");
+    fs::write(dir.join("nested/broken.png"), b"not a png").unwrap();
+    assert!(
+        pngme(&["encode", dice.to_str().unwrap(), "ruSt", "message"])
+            .status
+            .success()
+    );
+
+    let output = pngme(&["remove", dir.to_str().unwrap(), "ruSt"]);
+    assert!(!output.status.success());
+
+    let stdout = stdout(&output);
+    assert!(stdout.contains("dice.png: ok\n    Removed chunk ruSt\n"));
+    assert!(stdout.contains("broken.png: failed: "));
+    assert!(stdout.contains("animated.png: failed: Chunk ruSt does not exist\n"));
+    assert!(stdout.ends_with("1 succeeded, 3 failed\n"));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: 3 of 4 file(s) failed\n"
+    );
+}
+
+#[test]
+fn test_batch_print_glob() {
+    let dir = batch_dir("batch_print_glob");
+    let pattern = format!("{}/**/a*.png", dir.to_str().unwrap());
+
+    let output = pngme(&["print", &pattern]);
+    assert!(output.status.success());
+
+    let stdout = stdout(&output);
+    assert!(stdout.starts_with(&format!(
+        "{}: ok\n    IHDR (13 bytes)\n",
+        dir.join("nested/animated.png").display()
+    )));
+    assert!(stdout.ends_with("1 succeeded, 0 failed\n"));
+}
+
+#[test]
+fn test_batch_output_file_needs_single_input() {
+    let dir = batch_dir("batch_output_file");
+    let output = pngme(&["sanitize", dir.to_str().unwrap(), "out.png"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_print_json() {
+    let output = pngme(&["print", DICE_PNG, "--format", "json", "--data", "hex"]);
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let chunks = json["chunks"].as_array().unwrap();
+    assert_eq!(chunks.len(), 7);
+    assert_eq!(
+        chunks[0],
+        serde_json::json!({
+            "index": 0,
+            "offset": 8,
+            "chunk_type": "IHDR",
+            "critical": true,
+            "public": true,
+            "safe_to_copy": false,
+            "length": 13,
+            "crc": chunks[0]["crc"],
+            "data": "00000032000000320806000000",
+        })
+    );
+    assert_eq!(chunks[1]["offset"], 8 + 12 + 13);
+    assert_eq!(chunks[5]["chunk_type"], "RuSt");
+    assert_eq!(chunks[5]["public"], false);
+}
+
+#[test]
+fn test_print_json_without_data() {
+    let output = pngme(&["print", DICE_PNG, "--format", "json"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert!(json["chunks"][0].get("data").is_none());
+}
+
+#[test]
+fn test_decode_json() {
+    let file = fixture_copy("decode_json.png");
+    let file = file.to_str().unwrap();
+    assert!(pngme(&["encode", file, "ruSt", "Hi!"]).status.success());
+
+    let output = pngme(&[
+        "decode", file, "ruSt", "--format", "json", "--data", "base64",
+    ]);
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["chunk_type"], "ruSt");
+    assert_eq!(json["message"], "Hi!");
+    assert_eq!(json["length"], 3);
+    assert_eq!(json["data"], "SGkh");
+    assert_eq!(json["chunks"][0]["index"], 6);
+    assert_eq!(json["chunks"][0]["length"], 3);
+}
+
+#[test]
+fn test_batch_print_json() {
+    let dir = batch_dir("batch_print_json");
+    fs::write(dir.join("broken.png"), b"not a png").unwrap();
+
+    let output = pngme(&["print", dir.to_str().unwrap(), "--format", "json"]);
+    assert!(!output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["succeeded"], 3);
+    assert_eq!(json["failed"], 1);
+
+    let files = json["files"].as_array().unwrap();
+    assert!(files[0]["file"].as_str().unwrap().ends_with("broken.png"));
+    assert!(files[0]["error"].is_string());
+    assert_eq!(files[1]["result"]["chunks"].as_array().unwrap().len(), 7);
+}
+
+#[test]
+fn test_repair() {
+    let file = fixture_copy("repair.png");
+    let output_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("repair_fixed.png");
+    let output_path = output_path.to_str().unwrap();
+
+    // Flip a byte of the RuSt chunk's data and add junk after IEND.
+    let mut bytes = fs::read(&file).unwrap();
+    let rust_data = bytes.len() - 12 - 7;
+    bytes[rust_data] ^= 0xff;
+    bytes.extend_from_slice(b"junk");
+    fs::write(&file, &bytes).unwrap();
+    assert!(!pngme(&["print", file.to_str().unwrap()]).status.success());
+
+    let output = pngme(&["repair", file.to_str().unwrap(), output_path, "--fix-crc"]);
+    assert!(output.status.success());
+
+    let report = stdout(&output);
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with(&format!(
+        "Fixed RuSt chunk at offset {} with a bad checksum",
+        rust_data - 8
+    )));
+    assert_eq!(
+        lines[1],
+        format!("Dropped 4 byte(s) after IEND at offset {}", bytes.len() - 4)
+    );
+    assert_eq!(lines[2], "Recovered 7 chunk(s), made 2 fix(es)");
+    assert_eq!(stdout(&pngme(&["validate", output_path])), "OK\n");
+}
+
+#[test]
+fn test_repair_unreadable() {
+    let file = Path::new(env!("CARGO_TARGET_TMPDIR")).join("repair_unreadable.png");
+    fs::write(&file, b"not a png").unwrap();
+
+    let output = pngme(&[
+        "repair",
+        file.to_str().unwrap(),
+        "repair_unreadable_out.png",
+    ]);
+    assert!(!output.status.success());
+}
+
+/// Generates a fresh key pair under the test tmp dir and returns the paths
+/// of the signing key and the public key.
+fn key_pair(name: &str) -> (String, String) {
+    let key = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let public = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.pub", name));
+    let _ = fs::remove_file(&key);
+    let _ = fs::remove_file(&public);
+
+    assert!(pngme(&["keygen", key.to_str().unwrap()]).status.success());
+    (
+        key.to_str().unwrap().to_string(),
+        public.to_str().unwrap().to_string(),
+    )
+}
+
+#[test]
+fn test_sign_and_verify() {
+    let (key, public) = key_pair("sign_and_verify.key");
+    let file = fixture_copy("sign_and_verify.png");
+    let file = file.to_str().unwrap();
+
+    assert!(pngme(&["sign", file, "--key", &key]).status.success());
+    assert_eq!(chunk_types(file).last().unwrap(), "IEND");
+    assert!(chunk_types(file).contains(&"sgNt".to_string()));
+
+    let output = pngme(&["verify", file, "--key", &public]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "OK\n");
+}
+
+#[test]
+fn test_verify_fails_after_edit() {
+    let (key, public) = key_pair("verify_after_edit.key");
+    let file = fixture_copy("verify_after_edit.png");
+    let file = file.to_str().unwrap();
+    assert!(pngme(&["sign", file, "--key", &key]).status.success());
+
+    assert!(pngme(&["remove", file, "RuSt"]).status.success());
+    let output = pngme(&["verify", file, "--key", &public]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("changed after it was signed"));
+}
+
+#[test]
+fn test_verify_with_other_key() {
+    let (key, _) = key_pair("verify_other_key.key");
+    let (_, other_public) = key_pair("verify_other_key_2.key");
+    let file = fixture_copy("verify_other_key.png");
+    let file = file.to_str().unwrap();
+    assert!(pngme(&["sign", file, "--key", &key]).status.success());
+
+    let output = pngme(&["verify", file, "--key", &other_public]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("signed by a different key"));
+}
+
+#[test]
+fn test_verify_unsigned() {
+    let (_, public) = key_pair("verify_unsigned.key");
+    assert!(!pngme(&["verify", DICE_PNG, "--key", &public])
+        .status
+        .success());
+}
+
+#[test]
+fn test_keygen_keeps_existing_key() {
+    let (key, _) = key_pair("keygen_existing.key");
+    let before = fs::read_to_string(&key).unwrap();
+
+    assert!(!pngme(&["keygen", &key]).status.success());
+    assert_eq!(fs::read_to_string(&key).unwrap(), before);
+}
+
+const TINY_WEBP: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tiny.webp");
+
+fn webp_copy(name: &str) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::copy(TINY_WEBP, &path).unwrap();
+    path
+}
+
+#[test]
+fn test_webp_encode_decode_remove() {
+    let file = webp_copy("webp_encode_decode_remove.webp");
+    let file = file.to_str().unwrap();
+
+    let output = pngme(&["encode", file, "ruSt", "Hidden in a WebP"]);
+    assert!(output.status.success());
+    assert_eq!(chunk_types(file), ["VP8L", "ruSt"]);
+
+    let output = pngme(&["decode", file, "ruSt"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Hidden in a WebP\n");
+
+    let output = pngme(&["remove", file, "ruSt"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Removed chunk ruSt\n");
+    assert_eq!(fs::read(file).unwrap(), fs::read(TINY_WEBP).unwrap());
+}
+
+#[test]
+fn test_webp_split_encrypted_message() {
+    let file = webp_copy("webp_split_encrypted.webp");
+    let file = file.to_str().unwrap();
+
+    let output = pngme(&[
+        "encode",
+        file,
+        "XMP ",
+        "A longer message that needs more than one chunk",
+        "--part-size",
+        "32",
+        "--passphrase",
+        "hunter2",
+    ]);
+    assert!(output.status.success());
+    assert!(chunk_types(file).len() > 2);
+
+    let output = pngme(&["decode", file, "XMP ", "--passphrase", "hunter2"]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "A longer message that needs more than one chunk\n"
+    );
+
+    let output = pngme(&["remove", file, "XMP "]);
+    assert!(output.status.success());
+    assert_eq!(chunk_types(file), ["VP8L"]);
+}
+
+#[test]
+fn test_webp_print_json() {
+    let output = pngme(&["print", TINY_WEBP, "--format", "json", "--data", "hex"]);
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let chunk = &json["chunks"][0];
+    assert_eq!(chunk["chunk_type"], "VP8L");
+    assert_eq!(chunk["offset"], 12);
+    assert_eq!(chunk["length"], 13);
+    assert!(chunk.get("crc").is_none());
+}